@@ -6,14 +6,12 @@ use crate::session::auth::{self, CredentialsTestResult};
 use nasomail_shared::payload::auth::AuthPayload;
 
 pub async fn login(name: String, passphrase: String) -> anyhow::Result<ExitCode> {
-    auth::set_credentials(&AuthPayload {
+    let result = auth::log_in(&AuthPayload {
         username: name.clone(),
-        passphrase: passphrase,
+        passphrase,
     })
     .await?;
 
-    let result = auth::try_credentials().await?;
-
     Ok(if result == CredentialsTestResult::Success {
         println!(
             "{}{}",
@@ -26,8 +24,6 @@ pub async fn login(name: String, passphrase: String) -> anyhow::Result<ExitCode>
 
         ExitCode::SUCCESS
     } else {
-        auth::remove_credentials().await?;
-
         println!(
             "{}{}",
             "Error".bright_red().bold(),
@@ -4,6 +4,14 @@ use std::process::ExitCode;
 use crate::session::auth;
 
 pub async fn logout() -> anyhow::Result<ExitCode> {
+    if let Err(e) = auth::revoke_credentials().await {
+        println!(
+            "{}{}",
+            "Warning".bright_yellow().bold(),
+            format!(": Could not revoke session on server{}", format!(": {}", e))
+        );
+    }
+
     auth::remove_credentials().await?;
 
     println!("{}{}", "Success".bright_green().bold(), ": Logged out");
@@ -2,12 +2,13 @@ mod connect;
 mod disconnect;
 mod login;
 mod logout;
+mod register;
+mod send;
+mod status;
 
 use std::process::ExitCode;
 
-use crate::session::connection;
 use clap::{Parser, Subcommand};
-use colored::Colorize;
 
 /// A simple client application for communicating through a NasoMail server
 #[derive(Parser)]
@@ -34,6 +35,18 @@ pub enum Commands {
     /// Log out of the current user account
     LogOut,
 
+    /// Create a new user account on the
+    /// currently connected server
+    Register {
+        /// The name of the user account to create
+        #[arg(short, long)]
+        name: String,
+
+        /// The passphrase of the user account to create
+        #[arg(short, long)]
+        passphrase: String,
+    },
+
     /// Connect to the specified server
     Connect {
         /// The address of the server to connect to
@@ -42,6 +55,25 @@ pub enum Commands {
 
     /// Disconnect from the currently connected server
     Disconnect,
+
+    /// Send a mail as the current user account
+    Send {
+        /// The name of the user to send the mail to, or `user@host:port` for a user of another server
+        #[arg(short, long)]
+        recipient: String,
+
+        /// The subject of the mail
+        #[arg(short, long)]
+        subject: String,
+
+        /// The body of the mail
+        #[arg(short, long)]
+        body: String,
+    },
+
+    /// Check whether the saved session is still
+    /// valid on the currently connected server
+    Status,
 }
 
 impl Cli {
@@ -51,8 +83,15 @@ impl Cli {
         Ok(match self.command {
             Commands::LogIn { name, passphrase } => login::login(name, passphrase).await?,
             Commands::LogOut => logout::logout().await?,
+            Commands::Register { name, passphrase } => register::register(name, passphrase).await?,
             Commands::Connect { addr } => connect::connect(addr).await?,
             Commands::Disconnect => disconnect::disconnect().await?,
+            Commands::Send {
+                recipient,
+                subject,
+                body,
+            } => send::send(recipient, subject, body).await?,
+            Commands::Status => status::status().await?,
         })
     }
 }
@@ -0,0 +1,81 @@
+use colored::Colorize;
+use reqwest::StatusCode;
+use std::process::ExitCode;
+
+use crate::session::connection;
+
+use nasomail_shared::{
+    api,
+    payload::{IdPayload, auth::AuthPayload, error::ErrorPayload},
+};
+
+pub async fn register(name: String, passphrase: String) -> anyhow::Result<ExitCode> {
+    let Some(connection) = connection::get_connection().await? else {
+        println!(
+            "{}{}",
+            "Error".bright_red().bold(),
+            ": No server is currently connected"
+        );
+
+        return Ok(ExitCode::FAILURE);
+    };
+
+    let response = reqwest::Client::new()
+        .post(format!(
+            "http://{}{}",
+            connection,
+            api::api_users_register_absolute()
+        ))
+        .json(&AuthPayload {
+            username: name.clone(),
+            passphrase,
+        })
+        .send()
+        .await?;
+
+    Ok(match response.status() {
+        StatusCode::CREATED => {
+            let payload = response.json::<IdPayload>().await?;
+
+            println!(
+                "{}{}",
+                "Success".bright_green().bold(),
+                format!(
+                    ": Registered user{}",
+                    format!(": {} (#{})", name.trim(), payload.id)
+                        .bright_blue()
+                        .bold()
+                )
+            );
+
+            ExitCode::SUCCESS
+        }
+        StatusCode::CONFLICT | StatusCode::UNPROCESSABLE_ENTITY => {
+            let payload = response.json::<ErrorPayload>().await?;
+
+            println!(
+                "{}{}",
+                "Error".bright_red().bold(),
+                format!(": Failed to register{}", format!(": {}", payload.error))
+            );
+            for field in payload.fields {
+                println!(
+                    "  {}{}",
+                    field.field.bright_blue().bold(),
+                    format!(": {:?}", field.kind)
+                );
+            }
+
+            ExitCode::FAILURE
+        }
+        status => {
+            println!(
+                "{}{}",
+                "Error".bright_red().bold(),
+                format!(": Failed to register{}", format!(": {}", status))
+            );
+
+            ExitCode::FAILURE
+        }
+    })
+}
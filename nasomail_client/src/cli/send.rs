@@ -0,0 +1,76 @@
+use colored::Colorize;
+use reqwest::{Method, StatusCode};
+use std::process::ExitCode;
+
+use crate::session::auth;
+
+use nasomail_shared::{
+    api,
+    payload::{IdPayload, error::ErrorPayload, mail::SendMailPayload},
+};
+
+pub async fn send(recipient: String, subject: String, body: String) -> anyhow::Result<ExitCode> {
+    let response = auth::authorized(Method::POST, &api::api_mails_absolute())
+        .await?
+        .json(&SendMailPayload {
+            recipient: recipient.clone(),
+            subject,
+            body,
+            send_at: None,
+        })
+        .send()
+        .await?;
+
+    Ok(match response.status() {
+        status @ (StatusCode::CREATED | StatusCode::ACCEPTED) => {
+            let payload = response.json::<IdPayload>().await?;
+
+            let verb = if status == StatusCode::ACCEPTED {
+                "Queued"
+            } else {
+                "Sent"
+            };
+
+            println!(
+                "{}{}",
+                "Success".bright_green().bold(),
+                format!(
+                    ": {} mail to{}",
+                    verb,
+                    format!(": {} (#{})", recipient.trim(), payload.id)
+                        .bright_blue()
+                        .bold()
+                )
+            );
+
+            ExitCode::SUCCESS
+        }
+        StatusCode::UNPROCESSABLE_ENTITY => {
+            let payload = response.json::<ErrorPayload>().await?;
+
+            println!(
+                "{}{}",
+                "Error".bright_red().bold(),
+                format!(": Failed to send mail{}", format!(": {}", payload.error))
+            );
+            for field in payload.fields {
+                println!(
+                    "  {}{}",
+                    field.field.bright_blue().bold(),
+                    format!(": {:?}", field.kind)
+                );
+            }
+
+            ExitCode::FAILURE
+        }
+        status => {
+            println!(
+                "{}{}",
+                "Error".bright_red().bold(),
+                format!(": Failed to send mail{}", format!(": {}", status))
+            );
+
+            ExitCode::FAILURE
+        }
+    })
+}
@@ -0,0 +1,29 @@
+use colored::Colorize;
+use std::process::ExitCode;
+
+use crate::session::auth::{self, CredentialsTestResult};
+
+pub async fn status() -> anyhow::Result<ExitCode> {
+    let result = auth::try_credentials().await?;
+
+    Ok(if result == CredentialsTestResult::Success {
+        println!(
+            "{}{}",
+            "Success".bright_green().bold(),
+            ": Logged in and connected"
+        );
+
+        ExitCode::SUCCESS
+    } else {
+        println!(
+            "{}{}",
+            "Warning".bright_yellow().bold(),
+            format!(
+                ": Not logged in{}",
+                format!(": {:?}", result).bright_blue().bold()
+            )
+        );
+
+        ExitCode::FAILURE
+    })
+}
@@ -10,6 +10,6 @@ use cli::Cli;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<ExitCode> {
-    let cli = task::spawn_blocking(|| Cli::parse()).await?;
+    let cli = task::spawn_blocking(Cli::parse).await?;
     cli.run().await
 }
@@ -1,4 +1,7 @@
 //! A utility for storing the user's credentials.
+//!
+//! The only credential stored on disk is the bearer token
+//! issued by the server when logging in, never the passphrase.
 
 use tokio::{
     fs::{self, File},
@@ -11,12 +14,9 @@ use crate::{
 };
 use nasomail_shared::{
     api,
-    payload::{
-        BoolPayload,
-        auth::{AuthPayload, PassOnlyAuthPayload},
-    },
-    query::user::UserQuery,
+    payload::auth::{AuthPayload, SessionPayload},
 };
+use reqwest::StatusCode;
 
 /// A custom error type for I/O-related
 /// errors in credentials management.
@@ -67,8 +67,8 @@ pub enum CredentialsTestResult {
     NoConnection,
 }
 
-/// Writes an `AuthPayload` containing
-/// the user's credentials as JSON.
+/// Writes a `SessionPayload` containing
+/// the user's bearer token as JSON.
 ///
 /// # Errors
 ///
@@ -78,38 +78,38 @@ pub enum CredentialsTestResult {
 /// Returns `Err(SerError)`  if `serde_json::to_string_pretty` fails.
 /// Returns `Err(RwError)`   if `File::write_all` fails.
 ///
-pub async fn set_credentials(payload: &AuthPayload) -> anyhow::Result<(), CredentialsIoError> {
+pub async fn set_credentials(payload: &SessionPayload) -> anyhow::Result<(), CredentialsIoError> {
     let path = meta::credentials_path();
 
     if let Some(parent) = path.parent()
         && !fs::try_exists(&parent)
             .await
-            .map_err(|e| CredentialsIoError::DirError(e))?
+            .map_err(CredentialsIoError::DirError)?
     {
         fs::create_dir_all(parent)
             .await
-            .map_err(|e| CredentialsIoError::DirError(e))?;
+            .map_err(CredentialsIoError::DirError)?;
     }
 
     let mut file = File::create(path)
         .await
-        .map_err(|e| CredentialsIoError::FileError(e))?;
+        .map_err(CredentialsIoError::FileError)?;
 
     let payload_json =
-        serde_json::to_string_pretty(payload).map_err(|e| CredentialsIoError::SerError(e))?;
+        serde_json::to_string_pretty(payload).map_err(CredentialsIoError::SerError)?;
 
     file.write_all(payload_json.as_bytes())
         .await
-        .map_err(|e| CredentialsIoError::RwError(e))?;
+        .map_err(CredentialsIoError::RwError)?;
 
     Ok(())
 }
 
 /// Reads the user's credentials as JSON
-/// and returns it as an `AuthPayload`.
+/// and returns it as a `SessionPayload`.
 ///
-/// Returns `Ok(Some(AuthPayload))` if there are saved credentials.
-/// Returns `Ok(None)`              if there are no saved credentials.
+/// Returns `Ok(Some(SessionPayload))` if there are saved credentials.
+/// Returns `Ok(None)`                 if there are no saved credentials.
 ///
 /// # Errors
 ///
@@ -118,27 +118,27 @@ pub async fn set_credentials(payload: &AuthPayload) -> anyhow::Result<(), Creden
 /// Returns `Err(RwError)`   if `File::read_to_string` fails.
 /// Returns `Err(SerError)`  if `serde_json::from_str` fails.
 ///
-pub async fn get_credentials() -> anyhow::Result<Option<AuthPayload>, CredentialsIoError> {
+pub async fn get_credentials() -> anyhow::Result<Option<SessionPayload>, CredentialsIoError> {
     let path = meta::credentials_path();
 
     if !fs::try_exists(&path)
         .await
-        .map_err(|e| CredentialsIoError::DirError(e))?
+        .map_err(CredentialsIoError::DirError)?
     {
         return Ok(None);
     }
 
     let mut file = File::open(path)
         .await
-        .map_err(|e| CredentialsIoError::FileError(e))?;
+        .map_err(CredentialsIoError::FileError)?;
 
     let mut buf = String::new();
     file.read_to_string(&mut buf)
         .await
-        .map_err(|e| CredentialsIoError::RwError(e))?;
+        .map_err(CredentialsIoError::RwError)?;
 
     let payload =
-        serde_json::from_str::<AuthPayload>(&buf).map_err(|e| CredentialsIoError::SerError(e))?;
+        serde_json::from_str::<SessionPayload>(&buf).map_err(CredentialsIoError::SerError)?;
 
     Ok(Some(payload))
 }
@@ -158,33 +158,117 @@ pub async fn remove_credentials() -> anyhow::Result<bool, CredentialsIoError> {
 
     if !fs::try_exists(&path)
         .await
-        .map_err(|e| CredentialsIoError::DirError(e))?
+        .map_err(CredentialsIoError::DirError)?
     {
         return Ok(false);
     }
 
     fs::remove_file(path)
         .await
-        .map_err(|e| CredentialsIoError::FileError(e))?;
+        .map_err(CredentialsIoError::FileError)?;
 
     Ok(true)
 }
 
-/// Checks if there are any saved credentials.
+/// Logs in as the user specified by the provided `AuthPayload`
+/// on the currently connected server and saves the bearer token
+/// that the server responds with.
 ///
-/// Returns `Ok(true)`  if there are saved credentials.
-/// Returns `Ok(false)` if there are no saved credentials.
+/// Returns `Ok(CredentialsTestResult)` unless any unexpected errors occur.
+/// Returns `Err(CredentialsTestError)` if the login failed to be performed due to an unexpected error.
+///
+pub async fn log_in(
+    payload: &AuthPayload,
+) -> anyhow::Result<CredentialsTestResult, CredentialsTestError> {
+    let result = connection::try_connection()
+        .await
+        .map_err(CredentialsTestError::ConnectionTestError)?;
+
+    if result != ConnectionTestResult::Success {
+        return Ok(CredentialsTestResult::BadConnection(result));
+    }
+
+    let Some(connection) = connection::get_connection()
+        .await
+        .map_err(CredentialsTestError::ConnectionIoError)?
+    else {
+        return Ok(CredentialsTestResult::NoConnection);
+    };
+
+    let result = reqwest::Client::new()
+        .post(format!(
+            "http://{}{}",
+            connection,
+            api::api_sessions_login_absolute()
+        ))
+        .json(payload)
+        .send()
+        .await
+        .map_err(CredentialsTestError::ConnectionFailure)?;
+
+    if result.status() == StatusCode::UNAUTHORIZED {
+        return Ok(CredentialsTestResult::AuthFailure);
+    }
+
+    let session = result
+        .error_for_status()
+        .map_err(CredentialsTestError::BadStatus)?
+        .json::<SessionPayload>()
+        .await
+        .map_err(CredentialsTestError::BadResponse)?;
+
+    set_credentials(&session)
+        .await
+        .map_err(CredentialsTestError::CredentialsIoError)?;
+
+    Ok(CredentialsTestResult::Success)
+}
+
+/// Revokes the saved bearer token on the currently connected server.
 ///
-/// # Errors:
+/// The saved credentials are left in place, see `remove_credentials`.
 ///
-/// Returns `Err(DirError)` if `fs::try_exists` fails.
+/// Returns `Ok(true)`  if the token was revoked or had already expired.
+/// Returns `Ok(false)` if there is no saved connection or credentials.
 ///
-pub async fn has_credentials() -> anyhow::Result<bool, CredentialsIoError> {
-    let path = meta::credentials_path();
+/// # Errors
+///
+/// Returns `Err(CredentialsTestError)` if the server could not be
+/// reached or responded with an unexpected status.
+///
+pub async fn revoke_credentials() -> anyhow::Result<bool, CredentialsTestError> {
+    let Some(connection) = connection::get_connection()
+        .await
+        .map_err(CredentialsTestError::ConnectionIoError)?
+    else {
+        return Ok(false);
+    };
+
+    let Some(credentials) = get_credentials()
+        .await
+        .map_err(CredentialsTestError::CredentialsIoError)?
+    else {
+        return Ok(false);
+    };
 
-    Ok(fs::try_exists(path)
+    let result = reqwest::Client::new()
+        .post(format!(
+            "http://{}{}",
+            connection,
+            api::api_sessions_logout_absolute()
+        ))
+        .bearer_auth(credentials.token)
+        .send()
         .await
-        .map_err(|e| CredentialsIoError::DirError(e))?)
+        .map_err(CredentialsTestError::ConnectionFailure)?;
+
+    if result.status() != StatusCode::UNAUTHORIZED {
+        result
+            .error_for_status()
+            .map_err(CredentialsTestError::BadStatus)?;
+    }
+
+    Ok(true)
 }
 
 /// Checks if the current saved credentials are valid
@@ -216,36 +300,49 @@ pub async fn try_credentials() -> anyhow::Result<CredentialsTestResult, Credenti
         return Ok(CredentialsTestResult::NoCredentials);
     };
 
-    let query = UserQuery::ByName {
-        name: credentials.username,
-    };
-
     let result = reqwest::Client::new()
-        .post(format!(
+        .get(format!(
             "http://{}{}",
             connection,
-            api::api_users_auth_absolute()
+            api::api_sessions_current_absolute()
         ))
-        .query(&query)
-        .json(&PassOnlyAuthPayload {
-            passphrase: credentials.passphrase,
-        })
+        .bearer_auth(credentials.token)
         .send()
         .await
         .map_err(CredentialsTestError::ConnectionFailure)?;
 
-    let result = result
+    if result.status() == StatusCode::UNAUTHORIZED {
+        return Ok(CredentialsTestResult::AuthFailure);
+    }
+
+    result
         .error_for_status()
         .map_err(CredentialsTestError::BadStatus)?;
 
-    let payload = result
-        .json::<BoolPayload>()
-        .await
-        .map_err(CredentialsTestError::BadResponse)?;
+    Ok(CredentialsTestResult::Success)
+}
+
+/// Builds a request to `path` on the currently connected server
+/// that carries the saved bearer token.
+///
+/// # Errors
+///
+/// Returns `Err` if there is no saved connection or credentials,
+/// or if either of them could not be read.
+///
+pub async fn authorized(
+    method: reqwest::Method,
+    path: &str,
+) -> anyhow::Result<reqwest::RequestBuilder> {
+    let Some(connection) = connection::get_connection().await? else {
+        anyhow::bail!("no server is currently connected");
+    };
+
+    let Some(credentials) = get_credentials().await? else {
+        anyhow::bail!("not logged in");
+    };
 
-    Ok(if payload.result {
-        CredentialsTestResult::Success
-    } else {
-        CredentialsTestResult::AuthFailure
-    })
+    Ok(reqwest::Client::new()
+        .request(method, format!("http://{}{}", connection, path))
+        .bearer_auth(credentials.token))
 }
@@ -57,20 +57,20 @@ pub async fn set_connection(connection: &str) -> anyhow::Result<(), ConnectionIo
     if let Some(parent) = path.parent()
         && !fs::try_exists(&parent)
             .await
-            .map_err(|e| ConnectionIoError::DirError(e))?
+            .map_err(ConnectionIoError::DirError)?
     {
         fs::create_dir_all(parent)
             .await
-            .map_err(|e| ConnectionIoError::DirError(e))?;
+            .map_err(ConnectionIoError::DirError)?;
     }
 
     let mut file = File::create(path)
         .await
-        .map_err(|e| ConnectionIoError::FileError(e))?;
+        .map_err(ConnectionIoError::FileError)?;
 
     file.write_all(connection.trim().as_bytes())
         .await
-        .map_err(|e| ConnectionIoError::RwError(e))?;
+        .map_err(ConnectionIoError::RwError)?;
 
     Ok(())
 }
@@ -95,12 +95,12 @@ pub async fn get_connection() -> anyhow::Result<Option<String>, ConnectionIoErro
 
     let mut file = File::open(path)
         .await
-        .map_err(|e| ConnectionIoError::FileError(e))?;
+        .map_err(ConnectionIoError::FileError)?;
 
     let mut buf = String::new();
     file.read_to_string(&mut buf)
         .await
-        .map_err(|e| ConnectionIoError::RwError(e))?;
+        .map_err(ConnectionIoError::RwError)?;
 
     Ok(Some(buf.trim().to_owned()))
 }
@@ -124,7 +124,7 @@ pub async fn remove_connection() -> anyhow::Result<bool, ConnectionIoError> {
 
     fs::remove_file(path)
         .await
-        .map_err(|e| ConnectionIoError::FileError(e))?;
+        .map_err(ConnectionIoError::FileError)?;
 
     Ok(true)
 }
@@ -141,9 +141,9 @@ pub async fn remove_connection() -> anyhow::Result<bool, ConnectionIoError> {
 pub async fn has_connection() -> anyhow::Result<bool, ConnectionIoError> {
     let path = meta::connection_path();
 
-    Ok(fs::try_exists(path)
+    fs::try_exists(path)
         .await
-        .map_err(|e| ConnectionIoError::DirError(e))?)
+        .map_err(ConnectionIoError::DirError)
 }
 
 /// Checks if the current saved connection is reachable.
@@ -1,17 +1,30 @@
-PRAGMA foreign_keys = ON;
-
 CREATE TABLE IF NOT EXISTS users (
     id         INTEGER  PRIMARY KEY,
 
     name TEXT UNIQUE NOT NULL COLLATE NOCASE
         CHECK (name = TRIM(name) AND LENGTH(name) >=  3 AND LENGTH(name) <=  20),
 
-    passphrase TEXT NOT NULL
-        CHECK (passphrase = TRIM(passphrase) AND LENGTH(passphrase) >=  8 AND LENGTH(passphrase) <=  20),
+    -- A PHC-format Argon2id hash of the passphrase
+    passphrase TEXT NOT NULL,
 
     created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
 );
 
+CREATE TABLE IF NOT EXISTS sessions (
+    id         INTEGER  PRIMARY KEY,
+    user_id    INTEGER  NOT NULL,
+
+    -- A hex-encoded SHA-256 digest of the bearer token
+    token_hash TEXT     UNIQUE NOT NULL,
+
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+
+    FOREIGN KEY (user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE
+);
+
 CREATE TABLE IF NOT EXISTS mails (
     id         INTEGER  PRIMARY KEY,
     user_id    INTEGER  NOT NULL,
@@ -45,6 +58,18 @@ CREATE TABLE IF NOT EXISTS attachments (
     data       BLOB     NOT NULL
         CHECK (length(data) <= 1024*1024*1000),
 
+    filename     TEXT     NOT NULL DEFAULT ''
+        CHECK (filename = TRIM(filename) AND LENGTH(filename) <=  255),
+
+    content_type TEXT     NOT NULL DEFAULT 'application/octet-stream'
+        CHECK (content_type = TRIM(content_type) AND LENGTH(content_type) <=  255),
+
+    -- The size of `data` in bytes
+    size         INTEGER  NOT NULL DEFAULT 0,
+
+    -- A hex-encoded SHA-256 digest of `data`, NULL until the upload completes
+    sha256       TEXT,
+
     created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
 
     FOREIGN KEY (mail_id)
@@ -52,8 +77,14 @@ CREATE TABLE IF NOT EXISTS attachments (
         ON DELETE CASCADE
 );
 
+CREATE INDEX IF NOT EXISTS idx_sessions_user_id
+    ON sessions(user_id);
+
 CREATE INDEX IF NOT EXISTS idx_mails_user_id
     ON mails(user_id);
 
+CREATE INDEX IF NOT EXISTS idx_mails_user_id_created_at
+    ON mails(user_id, created_at, id);
+
 CREATE INDEX IF NOT EXISTS idx_attachments_mail_id
     ON attachments(mail_id);
@@ -0,0 +1,46 @@
+-- Mails waiting to be fetched by the server of a remote recipient
+CREATE TABLE delivery_queue (
+    id         INTEGER  PRIMARY KEY,
+
+    -- The `sent` copy owned by the sender, which is what gets delivered
+    mail_id    INTEGER  NOT NULL,
+
+    -- The full address of the recipient, i.e, `user@host:port`
+    recipient  TEXT     NOT NULL
+        CHECK (recipient = TRIM(recipient) AND LENGTH(recipient) <=  255),
+
+    -- A hex-encoded random token the remote server fetches the mail with
+    token      TEXT     UNIQUE NOT NULL,
+
+    status     TEXT     NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'delivered', 'failed')),
+
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (mail_id)
+        REFERENCES mails(id)
+        ON DELETE CASCADE
+);
+
+-- Every attempt at handing a queued mail to the remote server
+CREATE TABLE delivery_attempts (
+    id          INTEGER  PRIMARY KEY,
+    queue_id    INTEGER  NOT NULL,
+
+    -- The HTTP status the remote server responded with, NULL if it could not be reached
+    status_code INTEGER,
+
+    error       TEXT,
+
+    created_at  DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (queue_id)
+        REFERENCES delivery_queue(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX idx_delivery_queue_mail_id
+    ON delivery_queue(mail_id);
+
+CREATE INDEX idx_delivery_attempts_queue_id
+    ON delivery_attempts(queue_id);
@@ -0,0 +1,8 @@
+-- How many attempts have been made so far
+ALTER TABLE delivery_queue ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+
+-- When the next attempt is due, NULL means as soon as possible
+ALTER TABLE delivery_queue ADD COLUMN next_attempt_at DATETIME;
+
+CREATE INDEX idx_delivery_queue_status_next_attempt_at
+    ON delivery_queue(status, next_attempt_at);
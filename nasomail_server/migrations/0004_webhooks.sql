@@ -0,0 +1,78 @@
+-- Subscriptions to events, either of a single user or server-wide
+CREATE TABLE webhooks (
+    id         INTEGER  PRIMARY KEY,
+
+    -- NULL for the server-wide webhooks of `Config::webhooks`
+    user_id    INTEGER,
+
+    url        TEXT     NOT NULL
+        CHECK (url = TRIM(url) AND LENGTH(url) >= 1 AND LENGTH(url) <= 2048),
+
+    -- A comma-separated list of event types, e.g, `received,status_changed`
+    events     TEXT     NOT NULL,
+
+    -- The key requests are signed with
+    secret     TEXT     NOT NULL
+        CHECK (LENGTH(secret) >= 1 AND LENGTH(secret) <= 255),
+
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE
+);
+
+-- Events waiting to be (or already) posted to a webhook
+CREATE TABLE webhook_deliveries (
+    id              INTEGER  PRIMARY KEY,
+    webhook_id      INTEGER  NOT NULL,
+
+    -- The `EventPayload`, as JSON
+    event           TEXT     NOT NULL,
+
+    -- The exact body that is posted, so every attempt is signed the same way
+    body            TEXT     NOT NULL,
+
+    status          TEXT     NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'delivered', 'failed')),
+
+    attempts        INTEGER  NOT NULL DEFAULT 0,
+
+    -- When the next attempt is due, NULL means as soon as possible
+    next_attempt_at DATETIME,
+
+    created_at      DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (webhook_id)
+        REFERENCES webhooks(id)
+        ON DELETE CASCADE
+);
+
+-- Every attempt at posting a delivery
+CREATE TABLE webhook_attempts (
+    id          INTEGER  PRIMARY KEY,
+    delivery_id INTEGER  NOT NULL,
+
+    -- The HTTP status the webhook responded with, NULL if it could not be reached
+    status_code INTEGER,
+
+    error       TEXT,
+
+    created_at  DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (delivery_id)
+        REFERENCES webhook_deliveries(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX idx_webhooks_user_id
+    ON webhooks(user_id);
+
+CREATE INDEX idx_webhook_deliveries_webhook_id_created_at
+    ON webhook_deliveries(webhook_id, created_at);
+
+CREATE INDEX idx_webhook_deliveries_status_next_attempt_at
+    ON webhook_deliveries(status, next_attempt_at);
+
+CREATE INDEX idx_webhook_attempts_delivery_id
+    ON webhook_attempts(delivery_id);
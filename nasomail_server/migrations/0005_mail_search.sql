@@ -0,0 +1,34 @@
+-- A full-text index over `mails`, which only holds the index itself
+-- and reads the indexed text back from `mails` through `rowid`
+CREATE VIRTUAL TABLE mails_fts USING fts5(
+    subject,
+    body,
+    sender,
+    recipient,
+    content = 'mails',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+-- Keep the index in sync with `mails`, changing the status
+-- of a mail leaves the indexed text untouched
+CREATE TRIGGER mails_fts_insert AFTER INSERT ON mails BEGIN
+    INSERT INTO mails_fts (rowid, subject, body, sender, recipient)
+        VALUES (new.id, new.subject, new.body, new.sender, new.recipient);
+END;
+
+CREATE TRIGGER mails_fts_delete AFTER DELETE ON mails BEGIN
+    INSERT INTO mails_fts (mails_fts, rowid, subject, body, sender, recipient)
+        VALUES ('delete', old.id, old.subject, old.body, old.sender, old.recipient);
+END;
+
+CREATE TRIGGER mails_fts_update AFTER UPDATE OF subject, body, sender, recipient ON mails BEGIN
+    INSERT INTO mails_fts (mails_fts, rowid, subject, body, sender, recipient)
+        VALUES ('delete', old.id, old.subject, old.body, old.sender, old.recipient);
+    INSERT INTO mails_fts (rowid, subject, body, sender, recipient)
+        VALUES (new.id, new.subject, new.body, new.sender, new.recipient);
+END;
+
+-- Index every mail that existed before this migration
+INSERT INTO mails_fts (mails_fts) VALUES ('rebuild');
@@ -0,0 +1,34 @@
+-- The `Message-ID` of the mail, without angle brackets, which every copy
+-- of the same mail shares, e.g, the `sent` copy and the received one
+ALTER TABLE mails ADD COLUMN message_id TEXT NOT NULL DEFAULT ''
+    CHECK (message_id = TRIM(message_id) AND LENGTH(message_id) <= 255);
+
+-- The `message_id` of the mail this one replies to
+ALTER TABLE mails ADD COLUMN in_reply_to TEXT
+    CHECK (in_reply_to = TRIM(in_reply_to) AND LENGTH(in_reply_to) <= 255);
+
+-- The `References` of the mail, i.e, the space-separated `message_id`s
+-- of the mails before it in its thread, oldest first
+ALTER TABLE mails ADD COLUMN refs TEXT NOT NULL DEFAULT ''
+    CHECK (LENGTH(refs) <= 8192);
+
+-- The `id` of the first mail of the thread in the mailbox of the owner,
+-- which stays the same after that mail is deleted
+ALTER TABLE mails ADD COLUMN thread_id INTEGER;
+
+-- Mails from before threading are threads of their own
+-- and get random ids, since they never had real ones
+UPDATE mails SET
+    message_id = LOWER(HEX(RANDOMBLOB(16))) || '@nasomail.invalid',
+    thread_id = id;
+
+-- Mails that are not part of an existing thread start a new one
+CREATE TRIGGER mails_thread_insert AFTER INSERT ON mails WHEN new.thread_id IS NULL BEGIN
+    UPDATE mails SET thread_id = new.id WHERE id = new.id;
+END;
+
+CREATE INDEX idx_mails_user_id_message_id
+    ON mails(user_id, message_id);
+
+CREATE INDEX idx_mails_user_id_thread_id_created_at
+    ON mails(user_id, thread_id, created_at);
@@ -0,0 +1,38 @@
+-- User-defined labels to organize mails with, a mail may have any number of them
+CREATE TABLE labels (
+    id         INTEGER  PRIMARY KEY,
+    user_id    INTEGER  NOT NULL,
+
+    name       TEXT     NOT NULL COLLATE NOCASE
+        CHECK (name = TRIM(name) AND LENGTH(name) >= 1 AND LENGTH(name) <= 64),
+
+    -- A lowercase `#rrggbb` hex color
+    color      TEXT     NOT NULL
+        CHECK (color GLOB '#[0-9a-f][0-9a-f][0-9a-f][0-9a-f][0-9a-f][0-9a-f]'),
+
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    UNIQUE (user_id, name),
+
+    FOREIGN KEY (user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE
+);
+
+CREATE TABLE mail_labels (
+    mail_id  INTEGER NOT NULL,
+    label_id INTEGER NOT NULL,
+
+    PRIMARY KEY (mail_id, label_id),
+
+    FOREIGN KEY (mail_id)
+        REFERENCES mails(id)
+        ON DELETE CASCADE,
+
+    FOREIGN KEY (label_id)
+        REFERENCES labels(id)
+        ON DELETE CASCADE
+) WITHOUT ROWID;
+
+CREATE INDEX idx_mail_labels_label_id
+    ON mail_labels(label_id);
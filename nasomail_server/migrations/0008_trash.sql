@@ -0,0 +1,7 @@
+-- When the mail was moved to the trash, NULL if it is not in the trash.
+-- Trashed mails are deleted for good after `Config::trash_retention_days`
+ALTER TABLE mails ADD COLUMN deleted_at DATETIME;
+
+CREATE INDEX idx_mails_deleted_at
+    ON mails(deleted_at)
+    WHERE deleted_at IS NOT NULL;
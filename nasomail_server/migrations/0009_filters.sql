@@ -0,0 +1,13 @@
+-- The Sieve script of a user, which is run on every mail delivered into their inbox
+CREATE TABLE filters (
+    user_id    INTEGER  PRIMARY KEY,
+
+    script     TEXT     NOT NULL
+        CHECK (LENGTH(script) <= 65536),
+
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE
+);
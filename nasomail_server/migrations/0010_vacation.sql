@@ -0,0 +1,44 @@
+-- Whether the mail was generated automatically, e.g, a bounce or an
+-- out-of-office reply, which must never be replied to automatically
+ALTER TABLE mails ADD COLUMN auto_submitted BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The out-of-office reply of a user, sent to the senders of the mails
+-- they receive from `starts_on` through `ends_on`, both in UTC
+CREATE TABLE vacations (
+    user_id    INTEGER  PRIMARY KEY,
+
+    starts_on  DATE     NOT NULL
+        CHECK (starts_on IS DATE(starts_on)),
+
+    ends_on    DATE     NOT NULL
+        CHECK (ends_on IS DATE(ends_on) AND ends_on >= starts_on),
+
+    subject    TEXT     NOT NULL
+        CHECK (subject = TRIM(subject) AND LENGTH(subject) >= 1 AND LENGTH(subject) <= 255),
+
+    body       TEXT     NOT NULL
+        CHECK (body = TRIM(body) AND LENGTH(body) >= 1 AND LENGTH(body) <= 65536),
+
+    -- How many days to wait before replying to the same sender again
+    days       INTEGER  NOT NULL
+        CHECK (days >= 1 AND days <= 365),
+
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE
+);
+
+-- When each sender was last sent the out-of-office reply of a user
+CREATE TABLE vacation_replies (
+    user_id    INTEGER  NOT NULL,
+    sender     TEXT     NOT NULL COLLATE NOCASE,
+    replied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (user_id, sender),
+
+    FOREIGN KEY (user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE
+) WITHOUT ROWID;
@@ -0,0 +1,22 @@
+-- Drafts that are going to be sent at `run_at`, either because they were
+-- scheduled or because they are still within the undo window, see
+-- `Config::undo_send_secs`. The draft is sent by the `schedule` worker
+-- and cancelling the job turns it back into a regular draft
+CREATE TABLE send_jobs (
+    mail_id    INTEGER  PRIMARY KEY,
+    user_id    INTEGER  NOT NULL,
+    run_at     DATETIME NOT NULL
+        CHECK (run_at IS DATETIME(run_at)),
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (mail_id)
+        REFERENCES mails(id)
+        ON DELETE CASCADE,
+
+    FOREIGN KEY (user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX idx_send_jobs_run_at
+    ON send_jobs(run_at);
@@ -0,0 +1,32 @@
+-- The next IMAP UID to hand out, shared by every mailbox.
+-- UIDs are never reused, unlike the `id`s of deleted mails
+CREATE TABLE imap_uids (
+    next INTEGER NOT NULL
+);
+
+INSERT INTO imap_uids (next) SELECT COALESCE(MAX(id), 0) + 1 FROM mails;
+
+-- The IMAP UID of the mail, which is replaced whenever the mail moves
+-- into another mailbox, e.g, when a draft is sent or a mail is moved to
+-- the trash, so it is always higher than the UIDs already there.
+-- Mails keep their `id`s as UIDs, so clients do not have to resync
+ALTER TABLE mails ADD COLUMN uid INTEGER NOT NULL DEFAULT 0;
+
+UPDATE mails SET uid = id;
+
+CREATE TRIGGER mails_uid_insert AFTER INSERT ON mails BEGIN
+    UPDATE imap_uids SET next = next + 1;
+    UPDATE mails SET uid = (SELECT next - 1 FROM imap_uids) WHERE id = new.id;
+END;
+
+CREATE TRIGGER mails_uid_move AFTER UPDATE OF status, deleted_at ON mails
+    WHEN (old.deleted_at IS NULL) != (new.deleted_at IS NULL)
+        OR (old.status IN ('new', 'read')) != (new.status IN ('new', 'read'))
+        OR (old.status NOT IN ('new', 'read') AND old.status != new.status)
+BEGIN
+    UPDATE imap_uids SET next = next + 1;
+    UPDATE mails SET uid = (SELECT next - 1 FROM imap_uids) WHERE id = new.id;
+END;
+
+CREATE INDEX idx_mails_user_id_uid
+    ON mails(user_id, uid);
@@ -0,0 +1,159 @@
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::Response,
+    routing::get,
+};
+use tracing::instrument;
+
+use nasomail_shared::api;
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+    blob,
+};
+
+pub trait RouterApiAttachmentsDownload {
+    /// Registers the `GET /api/attachments/{id}` endpoint
+    /// which downloads an attachment of the authenticated user.
+    fn with_api_attachments_download(self) -> Self;
+}
+
+impl RouterApiAttachmentsDownload for Router<AppContextGuard> {
+    fn with_api_attachments_download(self) -> Self {
+        self.route(api::API_ATTACHMENTS_ID, get(handle))
+    }
+}
+
+/// Streams the data of the attachment with the given `id`, with its stored
+/// MIME type as the `Content-Type`, its filename in the `Content-Disposition`
+/// and its SHA-256 digest (if known) as the `ETag`.
+///
+/// A single `Range: bytes=...` is honored with a `206 Partial Content`,
+/// unsatisfiable ranges get a `416 Range Not Satisfiable` and anything
+/// else (e.g, multiple ranges) is answered with the whole attachment.
+///
+/// Responds with a `404 Not Found` if the attachment does not exist
+/// or belongs to a mail that is not owned by the authenticated user.
+#[instrument(skip(app, headers))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    // Clone the pool so the context is not held for the whole download
+    let pool = app.ctx().await.pool().await.clone();
+
+    let (size, filename, content_type, sha256): (i64, String, String, Option<String>) =
+        sqlx::query_as(
+            "SELECT attachments.size, attachments.filename,
+                    attachments.content_type, attachments.sha256
+                FROM attachments
+                JOIN mails ON mails.id = attachments.mail_id
+                WHERE attachments.id = ? AND mails.user_id = ?",
+        )
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or(ApiError::Status(StatusCode::NOT_FOUND))?;
+    let size = size as u64;
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_range(value, size));
+
+    let filename = if filename.is_empty() {
+        "attachment".to_owned()
+    } else {
+        // Keep the header value valid and the quoted string unambiguous
+        filename
+            .chars()
+            .map(|c| {
+                if c == '"' || c == '\\' || c.is_control() {
+                    '_'
+                } else {
+                    c
+                }
+            })
+            .collect()
+    };
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        )
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    if let Some(sha256) = sha256 {
+        response = response.header(header::ETAG, format!("\"{}\"", sha256));
+    }
+
+    let (start, end) = match range {
+        None => (0, size),
+        Some(Ok((start, end))) => {
+            response = response.status(StatusCode::PARTIAL_CONTENT).header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end - 1, size),
+            );
+            (start, end)
+        }
+        Some(Err(())) => {
+            return response
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                .body(Body::empty())
+                .map_err(|e| ApiError::Internal(e.into()));
+        }
+    };
+
+    let chunks = blob::read_stream(pool, id, start, end);
+
+    response
+        .header(header::CONTENT_LENGTH, end - start)
+        .body(Body::from_stream(chunks))
+        .map_err(|e| ApiError::Internal(e.into()))
+}
+
+/// Parses a `Range` header value into a half-open byte range of an
+/// attachment of `size` bytes.
+///
+/// Returns `None`          if the header should be ignored.
+/// Returns `Some(Err(()))` if the range is not satisfiable.
+///
+fn parse_range(value: &str, size: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        // A suffix range, i.e, the last `end` bytes
+        let suffix: u64 = end.parse().ok()?;
+        (size.saturating_sub(suffix), size)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            size
+        } else {
+            end.parse::<u64>().ok()?.saturating_add(1).min(size)
+        };
+        (start, end)
+    };
+
+    Some(if range.0 < range.1 {
+        Ok(range)
+    } else {
+        Err(())
+    })
+}
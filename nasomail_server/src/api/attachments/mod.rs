@@ -0,0 +1,22 @@
+mod download;
+
+use axum::Router;
+
+use nasomail_shared::api;
+
+use crate::{api::attachments::download::RouterApiAttachmentsDownload, app::AppContextGuard};
+
+pub trait RouterApiAttachments {
+    /// Registers routes for
+    /// attachment related APIs
+    fn with_api_attachments(self) -> Self;
+}
+
+impl RouterApiAttachments for Router<AppContextGuard> {
+    fn with_api_attachments(self) -> Self {
+        self.nest(
+            api::API_ATTACHMENTS,
+            Router::new().with_api_attachments_download(),
+        )
+    }
+}
@@ -0,0 +1,54 @@
+//! An extractor for authenticating requests
+//! with an `Authorization: Bearer` header.
+
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+
+use crate::{api::error::ApiError, app::AppContextGuard, session};
+
+/// The user that sent an authenticated request.
+///
+/// Handlers that take an `AuthUser` reject requests without
+/// a valid, unexpired bearer token with a `401 Unauthorized`.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub id: i64,
+    pub name: String,
+
+    /// The `id` of the row in the `sessions` table
+    /// that the bearer token belongs to.
+    pub session_id: i64,
+}
+
+impl FromRequestParts<AppContextGuard> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        app: &AppContextGuard,
+    ) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .ok_or(ApiError::Unauthorized)?;
+
+        let ctx = app.ctx().await;
+        let pool = ctx.pool().await;
+
+        let session = session::lookup(&pool, token)
+            .await?
+            .ok_or(ApiError::Unauthorized)?;
+
+        Ok(Self {
+            id: session.user_id,
+            name: session.user_name,
+            session_id: session.id,
+        })
+    }
+}
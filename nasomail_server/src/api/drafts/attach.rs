@@ -0,0 +1,153 @@
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    routing::post,
+};
+use tracing::{instrument, warn};
+
+use nasomail_shared::{
+    api,
+    payload::{IdPayload, mail::ATTACHMENT_MAX_SIZE},
+    query::mail::AttachmentUploadQuery,
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, validate::Validator},
+    app::AppContextGuard,
+    blob::{self, StreamError},
+    mime,
+};
+
+pub trait RouterApiDraftsAttach {
+    /// Registers the `POST /api/drafts/{id}/attachments` endpoint
+    /// which uploads an attachment to a draft of the authenticated user.
+    fn with_api_drafts_attach(self) -> Self;
+}
+
+impl RouterApiDraftsAttach for Router<AppContextGuard> {
+    fn with_api_drafts_attach(self) -> Self {
+        self.route(api::API_DRAFTS_ID_ATTACHMENTS, post(handle))
+    }
+}
+
+/// Streams the raw request body into a new row of the `attachments` table
+/// tied to the draft with the given `id`, then returns an `IdPayload`
+/// containing the `id` of the attachment.
+///
+/// The body is never held in memory as a whole, it is written into a
+/// `ZEROBLOB` of the announced `Content-Length` chunk by chunk. Requests
+/// without a `Content-Length` are rejected with a `411 Length Required` and
+/// ones larger than `Config::max_attachment_size` with a `413 Payload Too Large`.
+///
+/// The `filename` is taken from the provided `AttachmentUploadQuery` and the
+/// MIME type from the `Content-Type` header. If the header is missing or
+/// generic, the MIME type is guessed from the data and the `filename`.
+/// The size and a SHA-256 digest are recorded alongside the data.
+///
+/// Responds with a `404 Not Found` if the mail does not exist, is not
+/// a draft or is not owned by the authenticated user.
+#[instrument(skip(app, query, headers, body))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Query(query): Query<AttachmentUploadQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<(StatusCode, Json<IdPayload>), ApiError> {
+    // Only keep the last path component of whatever the client sent
+    let filename = query.filename.unwrap_or_default();
+    let filename = filename
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_owned();
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty() && value != mime::OCTET_STREAM);
+
+    Validator::new()
+        .len("filename", &filename, 0, 255)
+        .len(
+            "content_type",
+            content_type.as_deref().unwrap_or_default(),
+            0,
+            255,
+        )
+        .finish()?;
+
+    let len: u64 = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .ok_or(ApiError::Status(StatusCode::LENGTH_REQUIRED))?;
+
+    // Clone the pool so the context is not held for the whole upload
+    let (pool, max) = {
+        let ctx = app.ctx().await;
+        let max = *ctx.cfg().await.max_attachment_size().await;
+
+        (ctx.pool().await.clone(), max.min(ATTACHMENT_MAX_SIZE))
+    };
+
+    if len > max {
+        return Err(ApiError::Status(StatusCode::PAYLOAD_TOO_LARGE));
+    }
+
+    let attachment_id: Option<i64> = sqlx::query_scalar(
+        "INSERT INTO attachments (mail_id, data, filename, size)
+            SELECT id, ZEROBLOB(?), ?, ? FROM mails
+            WHERE id = ? AND user_id = ? AND status = 'draft' AND deleted_at IS NULL
+            RETURNING id",
+    )
+    .bind(len as i64)
+    .bind(&filename)
+    .bind(len as i64)
+    .bind(id)
+    .bind(user.id)
+    .fetch_optional(&pool)
+    .await?;
+
+    let attachment_id = attachment_id.ok_or(ApiError::Status(StatusCode::NOT_FOUND))?;
+
+    let written = match blob::write_stream(&pool, attachment_id, len, body.into_data_stream()).await
+    {
+        Ok(written) => written,
+        Err(e) => {
+            // Do not leave a partially written attachment behind
+            if let Err(e) = sqlx::query("DELETE FROM attachments WHERE id = ?")
+                .bind(attachment_id)
+                .execute(&pool)
+                .await
+            {
+                warn!(err = ?e, attachment_id = attachment_id, "failed to remove partial attachment");
+            }
+
+            return Err(match e {
+                StreamError::Read | StreamError::Length(_) => {
+                    ApiError::Status(StatusCode::BAD_REQUEST)
+                }
+                StreamError::Database(e) => ApiError::Database(e),
+                StreamError::Blob(e) => ApiError::Internal(e),
+            });
+        }
+    };
+
+    let content_type =
+        content_type.unwrap_or_else(|| mime::sniff(&written.head, &filename).to_owned());
+
+    sqlx::query("UPDATE attachments SET content_type = ?, sha256 = ? WHERE id = ?")
+        .bind(content_type)
+        .bind(written.sha256)
+        .bind(attachment_id)
+        .execute(&pool)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(IdPayload { id: attachment_id })))
+}
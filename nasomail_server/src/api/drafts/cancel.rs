@@ -0,0 +1,54 @@
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::post,
+};
+use tracing::instrument;
+
+use nasomail_shared::api;
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiDraftsCancel {
+    /// Registers the `POST /api/drafts/{id}/cancel` endpoint
+    /// which cancels sending a draft of the authenticated user.
+    fn with_api_drafts_cancel(self) -> Self;
+}
+
+impl RouterApiDraftsCancel for Router<AppContextGuard> {
+    fn with_api_drafts_cancel(self) -> Self {
+        self.route(api::API_DRAFTS_ID_CANCEL, post(handle))
+    }
+}
+
+/// Cancels sending the draft with the given `id`, which was scheduled or is
+/// still within the undo window, so it is kept as a regular draft.
+///
+/// Responds with a `404 Not Found` if the mail does not exist, is not
+/// scheduled, e.g, because it has already been sent, or is not owned
+/// by the authenticated user.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let result = sqlx::query("DELETE FROM send_jobs WHERE mail_id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user.id)
+        .execute(&*pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
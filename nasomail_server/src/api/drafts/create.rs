@@ -0,0 +1,48 @@
+use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{IdPayload, mail::DraftPayload},
+};
+
+use crate::{
+    api::{bearer::AuthUser, drafts, error::ApiError, mails},
+    app::AppContextGuard,
+    delivery,
+};
+
+pub trait RouterApiDraftsCreate {
+    /// Registers the `POST /api/drafts` endpoint
+    /// which creates a new draft for the authenticated user.
+    fn with_api_drafts_create(self) -> Self;
+}
+
+impl RouterApiDraftsCreate for Router<AppContextGuard> {
+    fn with_api_drafts_create(self) -> Self {
+        self.route(api::API_DRAFTS_ROOT, post(handle))
+    }
+}
+
+/// Saves the provided `DraftPayload` as a new `draft` row owned by the
+/// authenticated user, then returns an `IdPayload` containing its `id`.
+///
+/// The recipient is not checked until the draft is sent.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Json(payload): Json<DraftPayload>,
+) -> Result<(StatusCode, Json<IdPayload>), ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+    let origin = ctx.cfg().await.pub_addr().await.clone();
+
+    let mail = drafts::to_mail(user.name, delivery::message_id(&origin), payload);
+    mails::validate(&mail, 0)?;
+    let mut conn = pool.acquire().await?;
+
+    let id = delivery::insert(&mut conn, user.id, &mail, "draft").await?;
+
+    Ok((StatusCode::CREATED, Json(IdPayload { id })))
+}
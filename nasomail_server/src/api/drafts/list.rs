@@ -0,0 +1,60 @@
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    routing::get,
+};
+use sqlx::{QueryBuilder, Sqlite};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{Page, mail::MailSummary},
+    query::PageQuery,
+};
+
+use crate::{
+    api::{
+        bearer::AuthUser,
+        error::ApiError,
+        mails,
+        pagination::{self, Cursor},
+    },
+    app::AppContextGuard,
+};
+
+pub trait RouterApiDraftsList {
+    /// Registers the `GET /api/drafts` endpoint
+    /// which lists the drafts of the authenticated user.
+    fn with_api_drafts_list(self) -> Self;
+}
+
+impl RouterApiDraftsList for Router<AppContextGuard> {
+    fn with_api_drafts_list(self) -> Self {
+        self.route(api::API_DRAFTS_ROOT, get(handle))
+    }
+}
+
+/// Lists the drafts owned by the authenticated user, newest first,
+/// as a `Page` of `MailSummary`s.
+#[instrument(skip(app, query))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Page<MailSummary>>, ApiError> {
+    let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+    let limit = pagination::limit(query.limit);
+
+    let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT id, subject, sender, recipient, status, created_at
+            FROM mails WHERE status = 'draft' AND deleted_at IS NULL AND user_id = ",
+    );
+    builder.push_bind(user.id);
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    Ok(Json(
+        mails::fetch_page(&pool, builder, cursor, limit).await?,
+    ))
+}
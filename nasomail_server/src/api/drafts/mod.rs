@@ -0,0 +1,56 @@
+mod attach;
+mod cancel;
+mod create;
+mod list;
+mod send;
+mod update;
+
+use axum::Router;
+
+use nasomail_shared::{api, payload::mail::DraftPayload};
+
+use crate::{
+    api::drafts::{
+        attach::RouterApiDraftsAttach, cancel::RouterApiDraftsCancel,
+        create::RouterApiDraftsCreate, list::RouterApiDraftsList, send::RouterApiDraftsSend,
+        update::RouterApiDraftsUpdate,
+    },
+    app::AppContextGuard,
+    delivery::Mail,
+};
+
+pub trait RouterApiDrafts {
+    /// Registers routes for
+    /// draft related APIs
+    fn with_api_drafts(self) -> Self;
+}
+
+impl RouterApiDrafts for Router<AppContextGuard> {
+    fn with_api_drafts(self) -> Self {
+        self.nest(
+            api::API_DRAFTS,
+            Router::new()
+                .with_api_drafts_create()
+                .with_api_drafts_update()
+                .with_api_drafts_list()
+                .with_api_drafts_send()
+                .with_api_drafts_cancel()
+                .with_api_drafts_attach(),
+        )
+    }
+}
+
+/// Turns the provided `DraftPayload` into a `Mail` sent by `sender` with
+/// `message_id`, trimming every field the same way sent mails are trimmed.
+fn to_mail(sender: String, message_id: String, payload: DraftPayload) -> Mail {
+    Mail {
+        sender,
+        recipient: payload.recipient.trim().to_owned(),
+        subject: payload.subject.trim().to_owned(),
+        body: payload.body.trim().to_owned(),
+        message_id,
+        in_reply_to: None,
+        references: Vec::new(),
+        auto_submitted: false,
+    }
+}
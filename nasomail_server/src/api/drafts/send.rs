@@ -0,0 +1,105 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::post,
+};
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::IdPayload, query::mail::DraftSendQuery};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, mails},
+    app::AppContextGuard,
+    delivery::{self, Destination},
+    schedule,
+};
+
+pub trait RouterApiDraftsSend {
+    /// Registers the `POST /api/drafts/{id}/send` endpoint
+    /// which sends a draft of the authenticated user.
+    fn with_api_drafts_send(self) -> Self;
+}
+
+impl RouterApiDraftsSend for Router<AppContextGuard> {
+    fn with_api_drafts_send(self) -> Self {
+        self.route(api::API_DRAFTS_ID_SEND, post(handle))
+    }
+}
+
+/// Sends the draft with the given `id` through the same delivery path as
+/// `POST /api/mails`, turning the draft into the `sent` copy, then returns
+/// an `IdPayload` containing its (unchanged) `id`, with a `202 Accepted`
+/// if it was queued for delivery to another server.
+///
+/// If `send_at` is set in the provided `DraftSendQuery` or `Config::undo_send_secs`
+/// is not `0`, the draft is scheduled instead, see `schedule::submit`, and a
+/// `202 Accepted` is returned right away. Scheduling a draft that already is
+/// scheduled reschedules it, a `send_at` that is not in the future is
+/// rejected with a `422 Unprocessable Entity`.
+///
+/// Unknown recipients are rejected with a `422 Unprocessable Entity` and
+/// mails that the filter of a local recipient rejects with a `403 Forbidden`,
+/// in both cases the draft is left as it was. Responds with a `404 Not Found`
+/// if the mail does not exist, is not a draft or is not owned by the
+/// authenticated user.
+#[instrument(skip(app, query))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Query(query): Query<DraftSendQuery>,
+) -> Result<(StatusCode, Json<IdPayload>), ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    mails::validate_send_at(&pool, query.send_at.as_deref()).await?;
+    let (origin, undo_secs) = {
+        let cfg = ctx.cfg().await;
+        (cfg.pub_addr().await.clone(), *cfg.undo_send_secs().await)
+    };
+
+    if query.send_at.is_some() || undo_secs > 0 {
+        let mut tx = pool.begin().await?;
+
+        let recipient: String = sqlx::query_scalar(
+            "SELECT recipient FROM mails
+                WHERE id = ? AND user_id = ? AND status = 'draft' AND deleted_at IS NULL",
+        )
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(ApiError::Status(StatusCode::NOT_FOUND))?;
+
+        schedule::submit(
+            &mut tx,
+            &origin,
+            user.id,
+            id,
+            &recipient,
+            query.send_at.as_deref(),
+            undo_secs,
+        )
+        .await?;
+
+        tx.commit().await?;
+        schedule::wake();
+
+        return Ok((StatusCode::ACCEPTED, Json(IdPayload { id })));
+    }
+
+    let delivered = delivery::send_draft(&pool, ctx.events(), &origin, user.id, id, false).await?;
+
+    let status = match delivered.destination {
+        Destination::Local { .. } => StatusCode::OK,
+        Destination::Remote { .. } => StatusCode::ACCEPTED,
+    };
+
+    Ok((
+        status,
+        Json(IdPayload {
+            id: delivered.sent_id,
+        }),
+    ))
+}
@@ -0,0 +1,67 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::put,
+};
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::mail::DraftPayload};
+
+use crate::{
+    api::{bearer::AuthUser, drafts, error::ApiError, mails},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiDraftsUpdate {
+    /// Registers the `PUT /api/drafts/{id}` endpoint
+    /// which overwrites a draft of the authenticated user.
+    fn with_api_drafts_update(self) -> Self;
+}
+
+impl RouterApiDraftsUpdate for Router<AppContextGuard> {
+    fn with_api_drafts_update(self) -> Self {
+        self.route(api::API_DRAFTS_ID, put(handle))
+    }
+}
+
+/// Overwrites the draft with the given `id` with the provided `DraftPayload`.
+///
+/// Meant to be called repeatedly while the user is typing, so it does
+/// nothing but a single `UPDATE`. The recipient is not checked until
+/// the draft is sent.
+///
+/// Responds with a `404 Not Found` if the mail does not exist, is not
+/// a draft or is not owned by the authenticated user.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<DraftPayload>,
+) -> Result<StatusCode, ApiError> {
+    // The message id of a draft is kept as it is, so none is needed here
+    let mail = drafts::to_mail(user.name, String::new(), payload);
+    mails::validate(&mail, 0)?;
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let result = sqlx::query(
+        "UPDATE mails SET recipient = ?, subject = ?, body = ?
+            WHERE id = ? AND user_id = ? AND status = 'draft' AND deleted_at IS NULL",
+    )
+    .bind(&mail.recipient)
+    .bind(&mail.subject)
+    .bind(&mail.body)
+    .bind(id)
+    .bind(user.id)
+    .execute(&*pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
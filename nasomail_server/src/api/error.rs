@@ -0,0 +1,124 @@
+//! The error type shared by the handlers
+//! of the `nasomail_server` REST API.
+
+use axum::{
+    Json,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+
+use nasomail_shared::payload::error::{ErrorPayload, FieldError, FieldErrorKind};
+
+use crate::{blob::StreamError, delivery::DeliveryError, federation::FederationError};
+
+/// A custom error type for REST API handlers
+/// that need to report more than a bare status.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("request failed validation")]
+    Validation(Vec<FieldError>),
+
+    #[error("request conflicts with existing data")]
+    Conflict(Vec<FieldError>),
+
+    #[error("missing or invalid bearer token")]
+    Unauthorized,
+
+    #[error("request failed: {0}")]
+    Status(StatusCode),
+
+    #[error("rejected by the recipient: {0}")]
+    Rejected(String),
+
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("internal error: {0}")]
+    Internal(#[from] anyhow::Error),
+}
+
+impl From<StatusCode> for ApiError {
+    fn from(value: StatusCode) -> Self {
+        Self::Status(value)
+    }
+}
+
+impl From<DeliveryError> for ApiError {
+    fn from(value: DeliveryError) -> Self {
+        match value {
+            DeliveryError::InvalidRecipient(_) => Self::Validation(vec![FieldError::new(
+                "recipient",
+                FieldErrorKind::Malformed,
+            )]),
+            DeliveryError::UnknownRecipient(_) => {
+                Self::Validation(vec![FieldError::new("recipient", FieldErrorKind::NotFound)])
+            }
+            DeliveryError::NoSuchDraft(_) => Self::Status(StatusCode::NOT_FOUND),
+            DeliveryError::Rejected(reason) => Self::Rejected(reason),
+            DeliveryError::Database(e) => Self::Database(e),
+        }
+    }
+}
+
+impl From<FederationError> for ApiError {
+    fn from(value: FederationError) -> Self {
+        match value {
+            FederationError::TooLarge(_) => Self::Status(StatusCode::PAYLOAD_TOO_LARGE),
+            FederationError::Delivery(e) => e.into(),
+            FederationError::Database(e) | FederationError::Stream(StreamError::Database(e)) => {
+                Self::Database(e)
+            }
+            FederationError::Stream(StreamError::Blob(e)) => Self::Internal(e),
+            FederationError::Spool(e) => Self::Internal(e.into()),
+            FederationError::Host(_) => {
+                Self::Validation(vec![FieldError::new("origin", FieldErrorKind::Invalid)])
+            }
+            // Everything else is the fault of the remote server
+            e => {
+                tracing::warn!(err = ?e, "remote server misbehaved");
+                Self::Status(StatusCode::BAD_GATEWAY)
+            }
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, fields) = match self {
+            Self::Validation(fields) => (StatusCode::UNPROCESSABLE_ENTITY, fields),
+            Self::Conflict(fields) => (StatusCode::CONFLICT, fields),
+            Self::Unauthorized => {
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    [(header::WWW_AUTHENTICATE, "Bearer")],
+                )
+                    .into_response();
+            }
+            Self::Status(status) => return status.into_response(),
+            Self::Rejected(reason) => {
+                // The reason is the only thing the sender gets to see
+                let payload = ErrorPayload {
+                    error: reason,
+                    fields: Vec::new(),
+                };
+
+                return (StatusCode::FORBIDDEN, Json(payload)).into_response();
+            }
+            Self::Database(e) => {
+                tracing::error!(err = ?e, "internal server error");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            Self::Internal(e) => {
+                tracing::error!(err = ?e, "internal server error");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+        let payload = ErrorPayload {
+            error: status.canonical_reason().unwrap_or_default().to_lowercase(),
+            fields,
+        };
+
+        (status, Json(payload)).into_response()
+    }
+}
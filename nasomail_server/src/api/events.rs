@@ -0,0 +1,84 @@
+use axum::{
+    Router,
+    extract::State,
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+};
+use futures_util::{Stream, StreamExt, stream};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::event::EventPayload};
+
+use crate::{api::bearer::AuthUser, app::AppContextGuard};
+
+pub trait RouterApiEvents {
+    /// Registers the `GET /api/events` endpoint which streams
+    /// events about the mails of the authenticated user.
+    fn with_api_events(self) -> Self;
+}
+
+impl RouterApiEvents for Router<AppContextGuard> {
+    fn with_api_events(self) -> Self {
+        self.route(api::API_EVENTS, get(handle))
+    }
+}
+
+/// Streams an `EventPayload` as a Server-Sent Event for everything that
+/// happens to the mails of the authenticated user, until the client
+/// disconnects.
+///
+/// Every event has an `id`, so a client that reconnects with the
+/// `Last-Event-ID` header gets the events it missed replayed first.
+/// If they cannot be replayed, i.e, they are no longer buffered or the
+/// server was restarted, or the client falls too far behind, it gets an
+/// `EventPayload::Resync` instead and should fetch everything again.
+#[instrument(skip(app, headers))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let last_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok());
+
+    let subscription = app.ctx().await.events().subscribe(last_id);
+    let user_id = user.id;
+
+    let missed = match subscription.missed {
+        Some(missed) => missed
+            .iter()
+            .filter(|event| event.user_id == user_id)
+            .map(|event| sse(Some(event.id), &event.payload))
+            .collect(),
+        None => vec![sse(Some(subscription.last_id), &EventPayload::Resync)],
+    };
+
+    let live = stream::unfold(subscription.receiver, move |mut receiver| async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) if event.user_id == user_id => sse(Some(event.id), &event.payload),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(_)) => sse(None, &EventPayload::Resync),
+                Err(RecvError::Closed) => return None,
+            };
+
+            return Some((event, receiver));
+        }
+    });
+
+    Sse::new(stream::iter(missed).chain(live)).keep_alive(KeepAlive::default())
+}
+
+/// Turns `payload` into a Server-Sent Event named after its `type`.
+fn sse(id: Option<u64>, payload: &EventPayload) -> Result<Event, axum::Error> {
+    let event = Event::default().event(payload.kind()).json_data(payload)?;
+
+    Ok(match id {
+        Some(id) => event.id(id.to_string()),
+        None => event,
+    })
+}
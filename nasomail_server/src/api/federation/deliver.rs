@@ -0,0 +1,154 @@
+use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+use tracing::instrument;
+
+use nasomail_shared::{
+    address::{self, Address},
+    api,
+    payload::{
+        error::{FieldError, FieldErrorKind},
+        federation::DeliverPayload,
+        mail::ADDRESS_MAX_LEN,
+    },
+};
+
+use crate::{
+    api::{error::ApiError, mails, validate::Validator},
+    app::AppContextGuard,
+    delivery::{self, Mail},
+    federation,
+};
+
+pub trait RouterApiFederationDeliver {
+    /// Registers the `POST /api/federation/deliver` endpoint
+    /// which receives a mail from another NasoMail server.
+    fn with_api_federation_deliver(self) -> Self;
+}
+
+impl RouterApiFederationDeliver for Router<AppContextGuard> {
+    fn with_api_federation_deliver(self) -> Self {
+        self.route(api::API_FEDERATION_DELIVER, post(handle))
+    }
+}
+
+/// Fetches the mail described by the provided `DeliverPayload` from its
+/// `origin` and delivers it into the inbox of its local recipient, with the
+/// sender qualified as `sender@origin`. Responds with a `204 No Content` once
+/// the mail and all of its attachments have been stored.
+///
+/// Nothing in the request itself is trusted, everything is fetched from
+/// the `origin`, which is what authenticates the sending server.
+///
+/// Unknown or non-local recipients, invalid mails and origins that are not
+/// public hosts, see `net::check`, are rejected with a `422 Unprocessable Entity`,
+/// attachments larger than `Config::max_attachment_size` with a `413 Payload Too
+/// Large`, mails that the filter of the recipient rejects with a `403 Forbidden`
+/// and any failure to fetch from the `origin` with a `502 Bad Gateway`.
+#[instrument(skip(app, payload), fields(origin = %payload.origin))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    Json(payload): Json<DeliverPayload>,
+) -> Result<StatusCode, ApiError> {
+    if !address::is_valid_host(&payload.origin) {
+        return Err(ApiError::Validation(vec![FieldError::new(
+            "origin",
+            FieldErrorKind::Malformed,
+        )]));
+    }
+
+    let origin = payload.origin.to_lowercase();
+
+    // Clone the pool so the context is not held while fetching
+    let (pool, events, pub_addr, max) = {
+        let ctx = app.ctx().await;
+        let cfg = ctx.cfg().await;
+
+        (
+            ctx.pool().await.clone(),
+            ctx.events().clone(),
+            cfg.pub_addr().await.clone(),
+            *cfg.max_attachment_size().await,
+        )
+    };
+
+    let fetched = federation::fetch(&origin, &payload.token).await?;
+
+    // The sender must be a bare username on the origin, or it could
+    // pass itself off as a user of some other server
+    let sender = fetched
+        .sender
+        .parse::<Address>()
+        .ok()
+        .filter(|sender| sender.host.is_none())
+        .ok_or_else(|| {
+            ApiError::Validation(vec![FieldError::new("sender", FieldErrorKind::Malformed)])
+        })?;
+
+    let recipient = fetched
+        .recipient
+        .parse::<Address>()
+        .ok()
+        .filter(|recipient| recipient.host.is_some() && recipient.is_local(&pub_addr))
+        .ok_or_else(|| {
+            ApiError::Validation(vec![FieldError::new("recipient", FieldErrorKind::NotFound)])
+        })?;
+
+    let mail = Mail {
+        sender: format!("{}@{}", sender.user, origin),
+        recipient: recipient.user,
+        subject: fetched.subject,
+        body: fetched.body,
+        // Invalid message ids are dropped rather than rejecting the mail
+        message_id: fetched
+            .message_id
+            .as_deref()
+            .and_then(delivery::parse_message_id)
+            .unwrap_or_else(|| delivery::message_id(&origin)),
+        in_reply_to: fetched
+            .in_reply_to
+            .as_deref()
+            .and_then(delivery::parse_message_id),
+        references: delivery::fit_references(
+            fetched
+                .references
+                .iter()
+                .filter_map(|id| delivery::parse_message_id(id))
+                .collect(),
+        ),
+        auto_submitted: fetched.auto_submitted,
+    };
+
+    mails::validate(&mail, 1)?;
+
+    let mut validator = Validator::new();
+    validator.trimmed_len("sender", &mail.sender, 1, ADDRESS_MAX_LEN);
+    for (index, attachment) in fetched.attachments.iter().enumerate() {
+        validator
+            .trimmed_len(
+                &format!("attachments[{index}].filename"),
+                &attachment.filename,
+                0,
+                255,
+            )
+            .trimmed_len(
+                &format!("attachments[{index}].content_type"),
+                &attachment.content_type,
+                1,
+                255,
+            );
+    }
+    validator.finish()?;
+
+    let inbound = federation::receive(
+        &pool,
+        &origin,
+        &payload.token,
+        &mail,
+        &fetched.attachments,
+        max,
+    )
+    .await?;
+
+    delivery::announce(&pool, &events, &pub_addr, inbound);
+
+    Ok(StatusCode::NO_CONTENT)
+}
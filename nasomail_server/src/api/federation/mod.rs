@@ -0,0 +1,28 @@
+mod deliver;
+mod outbound;
+
+use axum::Router;
+
+use nasomail_shared::api;
+
+use crate::{
+    api::federation::{deliver::RouterApiFederationDeliver, outbound::RouterApiFederationOutbound},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiFederation {
+    /// Registers routes for the
+    /// server-to-server APIs
+    fn with_api_federation(self) -> Self;
+}
+
+impl RouterApiFederation for Router<AppContextGuard> {
+    fn with_api_federation(self) -> Self {
+        self.nest(
+            api::API_FEDERATION,
+            Router::new()
+                .with_api_federation_deliver()
+                .with_api_federation_outbound(),
+        )
+    }
+}
@@ -0,0 +1,136 @@
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::Response,
+    routing::get,
+};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::federation::{FederatedAttachment, FederatedMail},
+};
+
+use crate::{api::error::ApiError, app::AppContextGuard, blob, delivery};
+
+pub trait RouterApiFederationOutbound {
+    /// Registers the `GET /api/federation/outbound/{token}` and
+    /// `GET /api/federation/outbound/{token}/attachments/{index}`
+    /// endpoints which serve queued mails to remote servers.
+    fn with_api_federation_outbound(self) -> Self;
+}
+
+impl RouterApiFederationOutbound for Router<AppContextGuard> {
+    fn with_api_federation_outbound(self) -> Self {
+        self.route(api::API_FEDERATION_OUTBOUND_TOKEN, get(handle_mail))
+            .route(
+                api::API_FEDERATION_OUTBOUND_TOKEN_ATTACHMENTS_INDEX,
+                get(handle_attachment),
+            )
+    }
+}
+
+/// Returns the queued mail with the given `token` as a `FederatedMail`.
+///
+/// Responds with a `404 Not Found` if there is no such
+/// mail or it is no longer pending delivery.
+#[instrument(skip(app, token))]
+async fn handle_mail(
+    State(app): State<AppContextGuard>,
+    Path(token): Path<String>,
+) -> Result<Json<FederatedMail>, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let (mail_id, sender, recipient, subject, body, message_id, in_reply_to, refs, auto_submitted): (
+        i64,
+        String,
+        String,
+        String,
+        String,
+        String,
+        Option<String>,
+        String,
+        bool,
+    ) = sqlx::query_as(
+        "SELECT mails.id, mails.sender, delivery_queue.recipient, mails.subject, mails.body,
+                mails.message_id, mails.in_reply_to, mails.refs, mails.auto_submitted
+            FROM delivery_queue
+            JOIN mails ON mails.id = delivery_queue.mail_id
+            WHERE delivery_queue.token = ? AND delivery_queue.status = 'pending'",
+    )
+    .bind(&token)
+    .fetch_optional(&*pool)
+    .await?
+    .ok_or(ApiError::Status(StatusCode::NOT_FOUND))?;
+
+    let attachments: Vec<(String, String, i64, Option<String>)> = sqlx::query_as(
+        "SELECT filename, content_type, size, sha256 FROM attachments
+            WHERE mail_id = ? ORDER BY id",
+    )
+    .bind(mail_id)
+    .fetch_all(&*pool)
+    .await?;
+
+    Ok(Json(FederatedMail {
+        sender,
+        recipient,
+        subject,
+        body,
+        attachments: attachments
+            .into_iter()
+            .map(
+                |(filename, content_type, size, sha256)| FederatedAttachment {
+                    filename,
+                    content_type,
+                    size,
+                    sha256,
+                },
+            )
+            .collect(),
+        message_id: Some(message_id),
+        in_reply_to,
+        references: delivery::split_references(&refs),
+        auto_submitted,
+    }))
+}
+
+/// Streams the data of the attachment at `index` (in the order of
+/// `FederatedMail::attachments`) of the queued mail with the given `token`.
+///
+/// Responds with a `404 Not Found` if there is no such mail or attachment
+/// or the mail is no longer pending delivery.
+#[instrument(skip(app, token))]
+async fn handle_attachment(
+    State(app): State<AppContextGuard>,
+    Path((token, index)): Path<(String, i64)>,
+) -> Result<Response, ApiError> {
+    // Clone the pool so the context is not held for the whole download
+    let pool = app.ctx().await.pool().await.clone();
+
+    let (id, size): (i64, i64) = sqlx::query_as(
+        "SELECT attachments.id, attachments.size FROM delivery_queue
+            JOIN attachments ON attachments.mail_id = delivery_queue.mail_id
+            WHERE delivery_queue.token = ? AND delivery_queue.status = 'pending'
+            ORDER BY attachments.id
+            LIMIT 1 OFFSET ?",
+    )
+    .bind(&token)
+    .bind(index)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(ApiError::Status(StatusCode::NOT_FOUND))?;
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, size)
+        .body(Body::from_stream(blob::read_stream(
+            pool,
+            id,
+            0,
+            size as u64,
+        )))
+        .map_err(|e| ApiError::Internal(e.into()))
+}
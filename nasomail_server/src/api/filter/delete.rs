@@ -0,0 +1,45 @@
+use axum::{Router, extract::State, http::StatusCode, routing::delete};
+use tracing::instrument;
+
+use nasomail_shared::api;
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiFilterDelete {
+    /// Registers the `DELETE /api/filter` endpoint which
+    /// removes the Sieve script of the authenticated user.
+    fn with_api_filter_delete(self) -> Self;
+}
+
+impl RouterApiFilterDelete for Router<AppContextGuard> {
+    fn with_api_filter_delete(self) -> Self {
+        self.route(api::API_FILTER_ROOT, delete(handle))
+    }
+}
+
+/// Removes the Sieve script of the authenticated user, so every mail
+/// delivered from now on goes into their inbox unfiltered.
+///
+/// Responds with a `404 Not Found` if the user has no script.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+) -> Result<StatusCode, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let deleted = sqlx::query("DELETE FROM filters WHERE user_id = ?")
+        .bind(user.id)
+        .execute(&*pool)
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,43 @@
+use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::filter::FilterPayload};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiFilterGet {
+    /// Registers the `GET /api/filter` endpoint which
+    /// returns the Sieve script of the authenticated user.
+    fn with_api_filter_get(self) -> Self;
+}
+
+impl RouterApiFilterGet for Router<AppContextGuard> {
+    fn with_api_filter_get(self) -> Self {
+        self.route(api::API_FILTER_ROOT, get(handle))
+    }
+}
+
+/// Returns the Sieve script that filters the mails delivered
+/// to the authenticated user as a `FilterPayload`.
+///
+/// Responds with a `404 Not Found` if the user has no script.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+) -> Result<Json<FilterPayload>, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let script: Option<String> = sqlx::query_scalar("SELECT script FROM filters WHERE user_id = ?")
+        .bind(user.id)
+        .fetch_optional(&*pool)
+        .await?;
+
+    let script = script.ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(FilterPayload { script }))
+}
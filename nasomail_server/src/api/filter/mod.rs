@@ -0,0 +1,35 @@
+mod delete;
+mod get;
+mod update;
+mod validate;
+
+use axum::Router;
+
+use nasomail_shared::api;
+
+use crate::{
+    api::filter::{
+        delete::RouterApiFilterDelete, get::RouterApiFilterGet, update::RouterApiFilterUpdate,
+        validate::RouterApiFilterValidate,
+    },
+    app::AppContextGuard,
+};
+
+pub trait RouterApiFilter {
+    /// Registers routes for
+    /// filter related APIs
+    fn with_api_filter(self) -> Self;
+}
+
+impl RouterApiFilter for Router<AppContextGuard> {
+    fn with_api_filter(self) -> Self {
+        self.nest(
+            api::API_FILTER,
+            Router::new()
+                .with_api_filter_get()
+                .with_api_filter_update()
+                .with_api_filter_delete()
+                .with_api_filter_validate(),
+        )
+    }
+}
@@ -0,0 +1,67 @@
+use axum::{Json, Router, extract::State, http::StatusCode, routing::put};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{
+        error::FieldErrorKind,
+        filter::{FilterPayload, SCRIPT_MAX_LEN},
+    },
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, validate::Validator},
+    app::AppContextGuard,
+    sieve,
+};
+
+pub trait RouterApiFilterUpdate {
+    /// Registers the `PUT /api/filter` endpoint which
+    /// sets the Sieve script of the authenticated user.
+    fn with_api_filter_update(self) -> Self;
+}
+
+impl RouterApiFilterUpdate for Router<AppContextGuard> {
+    fn with_api_filter_update(self) -> Self {
+        self.route(api::API_FILTER_ROOT, put(handle))
+    }
+}
+
+/// Replaces the Sieve script of the authenticated user with the one in the
+/// provided `FilterPayload`, which is run on every mail delivered from now on.
+///
+/// Responds with a `422 Unprocessable Entity` if the script is too long or
+/// is not valid, `POST /api/filter/validate` tells what is wrong with it.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Json(payload): Json<FilterPayload>,
+) -> Result<StatusCode, ApiError> {
+    Validator::new()
+        .len("script", &payload.script, 0, SCRIPT_MAX_LEN)
+        .finish()?;
+
+    Validator::new()
+        .check(
+            "script",
+            sieve::compile(&payload.script).is_ok(),
+            FieldErrorKind::Malformed,
+        )
+        .finish()?;
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    sqlx::query(
+        "INSERT INTO filters (user_id, script) VALUES (?, ?)
+         ON CONFLICT (user_id) DO UPDATE
+         SET script = excluded.script, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(user.id)
+    .bind(&payload.script)
+    .execute(&*pool)
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,53 @@
+use axum::{Json, Router, routing::post};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::filter::{FilterError, FilterPayload, FilterValidationPayload, SCRIPT_MAX_LEN},
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, validate::Validator},
+    app::AppContextGuard,
+    sieve,
+};
+
+pub trait RouterApiFilterValidate {
+    /// Registers the `POST /api/filter/validate` endpoint
+    /// which checks a Sieve script without saving it.
+    fn with_api_filter_validate(self) -> Self;
+}
+
+impl RouterApiFilterValidate for Router<AppContextGuard> {
+    fn with_api_filter_validate(self) -> Self {
+        self.route(api::API_FILTER_VALIDATE, post(handle))
+    }
+}
+
+/// Checks the Sieve script in the provided `FilterPayload` and returns every
+/// problem with it, along with its line and column, as a `FilterValidationPayload`.
+///
+/// A script with problems is still a `200 OK`, it only responds with a
+/// `422 Unprocessable Entity` if the script is too long to be saved at all.
+#[instrument(skip(payload))]
+async fn handle(
+    _user: AuthUser,
+    Json(payload): Json<FilterPayload>,
+) -> Result<Json<FilterValidationPayload>, ApiError> {
+    Validator::new()
+        .len("script", &payload.script, 0, SCRIPT_MAX_LEN)
+        .finish()?;
+
+    let errors = sieve::compile(&payload.script)
+        .err()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|e| FilterError {
+            line: e.line,
+            column: e.column,
+            message: e.message,
+        })
+        .collect();
+
+    Ok(Json(FilterValidationPayload { errors }))
+}
@@ -0,0 +1,53 @@
+use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{IdPayload, label::LabelPayload},
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, labels},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiLabelsCreate {
+    /// Registers the `POST /api/labels` endpoint
+    /// which creates a new label for the authenticated user.
+    fn with_api_labels_create(self) -> Self;
+}
+
+impl RouterApiLabelsCreate for Router<AppContextGuard> {
+    fn with_api_labels_create(self) -> Self {
+        self.route(api::API_LABELS_ROOT, post(handle))
+    }
+}
+
+/// Saves the provided `LabelPayload` as a new label owned by the
+/// authenticated user, then returns an `IdPayload` containing its `id`.
+///
+/// Responds with a `409 Conflict` if the user already
+/// has a label with the same name, ignoring case.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Json(payload): Json<LabelPayload>,
+) -> Result<(StatusCode, Json<IdPayload>), ApiError> {
+    let color = labels::validate(&payload)?;
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO labels (user_id, name, color) VALUES (?, ?, ?) RETURNING id",
+    )
+    .bind(user.id)
+    .bind(&payload.name)
+    .bind(color)
+    .fetch_one(&*pool)
+    .await
+    .map_err(labels::map_error)?;
+
+    Ok((StatusCode::CREATED, Json(IdPayload { id })))
+}
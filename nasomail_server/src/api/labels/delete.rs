@@ -0,0 +1,53 @@
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::delete,
+};
+use tracing::instrument;
+
+use nasomail_shared::api;
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiLabelsDelete {
+    /// Registers the `DELETE /api/labels/{id}` endpoint
+    /// which deletes a label of the authenticated user.
+    fn with_api_labels_delete(self) -> Self;
+}
+
+impl RouterApiLabelsDelete for Router<AppContextGuard> {
+    fn with_api_labels_delete(self) -> Self {
+        self.route(api::API_LABELS_ID, delete(handle))
+    }
+}
+
+/// Deletes the label with the given `id` and removes it from
+/// every mail that has it, the mails themselves are kept.
+///
+/// Responds with a `404 Not Found` if the label does not exist
+/// or is not owned by the authenticated user.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let deleted = sqlx::query("DELETE FROM labels WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user.id)
+        .execute(&*pool)
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
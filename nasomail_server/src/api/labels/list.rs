@@ -0,0 +1,71 @@
+use axum::{Json, Router, extract::State, routing::get};
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::label::LabelSummary};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiLabelsList {
+    /// Registers the `GET /api/labels` endpoint
+    /// which lists the labels of the authenticated user.
+    fn with_api_labels_list(self) -> Self;
+}
+
+impl RouterApiLabelsList for Router<AppContextGuard> {
+    fn with_api_labels_list(self) -> Self {
+        self.route(api::API_LABELS_ROOT, get(handle))
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct LabelRow {
+    id: i64,
+    name: String,
+    color: String,
+    count: i64,
+    unread: i64,
+    created_at: String,
+}
+
+/// Lists the labels owned by the authenticated user, ordered
+/// by name, as `LabelSummary`s along with how many mails,
+/// and how many unread ones, have each of them.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+) -> Result<Json<Vec<LabelSummary>>, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let rows: Vec<LabelRow> = sqlx::query_as(
+        "SELECT labels.id, labels.name, labels.color, labels.created_at,
+                COUNT(mails.id) AS count,
+                COUNT(CASE WHEN mails.status = 'new' THEN 1 END) AS unread
+            FROM labels
+            LEFT JOIN mail_labels ON mail_labels.label_id = labels.id
+            LEFT JOIN mails ON mails.id = mail_labels.mail_id AND mails.deleted_at IS NULL
+            WHERE labels.user_id = ?
+            GROUP BY labels.id
+            ORDER BY labels.name, labels.id",
+    )
+    .bind(user.id)
+    .fetch_all(&*pool)
+    .await?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| LabelSummary {
+                id: row.id,
+                name: row.name,
+                color: row.color,
+                count: row.count as u32,
+                unread: row.unread as u32,
+                created_at: row.created_at,
+            })
+            .collect(),
+    ))
+}
@@ -0,0 +1,156 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::post,
+};
+use sqlx::SqliteConnection;
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{
+        error::{FieldError, FieldErrorKind},
+        label::{LabelMailsPayload, MAILS_MAX_LEN},
+    },
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, validate::Validator},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiLabelsMails {
+    /// Registers the `POST /api/labels/{id}/mails` and `DELETE /api/labels/{id}/mails`
+    /// endpoints which add a label of the authenticated user to mails or remove it from them.
+    fn with_api_labels_mails(self) -> Self;
+}
+
+impl RouterApiLabelsMails for Router<AppContextGuard> {
+    fn with_api_labels_mails(self) -> Self {
+        self.route(api::API_LABELS_ID_MAILS, post(apply).delete(remove))
+    }
+}
+
+/// Adds the label with the given `id` to every mail in the provided
+/// `LabelMailsPayload`, mails that already have it are left as they are.
+///
+/// Nothing is changed if any of the mails does not exist or is not owned
+/// by the authenticated user, which is rejected with a `422 Unprocessable Entity`.
+/// Responds with a `404 Not Found` if the label does not exist
+/// or is not owned by the authenticated user.
+#[instrument(skip(app, payload))]
+async fn apply(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<LabelMailsPayload>,
+) -> Result<StatusCode, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let mut tx = pool.begin().await?;
+    let ids = check(&mut tx, user.id, id, &payload.ids).await?;
+
+    sqlx::query(
+        "INSERT OR IGNORE INTO mail_labels (mail_id, label_id)
+            SELECT value, ? FROM JSON_EACH(?)",
+    )
+    .bind(id)
+    .bind(ids)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Removes the label with the given `id` from every mail in the provided
+/// `LabelMailsPayload`, mails that do not have it are left as they are.
+///
+/// Fails like `apply`.
+#[instrument(skip(app, payload))]
+async fn remove(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<LabelMailsPayload>,
+) -> Result<StatusCode, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let mut tx = pool.begin().await?;
+    let ids = check(&mut tx, user.id, id, &payload.ids).await?;
+
+    sqlx::query(
+        "DELETE FROM mail_labels
+            WHERE label_id = ? AND mail_id IN (SELECT value FROM JSON_EACH(?))",
+    )
+    .bind(id)
+    .bind(ids)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Checks that the label `id` and every one of the `ids`, of which
+/// there must be between `1` and `MAILS_MAX_LEN`, are owned by `user_id`,
+/// then returns the `ids` as a JSON array to be used with `JSON_EACH`.
+///
+/// # Errors
+///
+/// Returns `Err` if they are not or the database could not be queried.
+///
+async fn check(
+    conn: &mut SqliteConnection,
+    user_id: i64,
+    id: i64,
+    ids: &[i64],
+) -> Result<String, ApiError> {
+    let exists: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM labels WHERE id = ? AND user_id = ?)")
+            .bind(id)
+            .bind(user_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+    if !exists {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    let len_kind = if ids.is_empty() {
+        FieldErrorKind::TooShort { min: 1 }
+    } else {
+        FieldErrorKind::TooLong { max: MAILS_MAX_LEN }
+    };
+
+    Validator::new()
+        .check("ids", (1..=MAILS_MAX_LEN).contains(&ids.len()), len_kind)
+        .finish()?;
+
+    let ids = serde_json::to_string(ids).unwrap_or_default();
+
+    let missing: bool = sqlx::query_scalar(
+        "SELECT EXISTS(
+            SELECT 1 FROM JSON_EACH(?) AS ids
+                WHERE NOT EXISTS(SELECT 1 FROM mails WHERE id = ids.value AND user_id = ?)
+        )",
+    )
+    .bind(&ids)
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if missing {
+        return Err(ApiError::Validation(vec![FieldError::new(
+            "ids",
+            FieldErrorKind::NotFound,
+        )]));
+    }
+
+    Ok(ids)
+}
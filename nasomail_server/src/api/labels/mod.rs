@@ -0,0 +1,75 @@
+mod create;
+mod delete;
+mod list;
+mod mails;
+mod update;
+
+use axum::Router;
+use sqlx::error::ErrorKind;
+
+use nasomail_shared::{
+    api,
+    payload::{
+        error::{FieldError, FieldErrorKind},
+        label::{LabelPayload, NAME_MAX_LEN},
+    },
+};
+
+use crate::{
+    api::{
+        error::ApiError,
+        labels::{
+            create::RouterApiLabelsCreate, delete::RouterApiLabelsDelete,
+            list::RouterApiLabelsList, mails::RouterApiLabelsMails, update::RouterApiLabelsUpdate,
+        },
+        validate::Validator,
+    },
+    app::AppContextGuard,
+};
+
+pub trait RouterApiLabels {
+    /// Registers routes for
+    /// label related APIs
+    fn with_api_labels(self) -> Self;
+}
+
+impl RouterApiLabels for Router<AppContextGuard> {
+    fn with_api_labels(self) -> Self {
+        self.nest(
+            api::API_LABELS,
+            Router::new()
+                .with_api_labels_create()
+                .with_api_labels_list()
+                .with_api_labels_update()
+                .with_api_labels_delete()
+                .with_api_labels_mails(),
+        )
+    }
+}
+
+/// Validates `payload` against the constraints of the `labels` table
+/// and returns its `color` in lowercase, the way it is stored.
+fn validate(payload: &LabelPayload) -> Result<String, ApiError> {
+    let is_color = payload.color.len() == 7
+        && payload.color.starts_with('#')
+        && payload.color[1..].chars().all(|c| c.is_ascii_hexdigit());
+
+    Validator::new()
+        .trimmed_len("name", &payload.name, 1, NAME_MAX_LEN)
+        .check("color", is_color, FieldErrorKind::Malformed)
+        .finish()?;
+
+    Ok(payload.color.to_ascii_lowercase())
+}
+
+/// Turns the error of inserting or updating a label into an `ApiError`,
+/// reporting a name that is already used by another label as taken.
+fn map_error(e: sqlx::Error) -> ApiError {
+    match e.as_database_error().map(|e| e.kind()) {
+        Some(ErrorKind::UniqueViolation) => {
+            ApiError::Conflict(vec![FieldError::new("name", FieldErrorKind::Taken)])
+        }
+        Some(ErrorKind::CheckViolation) => ApiError::Validation(Vec::new()),
+        _ => ApiError::Database(e),
+    }
+}
@@ -0,0 +1,60 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::put,
+};
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::label::LabelPayload};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, labels},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiLabelsUpdate {
+    /// Registers the `PUT /api/labels/{id}` endpoint
+    /// which renames or recolors a label of the authenticated user.
+    fn with_api_labels_update(self) -> Self;
+}
+
+impl RouterApiLabelsUpdate for Router<AppContextGuard> {
+    fn with_api_labels_update(self) -> Self {
+        self.route(api::API_LABELS_ID, put(handle))
+    }
+}
+
+/// Replaces the name and color of the label with the given `id` with
+/// the ones in the provided `LabelPayload`, keeping all of its mails.
+///
+/// Responds with a `404 Not Found` if the label does not exist or is not
+/// owned by the authenticated user and with a `409 Conflict` if another
+/// label of the user already has the same name, ignoring case.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<LabelPayload>,
+) -> Result<StatusCode, ApiError> {
+    let color = labels::validate(&payload)?;
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let updated = sqlx::query("UPDATE labels SET name = ?, color = ? WHERE id = ? AND user_id = ?")
+        .bind(&payload.name)
+        .bind(color)
+        .bind(id)
+        .bind(user.id)
+        .execute(&*pool)
+        .await
+        .map_err(labels::map_error)?;
+
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,102 @@
+use axum::{
+    Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::delete,
+};
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::event::EventPayload, query::mail::MailDeleteQuery};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiMailsDelete {
+    /// Registers the `DELETE /api/mails/{id}` endpoint
+    /// which moves a mail of the authenticated user to the trash.
+    fn with_api_mails_delete(self) -> Self;
+}
+
+impl RouterApiMailsDelete for Router<AppContextGuard> {
+    fn with_api_mails_delete(self) -> Self {
+        self.route(api::API_MAILS_ID, delete(handle))
+    }
+}
+
+/// Moves the mail with the given `id` to the trash and publishes an
+/// `EventPayload::Trashed`, mails that already are in the trash are left
+/// as they are. It is deleted for good after `Config::trash_retention_days`
+/// unless it is restored before that. Scheduled drafts are not sent anymore.
+///
+/// If `permanent` is set in the provided `MailDeleteQuery`, the mail is
+/// deleted for good right away instead, along with its attachments, and
+/// an `EventPayload::Deleted` is published.
+///
+/// Responds with a `404 Not Found` if the mail does not exist
+/// or is not owned by the authenticated user.
+#[instrument(skip(app, query))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Query(query): Query<MailDeleteQuery>,
+) -> Result<StatusCode, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    if query.permanent {
+        let deleted = sqlx::query("DELETE FROM mails WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user.id)
+            .execute(&*pool)
+            .await?;
+
+        if deleted.rows_affected() == 0 {
+            return Err(ApiError::Status(StatusCode::NOT_FOUND));
+        }
+
+        ctx.events().publish(user.id, EventPayload::Deleted { id });
+
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let trashed = sqlx::query(
+        "UPDATE mails SET deleted_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+    )
+    .bind(id)
+    .bind(user.id)
+    .execute(&mut *tx)
+    .await?;
+
+    if trashed.rows_affected() > 0 {
+        // Trashed drafts are never sent, even if they are restored before they were due
+        sqlx::query("DELETE FROM send_jobs WHERE mail_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        ctx.events().publish(user.id, EventPayload::Trashed { id });
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    // The mail may already be in the trash
+    let exists: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM mails WHERE id = ? AND user_id = ?)")
+            .bind(id)
+            .bind(user.id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+    if !exists {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
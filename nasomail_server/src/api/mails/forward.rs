@@ -0,0 +1,92 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::post,
+};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{
+        IdPayload,
+        mail::{BODY_MAX_LEN, ForwardPayload},
+    },
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, mails},
+    app::AppContextGuard,
+    delivery::{self, Mail},
+    message,
+};
+
+pub trait RouterApiMailsForward {
+    /// Registers the `POST /api/mails/{id}/forward` endpoint
+    /// which forwards a mail of the authenticated user.
+    fn with_api_mails_forward(self) -> Self;
+}
+
+impl RouterApiMailsForward for Router<AppContextGuard> {
+    fn with_api_mails_forward(self) -> Self {
+        self.route(api::API_MAILS_ID_FORWARD, post(handle))
+    }
+}
+
+/// Forwards the mail with the given `id` along with its attachments to the
+/// recipient of the provided `ForwardPayload`, then responds like `POST /api/mails`.
+///
+/// The subject is prefixed with `Fwd:`, the original mail is included below
+/// the body (which is cut short if it gets too long) and the mail is linked
+/// to it through `references`, so the forward ends up in its thread.
+///
+/// Responds with a `404 Not Found` if the mail does not exist, is a draft
+/// or is not owned by the authenticated user.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<ForwardPayload>,
+) -> Result<(StatusCode, Json<IdPayload>), ApiError> {
+    let ctx = app.ctx().await;
+    let origin = ctx.cfg().await.pub_addr().await.clone();
+
+    let original = mails::fetch_original(&*ctx.pool().await, user.id, id)
+        .await?
+        .ok_or(ApiError::Status(StatusCode::NOT_FOUND))?;
+
+    let mail = Mail {
+        sender: user.name,
+        recipient: payload.recipient.trim().to_owned(),
+        subject: mails::prefix_subject("Fwd:", &original.subject),
+        body: message::fit(
+            &format!(
+                "{}\n\n---------- Forwarded message ----------\n\
+                 From: {}\n\
+                 Date: {} UTC\n\
+                 Subject: {}\n\
+                 To: {}\n\n{}",
+                payload.body.trim(),
+                original.sender,
+                original.created_at,
+                original.subject,
+                original.recipient,
+                original.body
+            ),
+            BODY_MAX_LEN,
+        ),
+        message_id: delivery::message_id(&origin),
+        in_reply_to: None,
+        references: delivery::references_of(
+            &original.message_id,
+            &delivery::split_references(&original.refs),
+        ),
+        auto_submitted: false,
+    };
+
+    mails::validate(&mail, 1)?;
+    mails::validate_send_at(&*ctx.pool().await, payload.send_at.as_deref()).await?;
+
+    mails::send_or_schedule(&ctx, user.id, &mail, Some(id), payload.send_at.as_deref()).await
+}
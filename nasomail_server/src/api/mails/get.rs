@@ -0,0 +1,79 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{
+        event::EventPayload,
+        mail::{MailDetail, MailStatus},
+    },
+    query::mail::MailGetQuery,
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, mails},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiMailsGet {
+    /// Registers the `GET /api/mails/{id}` endpoint
+    /// which fetches a single mail of the authenticated user.
+    fn with_api_mails_get(self) -> Self;
+}
+
+impl RouterApiMailsGet for Router<AppContextGuard> {
+    fn with_api_mails_get(self) -> Self {
+        self.route(api::API_MAILS_ID, get(handle))
+    }
+}
+
+/// Fetches the mail with the given `id` as a `MailDetail`, including
+/// its body and the metadata of its attachments.
+///
+/// Opening a `new` mail marks it as `read` right before fetching it and
+/// publishes an `EventPayload::StatusChanged`, unless `peek` is set in
+/// the provided `MailGetQuery`.
+///
+/// Responds with a `404 Not Found` if the mail does not exist
+/// or is not owned by the authenticated user.
+#[instrument(skip(app, query))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Query(query): Query<MailGetQuery>,
+) -> Result<Json<MailDetail>, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    if !query.peek {
+        let updated = sqlx::query(
+            "UPDATE mails SET status = 'read' WHERE id = ? AND user_id = ? AND status = 'new'",
+        )
+        .bind(id)
+        .bind(user.id)
+        .execute(&*pool)
+        .await?;
+
+        if updated.rows_affected() > 0 {
+            ctx.events().publish(
+                user.id,
+                EventPayload::StatusChanged {
+                    id,
+                    status: MailStatus::Read,
+                },
+            );
+        }
+    }
+
+    Ok(Json(
+        mails::fetch_detail(&pool, user.id, id)
+            .await?
+            .ok_or(ApiError::Status(StatusCode::NOT_FOUND))?,
+    ))
+}
@@ -0,0 +1,96 @@
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    routing::get,
+};
+use sqlx::{QueryBuilder, Sqlite};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{Page, mail::MailSummary},
+    query::mail::MailListQuery,
+};
+
+use crate::{
+    api::{
+        bearer::AuthUser,
+        error::ApiError,
+        mails,
+        pagination::{self, Cursor},
+    },
+    app::AppContextGuard,
+};
+
+pub trait RouterApiMailsList {
+    /// Registers the `GET /api/mails` endpoint
+    /// which lists the mails of the authenticated user.
+    fn with_api_mails_list(self) -> Self;
+}
+
+impl RouterApiMailsList for Router<AppContextGuard> {
+    fn with_api_mails_list(self) -> Self {
+        self.route(api::API_MAILS_ROOT, get(handle))
+    }
+}
+
+/// Lists the mails owned by the authenticated user, newest first,
+/// as a `Page` of `MailSummary`s filtered by the provided `MailListQuery`.
+///
+/// Only summaries are returned so that large inboxes stay cheap to list,
+/// fetch a single mail to get its body.
+#[instrument(skip(app, query))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Query(query): Query<MailListQuery>,
+) -> Result<Json<Page<MailSummary>>, ApiError> {
+    let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+    let limit = pagination::limit(query.limit);
+
+    let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT id, subject, sender, recipient, status, created_at
+            FROM mails WHERE user_id = ",
+    );
+    builder.push_bind(user.id);
+
+    if query.trashed {
+        builder.push(" AND deleted_at IS NOT NULL");
+    } else {
+        builder.push(" AND deleted_at IS NULL");
+    }
+    if let Some(status) = query.status {
+        builder.push(" AND status = ").push_bind(status.as_str());
+    }
+    if let Some(sender) = query.sender {
+        builder
+            .push(" AND sender = ")
+            .push_bind(sender)
+            .push(" COLLATE NOCASE");
+    }
+    if let Some(label) = query.label {
+        builder
+            .push(" AND id IN (SELECT mail_id FROM mail_labels WHERE label_id = ")
+            .push_bind(label)
+            .push(")");
+    }
+    if let Some(since) = query.since {
+        builder
+            .push(" AND created_at >= DATETIME(")
+            .push_bind(since)
+            .push(")");
+    }
+    if let Some(until) = query.until {
+        builder
+            .push(" AND created_at < DATETIME(")
+            .push_bind(until)
+            .push(")");
+    }
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    Ok(Json(
+        mails::fetch_page(&pool, builder, cursor, limit).await?,
+    ))
+}
@@ -0,0 +1,366 @@
+mod delete;
+mod forward;
+mod get;
+mod list;
+mod reply;
+mod restore;
+mod search;
+mod send;
+mod status;
+
+use axum::{Json, Router, http::StatusCode};
+use sqlx::{QueryBuilder, Sqlite, sqlite::SqlitePool};
+
+use nasomail_shared::{
+    api,
+    payload::{
+        IdPayload, Page,
+        error::FieldErrorKind,
+        mail::{
+            ADDRESS_MAX_LEN, AttachmentSummary, BODY_MAX_LEN, MailDetail, MailStatus, MailSummary,
+            SUBJECT_MAX_LEN,
+        },
+    },
+};
+
+use crate::{
+    api::mails::{
+        delete::RouterApiMailsDelete, forward::RouterApiMailsForward, get::RouterApiMailsGet,
+        list::RouterApiMailsList, reply::RouterApiMailsReply, restore::RouterApiMailsRestore,
+        search::RouterApiMailsSearch, send::RouterApiMailsSend, status::RouterApiMailsStatus,
+    },
+    api::{
+        error::ApiError,
+        pagination::{self, Cursor},
+        validate::{Validator, is_datetime},
+    },
+    app::{AppContext, AppContextGuard},
+    delivery::{self, Destination, Mail},
+    message, schedule,
+};
+
+pub trait RouterApiMails {
+    /// Registers routes for
+    /// mail related APIs
+    fn with_api_mails(self) -> Self;
+}
+
+impl RouterApiMails for Router<AppContextGuard> {
+    fn with_api_mails(self) -> Self {
+        self.nest(
+            api::API_MAILS,
+            Router::new()
+                .with_api_mails_send()
+                .with_api_mails_list()
+                .with_api_mails_search()
+                .with_api_mails_get()
+                .with_api_mails_status()
+                .with_api_mails_reply()
+                .with_api_mails_forward()
+                .with_api_mails_delete()
+                .with_api_mails_restore(),
+        )
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub struct MailSummaryRow {
+    pub id: i64,
+    pub subject: String,
+    pub sender: String,
+    pub recipient: String,
+    pub status: String,
+    pub created_at: String,
+}
+
+impl From<MailSummaryRow> for MailSummary {
+    fn from(value: MailSummaryRow) -> Self {
+        Self {
+            id: value.id,
+            subject: value.subject,
+            sender: value.sender,
+            recipient: value.recipient,
+            // The `CHECK` constraint on `status` guarantees this parses
+            status: value.status.parse().unwrap_or(MailStatus::Read),
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct MailDetailRow {
+    id: i64,
+    subject: String,
+    body: String,
+    sender: String,
+    recipient: String,
+    status: String,
+    created_at: String,
+    message_id: String,
+    in_reply_to: Option<String>,
+    refs: String,
+    thread_id: i64,
+    deleted_at: Option<String>,
+    send_at: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct AttachmentRow {
+    id: i64,
+    filename: String,
+    content_type: String,
+    size: i64,
+    sha256: Option<String>,
+    created_at: String,
+}
+
+impl From<AttachmentRow> for AttachmentSummary {
+    fn from(value: AttachmentRow) -> Self {
+        Self {
+            id: value.id,
+            filename: value.filename,
+            content_type: value.content_type,
+            size: value.size,
+            sha256: value.sha256,
+            created_at: value.created_at,
+        }
+    }
+}
+
+/// Fetches the mail `id` owned by `user_id` as a `MailDetail`, including
+/// its body, its labels and the metadata of its attachments.
+///
+/// Returns `Ok(None)` if there is no such mail.
+///
+pub async fn fetch_detail(
+    pool: &SqlitePool,
+    user_id: i64,
+    id: i64,
+) -> Result<Option<MailDetail>, sqlx::Error> {
+    let Some(mail): Option<MailDetailRow> = sqlx::query_as(
+        "SELECT id, subject, body, sender, recipient, status, created_at,
+                message_id, in_reply_to, refs, thread_id, deleted_at,
+                (SELECT run_at FROM send_jobs WHERE mail_id = mails.id) AS send_at
+            FROM mails WHERE id = ? AND user_id = ?",
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let attachments: Vec<AttachmentRow> = sqlx::query_as(
+        "SELECT id, filename, content_type, size, sha256, created_at
+            FROM attachments WHERE mail_id = ? ORDER BY id",
+    )
+    .bind(mail.id)
+    .fetch_all(pool)
+    .await?;
+
+    let labels: Vec<i64> =
+        sqlx::query_scalar("SELECT label_id FROM mail_labels WHERE mail_id = ? ORDER BY label_id")
+            .bind(mail.id)
+            .fetch_all(pool)
+            .await?;
+
+    Ok(Some(MailDetail {
+        id: mail.id,
+        subject: mail.subject,
+        body: mail.body,
+        sender: mail.sender,
+        recipient: mail.recipient,
+        // The `CHECK` constraint on `status` guarantees this parses
+        status: mail.status.parse().unwrap_or(MailStatus::Read),
+        created_at: mail.created_at,
+
+        message_id: mail.message_id,
+        in_reply_to: mail.in_reply_to,
+        references: delivery::split_references(&mail.refs),
+        thread_id: mail.thread_id,
+
+        labels,
+
+        deleted_at: mail.deleted_at,
+
+        send_at: mail.send_at,
+
+        attachments: attachments
+            .into_iter()
+            .map(AttachmentSummary::from)
+            .collect(),
+    }))
+}
+
+/// A mail that is being replied to or forwarded.
+#[derive(sqlx::FromRow)]
+struct OriginalRow {
+    sender: String,
+    recipient: String,
+    subject: String,
+    body: String,
+    status: String,
+    created_at: String,
+    message_id: String,
+    refs: String,
+}
+
+/// Fetches the mail `id` owned by `user_id` to reply to or forward it.
+///
+/// Returns `Ok(None)` if there is no such mail or it is a draft.
+///
+async fn fetch_original(
+    pool: &SqlitePool,
+    user_id: i64,
+    id: i64,
+) -> Result<Option<OriginalRow>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT sender, recipient, subject, body, status, created_at, message_id, refs
+            FROM mails WHERE id = ? AND user_id = ? AND status != 'draft'",
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Prefixes `subject` with `prefix`, e.g, `Re:`, unless it already starts
+/// with it, and cuts it down to fit the `mails` table.
+fn prefix_subject(prefix: &str, subject: &str) -> String {
+    let prefixed = subject
+        .get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix));
+
+    if prefixed {
+        message::fit(subject, SUBJECT_MAX_LEN)
+    } else {
+        message::fit(&format!("{prefix} {subject}"), SUBJECT_MAX_LEN)
+    }
+}
+
+/// Validates `mail` against the constraints of the `mails` table.
+///
+/// Drafts may be saved without a recipient, so `recipient_min`
+/// is `0` for drafts and `1` for mails that are about to be sent.
+pub fn validate(mail: &Mail, recipient_min: usize) -> Result<(), ApiError> {
+    Validator::new()
+        .trimmed_len("recipient", &mail.recipient, recipient_min, ADDRESS_MAX_LEN)
+        .trimmed_len("subject", &mail.subject, 0, SUBJECT_MAX_LEN)
+        .trimmed_len("body", &mail.body, 0, BODY_MAX_LEN)
+        .finish()
+}
+
+/// Validates `send_at` of a mail that is about to be sent, see `schedule::submit`,
+/// which must be in the future according to the clock of the database.
+pub async fn validate_send_at(pool: &SqlitePool, send_at: Option<&str>) -> Result<(), ApiError> {
+    let is_well_formed = send_at.is_none_or(is_datetime);
+
+    // Malformed dates are reported as such, not as being in the past
+    let is_future: bool = match send_at {
+        Some(send_at) if is_well_formed => {
+            sqlx::query_scalar("SELECT ? > CURRENT_TIMESTAMP")
+                .bind(send_at)
+                .fetch_one(pool)
+                .await?
+        }
+        _ => true,
+    };
+
+    Validator::new()
+        .check("send_at", is_well_formed, FieldErrorKind::Malformed)
+        .check("send_at", is_future, FieldErrorKind::Invalid)
+        .finish()
+}
+
+/// Sends `mail` on behalf of `user_id` along with the attachments of the mail
+/// `attachments_of`, see `delivery::send`, and returns an `IdPayload` containing
+/// the `id` of the `sent` copy, with a `201 Created` if it was delivered locally
+/// and with a `202 Accepted` if it was queued for delivery to another server.
+///
+/// If `send_at` is given or `Config::undo_send_secs` is not `0`, the mail is saved
+/// as a draft and scheduled instead, see `schedule::submit`, and the `id` is
+/// the one of the draft, with a `202 Accepted`. The draft turns into the `sent`
+/// copy once it is sent, so its `id` stays the same, while IMAP clients see it
+/// arrive in `Sent` with a new UID, see `imap`.
+///
+/// `mail` and `send_at` are expected to already have been validated.
+pub async fn send_or_schedule(
+    ctx: &AppContext,
+    user_id: i64,
+    mail: &Mail,
+    attachments_of: Option<i64>,
+    send_at: Option<&str>,
+) -> Result<(StatusCode, Json<IdPayload>), ApiError> {
+    let pool = ctx.pool().await;
+    let (origin, undo_secs) = {
+        let cfg = ctx.cfg().await;
+        (cfg.pub_addr().await.clone(), *cfg.undo_send_secs().await)
+    };
+
+    if send_at.is_none() && undo_secs == 0 {
+        let delivered =
+            delivery::send(&pool, ctx.events(), &origin, user_id, mail, attachments_of).await?;
+
+        let status = match delivered.destination {
+            Destination::Local { .. } => StatusCode::CREATED,
+            Destination::Remote { .. } => StatusCode::ACCEPTED,
+        };
+
+        return Ok((
+            status,
+            Json(IdPayload {
+                id: delivered.sent_id,
+            }),
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let id = delivery::insert(&mut tx, user_id, mail, "draft").await?;
+
+    if let Some(attachments_of) = attachments_of {
+        delivery::copy_attachments(&mut tx, attachments_of, id).await?;
+    }
+
+    schedule::submit(
+        &mut tx,
+        &origin,
+        user_id,
+        id,
+        &mail.recipient,
+        send_at,
+        undo_secs,
+    )
+    .await?;
+
+    tx.commit().await?;
+    schedule::wake();
+
+    Ok((StatusCode::ACCEPTED, Json(IdPayload { id })))
+}
+
+/// Completes a query that selects the columns of `MailSummaryRow`
+/// from `mails` with keyset pagination and fetches a single `Page`.
+///
+/// `builder` must end inside the `WHERE` clause.
+pub async fn fetch_page(
+    pool: &SqlitePool,
+    mut builder: QueryBuilder<'_, Sqlite>,
+    cursor: Option<Cursor>,
+    limit: u32,
+) -> Result<Page<MailSummary>, ApiError> {
+    pagination::push_keyset(&mut builder, "mails", cursor, limit);
+
+    let rows: Vec<MailSummaryRow> = builder.build_query_as().fetch_all(pool).await?;
+
+    let page = pagination::page(rows, limit, |row| Cursor {
+        created_at: row.created_at.clone(),
+        id: row.id,
+    });
+
+    Ok(Page {
+        items: page.items.into_iter().map(MailSummary::from).collect(),
+        next_cursor: page.next_cursor,
+    })
+}
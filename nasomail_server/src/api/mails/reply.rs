@@ -0,0 +1,100 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::post,
+};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{
+        IdPayload,
+        mail::{BODY_MAX_LEN, ReplyPayload},
+    },
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, mails},
+    app::AppContextGuard,
+    delivery::{self, Mail},
+    message,
+};
+
+pub trait RouterApiMailsReply {
+    /// Registers the `POST /api/mails/{id}/reply` endpoint
+    /// which replies to a mail of the authenticated user.
+    fn with_api_mails_reply(self) -> Self;
+}
+
+impl RouterApiMailsReply for Router<AppContextGuard> {
+    fn with_api_mails_reply(self) -> Self {
+        self.route(api::API_MAILS_ID_REPLY, post(handle))
+    }
+}
+
+/// Sends the provided `ReplyPayload` as a reply to the mail with the given
+/// `id`, i.e, to its sender, or to its recipient if it is a `sent` mail,
+/// then responds like `POST /api/mails`.
+///
+/// The subject is prefixed with `Re:`, the original mail is quoted below
+/// the body (which is cut short if it gets too long) and the threading
+/// headers are set so the reply ends up in the same thread.
+///
+/// Responds with a `404 Not Found` if the mail does not exist, is a draft
+/// or is not owned by the authenticated user.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<ReplyPayload>,
+) -> Result<(StatusCode, Json<IdPayload>), ApiError> {
+    let ctx = app.ctx().await;
+    let origin = ctx.cfg().await.pub_addr().await.clone();
+
+    let original = mails::fetch_original(&*ctx.pool().await, user.id, id)
+        .await?
+        .ok_or(ApiError::Status(StatusCode::NOT_FOUND))?;
+
+    let recipient = if original.status == "sent" {
+        original.recipient
+    } else {
+        original.sender.clone()
+    };
+
+    let quoted = original
+        .body
+        .lines()
+        .map(|line| format!("> {line}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mail = Mail {
+        sender: user.name,
+        recipient,
+        subject: mails::prefix_subject("Re:", &original.subject),
+        body: message::fit(
+            &format!(
+                "{}\n\nOn {} UTC, {} wrote:\n{}",
+                payload.body.trim(),
+                original.created_at,
+                original.sender,
+                quoted
+            ),
+            BODY_MAX_LEN,
+        ),
+        message_id: delivery::message_id(&origin),
+        references: delivery::references_of(
+            &original.message_id,
+            &delivery::split_references(&original.refs),
+        ),
+        in_reply_to: Some(original.message_id),
+        auto_submitted: false,
+    };
+
+    mails::validate(&mail, 1)?;
+    mails::validate_send_at(&*ctx.pool().await, payload.send_at.as_deref()).await?;
+
+    mails::send_or_schedule(&ctx, user.id, &mail, None, payload.send_at.as_deref()).await
+}
@@ -0,0 +1,58 @@
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::post,
+};
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::event::EventPayload};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiMailsRestore {
+    /// Registers the `POST /api/mails/{id}/restore` endpoint
+    /// which restores a mail of the authenticated user from the trash.
+    fn with_api_mails_restore(self) -> Self;
+}
+
+impl RouterApiMailsRestore for Router<AppContextGuard> {
+    fn with_api_mails_restore(self) -> Self {
+        self.route(api::API_MAILS_ID_RESTORE, post(handle))
+    }
+}
+
+/// Moves the mail with the given `id` out of the trash, back to where
+/// it was before, and publishes an `EventPayload::Restored`.
+///
+/// Responds with a `404 Not Found` if the mail does not exist, is not
+/// owned by the authenticated user or is not in the trash.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let restored = sqlx::query(
+        "UPDATE mails SET deleted_at = NULL
+            WHERE id = ? AND user_id = ? AND deleted_at IS NOT NULL",
+    )
+    .bind(id)
+    .bind(user.id)
+    .execute(&*pool)
+    .await?;
+
+    if restored.rows_affected() == 0 {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    ctx.events().publish(user.id, EventPayload::Restored { id });
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,184 @@
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    routing::get,
+};
+use sqlx::{QueryBuilder, Sqlite};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{
+        Page,
+        error::FieldErrorKind,
+        mail::{HIGHLIGHT_END, HIGHLIGHT_START, MailSearchHit, MailSummary},
+    },
+    query::mail::{MailSearchQuery, SEARCH_QUERY_MAX_LEN},
+};
+
+use crate::{
+    api::{
+        bearer::AuthUser,
+        error::ApiError,
+        mails::MailSummaryRow,
+        pagination::{self, Cursor},
+        validate::Validator,
+    },
+    app::AppContextGuard,
+};
+
+pub trait RouterApiMailsSearch {
+    /// Registers the `GET /api/mails/search` endpoint
+    /// which searches the mails of the authenticated user.
+    fn with_api_mails_search(self) -> Self;
+}
+
+impl RouterApiMailsSearch for Router<AppContextGuard> {
+    fn with_api_mails_search(self) -> Self {
+        self.route(api::API_MAILS_SEARCH, get(handle))
+    }
+}
+
+/// How many tokens of the body a snippet contains at most.
+const SNIPPET_TOKENS: u32 = 16;
+
+#[derive(sqlx::FromRow)]
+struct SearchRow {
+    #[sqlx(flatten)]
+    mail: MailSummaryRow,
+    subject_highlight: String,
+    snippet: String,
+}
+
+/// Searches the mails owned by the authenticated user with the `q` of the
+/// provided `MailSearchQuery`, newest first, as a `Page` of `MailSearchHit`s.
+///
+/// Results are paginated exactly like `GET /api/mails`, so they are
+/// ordered by date rather than by how well they match.
+#[instrument(skip(app, query))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Query(query): Query<MailSearchQuery>,
+) -> Result<Json<Page<MailSearchHit>>, ApiError> {
+    let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+    let limit = pagination::limit(query.limit);
+
+    let expression = to_match(&query.q);
+
+    Validator::new()
+        .len("q", &query.q, 0, SEARCH_QUERY_MAX_LEN)
+        .check(
+            "q",
+            !expression.is_empty(),
+            FieldErrorKind::TooShort { min: 1 },
+        )
+        .finish()?;
+
+    let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT mails.id, mails.subject, mails.sender, mails.recipient,
+                mails.status, mails.created_at,
+                highlight(mails_fts, 0, ",
+    );
+    builder
+        .push_bind(HIGHLIGHT_START)
+        .push(", ")
+        .push_bind(HIGHLIGHT_END)
+        .push(") AS subject_highlight, snippet(mails_fts, 1, ")
+        .push_bind(HIGHLIGHT_START)
+        .push(", ")
+        .push_bind(HIGHLIGHT_END)
+        .push(", '…', ")
+        .push_bind(SNIPPET_TOKENS)
+        .push(
+            ") AS snippet
+            FROM mails_fts JOIN mails ON mails.id = mails_fts.rowid
+            WHERE mails_fts MATCH ",
+        )
+        .push_bind(expression)
+        .push(" AND mails.deleted_at IS NULL AND mails.user_id = ")
+        .push_bind(user.id);
+
+    pagination::push_keyset(&mut builder, "mails", cursor, limit);
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let rows: Vec<SearchRow> = builder.build_query_as().fetch_all(&*pool).await?;
+
+    let page = pagination::page(rows, limit, |row| Cursor {
+        created_at: row.mail.created_at.clone(),
+        id: row.mail.id,
+    });
+
+    Ok(Json(Page {
+        items: page
+            .items
+            .into_iter()
+            .map(|row| MailSearchHit {
+                mail: MailSummary::from(row.mail),
+                subject: row.subject_highlight,
+                snippet: row.snippet,
+            })
+            .collect(),
+        next_cursor: page.next_cursor,
+    }))
+}
+
+/// Translates a search query, as documented on `MailSearchQuery`,
+/// into an FTS5 expression over the columns of `mails_fts`.
+///
+/// Every term is quoted, so nothing in `q` is ever interpreted as FTS5
+/// syntax. Returns an empty string if `q` contains no terms.
+fn to_match(q: &str) -> String {
+    let mut terms = Vec::new();
+    let mut rest = q.trim_start();
+
+    while !rest.is_empty() {
+        let column = rest.split_once(':').and_then(|(field, after)| {
+            let column = match field.to_ascii_lowercase().as_str() {
+                "from" => "sender",
+                "to" => "recipient",
+                "subject" => "subject",
+                "body" => "body",
+                _ => return None,
+            };
+
+            rest = after;
+            Some(column)
+        });
+
+        let (mut text, mut after) = match rest.strip_prefix('"') {
+            // An unterminated phrase runs until the end of the query
+            Some(phrase) => phrase.split_once('"').unwrap_or((phrase, "")),
+            None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+        };
+
+        let mut prefix = false;
+        if let Some(stripped) = after.strip_prefix('*') {
+            (prefix, after) = (true, stripped);
+        } else if let Some(stripped) = text.strip_suffix('*') {
+            (prefix, text) = (true, stripped);
+        }
+
+        rest = after.trim_start();
+
+        // Terms without any word characters match nothing
+        if !text.chars().any(char::is_alphanumeric) {
+            continue;
+        }
+
+        let phrase = format!(
+            "\"{}\"{}",
+            text.replace('"', "\"\""),
+            if prefix { "*" } else { "" }
+        );
+
+        terms.push(match column {
+            Some(column) => format!("{column} : {phrase}"),
+            None => phrase,
+        });
+    }
+
+    terms.join(" ")
+}
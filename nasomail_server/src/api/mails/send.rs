@@ -0,0 +1,70 @@
+use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{IdPayload, mail::SendMailPayload},
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, mails},
+    app::AppContextGuard,
+    delivery::{self, Mail},
+};
+
+pub trait RouterApiMailsSend {
+    /// Registers the `POST /api/mails` endpoint
+    /// which sends a mail as the authenticated user.
+    fn with_api_mails_send(self) -> Self;
+}
+
+impl RouterApiMailsSend for Router<AppContextGuard> {
+    fn with_api_mails_send(self) -> Self {
+        self.route(api::API_MAILS_ROOT, post(handle))
+    }
+}
+
+/// Sends the mail described by the provided `SendMailPayload` to a local
+/// recipient or queues it for a remote one, then returns an `IdPayload`
+/// containing the `id` of the `sent` copy owned by the authenticated user.
+///
+/// Responds with a `201 Created` if the mail was delivered locally and with
+/// a `202 Accepted` if it was queued for delivery to another server.
+///
+/// If `send_at` is set or `Config::undo_send_secs` is not `0`, the mail is
+/// saved as a draft and scheduled instead, see `mails::send_or_schedule`,
+/// and the `id` is the one of that draft. Filters of local recipients then
+/// only run once it is sent, rejected mails are bounced back to the sender.
+/// A `send_at` that is not in the future is rejected like any invalid field.
+///
+/// The `subject` and `body` are trimmed before being validated against
+/// the constraints of the `mails` table. Malformed addresses and unknown
+/// local recipients are rejected with a `422 Unprocessable Entity` like
+/// any other invalid field. Mails that the filter of a local recipient
+/// rejects are not sent at all, and are answered with a `403 Forbidden`
+/// whose `error` is the reason the filter gave.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Json(payload): Json<SendMailPayload>,
+) -> Result<(StatusCode, Json<IdPayload>), ApiError> {
+    let ctx = app.ctx().await;
+    let origin = ctx.cfg().await.pub_addr().await.clone();
+
+    let mail = Mail {
+        sender: user.name,
+        recipient: payload.recipient.trim().to_owned(),
+        subject: payload.subject.trim().to_owned(),
+        body: payload.body.trim().to_owned(),
+        message_id: delivery::message_id(&origin),
+        in_reply_to: None,
+        references: Vec::new(),
+        auto_submitted: false,
+    };
+
+    mails::validate(&mail, 1)?;
+    mails::validate_send_at(&*ctx.pool().await, payload.send_at.as_deref()).await?;
+
+    mails::send_or_schedule(&ctx, user.id, &mail, None, payload.send_at.as_deref()).await
+}
@@ -0,0 +1,86 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::put,
+};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{
+        error::{FieldError, FieldErrorKind},
+        event::EventPayload,
+        mail::{MailStatus, MailStatusPayload},
+    },
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiMailsStatus {
+    /// Registers the `PUT /api/mails/{id}/status` endpoint
+    /// which marks a mail of the authenticated user as read or unread.
+    fn with_api_mails_status(self) -> Self;
+}
+
+impl RouterApiMailsStatus for Router<AppContextGuard> {
+    fn with_api_mails_status(self) -> Self {
+        self.route(api::API_MAILS_ID_STATUS, put(handle))
+    }
+}
+
+/// Sets the `status` of the mail with the given `id` to the one in the
+/// provided `MailStatusPayload`.
+///
+/// Only received mails (`new` or `read`) can be updated and only to `new`
+/// or `read`, anything else is rejected with a `422 Unprocessable Entity`
+/// since drafts and sent mails have no notion of being read. Publishes
+/// an `EventPayload::StatusChanged` on success, even if nothing changed.
+///
+/// Responds with a `404 Not Found` if the mail does not exist
+/// or is not owned by the authenticated user.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<MailStatusPayload>,
+) -> Result<StatusCode, ApiError> {
+    let invalid = || ApiError::Validation(vec![FieldError::new("status", FieldErrorKind::Invalid)]);
+
+    if !matches!(payload.status, MailStatus::New | MailStatus::Read) {
+        return Err(invalid());
+    }
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let current: String = sqlx::query_scalar(
+        "UPDATE mails SET status = CASE WHEN status IN ('new', 'read') THEN ? ELSE status END
+            WHERE id = ? AND user_id = ?
+            RETURNING status",
+    )
+    .bind(payload.status.as_str())
+    .bind(id)
+    .bind(user.id)
+    .fetch_optional(&*pool)
+    .await?
+    .ok_or(ApiError::Status(StatusCode::NOT_FOUND))?;
+
+    if current != payload.status.as_str() {
+        return Err(invalid());
+    }
+
+    ctx.events().publish(
+        user.id,
+        EventPayload::StatusChanged {
+            id,
+            status: payload.status,
+        },
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -3,11 +3,37 @@
 
 use axum::Router;
 
+mod attachments;
+mod bearer;
 pub mod ctest;
+mod drafts;
+mod error;
+mod events;
+mod federation;
+mod filter;
+mod labels;
+mod mails;
+mod pagination;
+mod sessions;
+mod threads;
 mod users;
+mod vacation;
+mod validate;
+mod webhooks;
 
+use crate::api::attachments::RouterApiAttachments;
 use crate::api::ctest::RouterApiCtest;
+use crate::api::drafts::RouterApiDrafts;
+use crate::api::events::RouterApiEvents;
+use crate::api::federation::RouterApiFederation;
+use crate::api::filter::RouterApiFilter;
+use crate::api::labels::RouterApiLabels;
+use crate::api::mails::RouterApiMails;
+use crate::api::sessions::RouterApiSessions;
+use crate::api::threads::RouterApiThreads;
 use crate::api::users::RouterApiUsers;
+use crate::api::vacation::RouterApiVacation;
+use crate::api::webhooks::RouterApiWebhooks;
 use crate::app::AppContextGuard;
 
 use nasomail_shared::api;
@@ -20,6 +46,22 @@ pub trait RouterApi {
 
 impl RouterApi for Router<AppContextGuard> {
     fn with_api(self) -> Self {
-        self.nest(api::API, Router::new().with_api_ctest().with_api_users())
+        self.nest(
+            api::API,
+            Router::new()
+                .with_api_ctest()
+                .with_api_users()
+                .with_api_sessions()
+                .with_api_mails()
+                .with_api_threads()
+                .with_api_labels()
+                .with_api_filter()
+                .with_api_vacation()
+                .with_api_drafts()
+                .with_api_attachments()
+                .with_api_events()
+                .with_api_webhooks()
+                .with_api_federation(),
+        )
     }
 }
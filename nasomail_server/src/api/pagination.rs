@@ -0,0 +1,91 @@
+//! Keyset pagination over `(created_at, id)`,
+//! newest first, shared by every paginated listing.
+
+use nasomail_shared::payload::{
+    Page,
+    error::{FieldError, FieldErrorKind},
+};
+use sqlx::{QueryBuilder, Sqlite};
+
+use crate::api::error::ApiError;
+
+pub const DEFAULT_LIMIT: u32 = 50;
+pub const MAX_LIMIT: u32 = 200;
+
+/// The position of the last item of a page.
+pub struct Cursor {
+    pub created_at: String,
+    pub id: i64,
+}
+
+impl Cursor {
+    /// Encodes the cursor as an opaque string
+    /// to be handed out as `Page::next_cursor`.
+    pub fn encode(&self) -> String {
+        format!("{}:{}", self.id, self.created_at)
+    }
+
+    /// Decodes a cursor previously returned by `Cursor::encode`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(ApiError::Validation)` if `value` is malformed.
+    ///
+    pub fn decode(value: &str) -> Result<Self, ApiError> {
+        value
+            .split_once(':')
+            .and_then(|(id, created_at)| {
+                Some(Self {
+                    id: id.parse().ok()?,
+                    created_at: created_at.to_owned(),
+                })
+            })
+            .ok_or_else(|| {
+                ApiError::Validation(vec![FieldError::new("cursor", FieldErrorKind::Malformed)])
+            })
+    }
+}
+
+/// Clamps a requested page size to `1..=MAX_LIMIT`.
+pub fn limit(requested: Option<u32>) -> u32 {
+    requested.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+}
+
+/// Appends the keyset condition for `cursor` (if any), the ordering
+/// and a `LIMIT` of one more than `limit`, so `page` can tell whether
+/// there is a following page.
+///
+/// `table` is the name or alias of the paginated table in the query.
+pub fn push_keyset(
+    builder: &mut QueryBuilder<'_, Sqlite>,
+    table: &str,
+    cursor: Option<Cursor>,
+    limit: u32,
+) {
+    if let Some(cursor) = cursor {
+        builder
+            .push(format!(" AND ({table}.created_at, {table}.id) < ("))
+            .push_bind(cursor.created_at)
+            .push(", ")
+            .push_bind(cursor.id)
+            .push(")");
+    }
+
+    builder
+        .push(format!(
+            " ORDER BY {table}.created_at DESC, {table}.id DESC LIMIT "
+        ))
+        .push_bind(limit as i64 + 1);
+}
+
+/// Turns the rows fetched after `push_keyset` into a `Page`.
+pub fn page<T>(mut items: Vec<T>, limit: u32, cursor: impl Fn(&T) -> Cursor) -> Page<T> {
+    let next_cursor = if items.len() > limit as usize {
+        items.truncate(limit as usize);
+        items.last().map(|item| cursor(item).encode())
+    } else {
+        None
+    };
+
+    Page { items, next_cursor }
+}
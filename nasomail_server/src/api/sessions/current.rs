@@ -0,0 +1,30 @@
+use axum::{Json, Router, routing::get};
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::user::UserPayload};
+
+use crate::{api::bearer::AuthUser, app::AppContextGuard};
+
+pub trait RouterApiSessionsCurrent {
+    /// Registers the `/api/sessions/current` endpoint
+    /// which returns the user that the bearer token
+    /// of the request belongs to.
+    fn with_api_sessions_current(self) -> Self;
+}
+
+impl RouterApiSessionsCurrent for Router<AppContextGuard> {
+    fn with_api_sessions_current(self) -> Self {
+        self.route(api::API_SESSIONS_CURRENT, get(handle))
+    }
+}
+
+/// Returns a `UserPayload` describing the user that the bearer
+/// token of the request belongs to, which lets clients check
+/// whether a saved token is still valid.
+#[instrument]
+async fn handle(user: AuthUser) -> Json<UserPayload> {
+    Json(UserPayload {
+        id: user.id,
+        name: user.name,
+    })
+}
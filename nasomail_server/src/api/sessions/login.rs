@@ -0,0 +1,51 @@
+use axum::{Json, Router, extract::State, routing::post};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::auth::{AuthPayload, SessionPayload},
+    query::user::UserQuery,
+};
+
+use crate::{api::error::ApiError, app::AppContextGuard, passphrase, session};
+
+pub trait RouterApiSessionsLogin {
+    /// Registers the `/api/sessions/login` endpoint
+    /// which exchanges a username and passphrase
+    /// for a bearer token.
+    fn with_api_sessions_login(self) -> Self;
+}
+
+impl RouterApiSessionsLogin for Router<AppContextGuard> {
+    fn with_api_sessions_login(self) -> Self {
+        self.route(api::API_SESSIONS_LOGIN, post(handle))
+    }
+}
+
+/// Checks the `username` and `passphrase` of the provided `AuthPayload`
+/// and, if they match, creates a new row in the `sessions` table and
+/// returns a `SessionPayload` containing its bearer token.
+///
+/// Responds with a `401 Unauthorized` if the credentials do not match.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    Json(payload): Json<AuthPayload>,
+) -> Result<Json<SessionPayload>, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+    let cfg = ctx.cfg().await;
+
+    let query = UserQuery::ByName {
+        name: payload.username,
+    };
+
+    let argon2 = cfg.argon2().await.clone();
+    let user_id = passphrase::authenticate(&pool, argon2, query, payload.passphrase)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    let session = session::create(&pool, user_id, *cfg.session_lifetime_secs().await).await?;
+
+    Ok(Json(session))
+}
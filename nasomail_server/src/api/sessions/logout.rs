@@ -0,0 +1,37 @@
+use axum::{Router, extract::State, http::StatusCode, routing::post};
+use tracing::instrument;
+
+use nasomail_shared::api;
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+    session,
+};
+
+pub trait RouterApiSessionsLogout {
+    /// Registers the `/api/sessions/logout` endpoint
+    /// which revokes the bearer token of the request.
+    fn with_api_sessions_logout(self) -> Self;
+}
+
+impl RouterApiSessionsLogout for Router<AppContextGuard> {
+    fn with_api_sessions_logout(self) -> Self {
+        self.route(api::API_SESSIONS_LOGOUT, post(handle))
+    }
+}
+
+/// Removes the row in the `sessions` table that the bearer token
+/// of the request belongs to, so the token can no longer be used.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+) -> Result<StatusCode, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    session::revoke(&pool, user.session_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
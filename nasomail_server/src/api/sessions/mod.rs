@@ -0,0 +1,33 @@
+mod current;
+mod login;
+mod logout;
+
+use axum::Router;
+
+use nasomail_shared::api;
+
+use crate::{
+    api::sessions::{
+        current::RouterApiSessionsCurrent, login::RouterApiSessionsLogin,
+        logout::RouterApiSessionsLogout,
+    },
+    app::AppContextGuard,
+};
+
+pub trait RouterApiSessions {
+    /// Registers routes for
+    /// session related APIs
+    fn with_api_sessions(self) -> Self;
+}
+
+impl RouterApiSessions for Router<AppContextGuard> {
+    fn with_api_sessions(self) -> Self {
+        self.nest(
+            api::API_SESSIONS,
+            Router::new()
+                .with_api_sessions_login()
+                .with_api_sessions_logout()
+                .with_api_sessions_current(),
+        )
+    }
+}
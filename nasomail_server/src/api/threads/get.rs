@@ -0,0 +1,99 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{
+        event::EventPayload,
+        mail::{MailDetail, MailStatus},
+    },
+    query::mail::MailGetQuery,
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, mails},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiThreadsGet {
+    /// Registers the `GET /api/threads/{id}` endpoint
+    /// which fetches a whole thread of the authenticated user.
+    fn with_api_threads_get(self) -> Self;
+}
+
+impl RouterApiThreadsGet for Router<AppContextGuard> {
+    fn with_api_threads_get(self) -> Self {
+        self.route(api::API_THREADS_ID, get(handle))
+    }
+}
+
+/// Fetches every mail of the thread with the given `id`, oldest first,
+/// as `MailDetail`s.
+///
+/// Opening a thread marks all of its `new` mails as `read` and publishes an
+/// `EventPayload::StatusChanged` for each of them, unless `peek` is set in
+/// the provided `MailGetQuery`.
+///
+/// Responds with a `404 Not Found` if the thread does not exist
+/// or is not owned by the authenticated user.
+#[instrument(skip(app, query))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Query(query): Query<MailGetQuery>,
+) -> Result<Json<Vec<MailDetail>>, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    if !query.peek {
+        let read: Vec<i64> = sqlx::query_scalar(
+            "UPDATE mails SET status = 'read'
+                WHERE thread_id = ? AND user_id = ? AND status = 'new' AND deleted_at IS NULL
+                RETURNING id",
+        )
+        .bind(id)
+        .bind(user.id)
+        .fetch_all(&*pool)
+        .await?;
+
+        for id in read {
+            ctx.events().publish(
+                user.id,
+                EventPayload::StatusChanged {
+                    id,
+                    status: MailStatus::Read,
+                },
+            );
+        }
+    }
+
+    let ids: Vec<i64> = sqlx::query_scalar(
+        "SELECT id FROM mails
+            WHERE thread_id = ? AND user_id = ? AND status != 'draft' AND deleted_at IS NULL
+            ORDER BY created_at, id",
+    )
+    .bind(id)
+    .bind(user.id)
+    .fetch_all(&*pool)
+    .await?;
+
+    if ids.is_empty() {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    let mut mails = Vec::with_capacity(ids.len());
+    for id in ids {
+        // A mail deleted in the meantime is simply left out
+        if let Some(mail) = mails::fetch_detail(&pool, user.id, id).await? {
+            mails.push(mail);
+        }
+    }
+
+    Ok(Json(mails))
+}
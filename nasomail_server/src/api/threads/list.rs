@@ -0,0 +1,103 @@
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    routing::get,
+};
+use sqlx::{QueryBuilder, Sqlite};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{Page, mail::MailSummary, thread::ThreadSummary},
+    query::PageQuery,
+};
+
+use crate::{
+    api::{
+        bearer::AuthUser,
+        error::ApiError,
+        mails::MailSummaryRow,
+        pagination::{self, Cursor},
+    },
+    app::AppContextGuard,
+};
+
+pub trait RouterApiThreadsList {
+    /// Registers the `GET /api/threads` endpoint
+    /// which lists the threads of the authenticated user.
+    fn with_api_threads_list(self) -> Self;
+}
+
+impl RouterApiThreadsList for Router<AppContextGuard> {
+    fn with_api_threads_list(self) -> Self {
+        self.route(api::API_THREADS_ROOT, get(handle))
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ThreadRow {
+    thread_id: i64,
+    thread_subject: String,
+    count: u32,
+    unread: u32,
+
+    #[sqlx(flatten)]
+    latest: MailSummaryRow,
+}
+
+/// Lists the threads of the authenticated user, the most recently active
+/// first, as a `Page` of `ThreadSummary`s. Drafts and mails in the trash
+/// are not part of any thread.
+///
+/// Threads are paginated by their latest mail, just like `GET /api/mails`
+/// paginates mails.
+#[instrument(skip(app, query))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Page<ThreadSummary>>, ApiError> {
+    let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+    let limit = pagination::limit(query.limit);
+
+    let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT * FROM (
+            SELECT id, subject, sender, recipient, status, created_at, thread_id,
+                    COUNT(*) OVER thread AS count,
+                    SUM(status = 'new') OVER thread AS unread,
+                    FIRST_VALUE(subject) OVER (thread ORDER BY created_at, id) AS thread_subject,
+                    ROW_NUMBER() OVER (thread ORDER BY created_at DESC, id DESC) AS position
+                FROM mails WHERE status != 'draft' AND deleted_at IS NULL AND user_id = ",
+    );
+    builder.push_bind(user.id).push(
+        " WINDOW thread AS (PARTITION BY thread_id)
+        ) AS latest WHERE position = 1",
+    );
+
+    pagination::push_keyset(&mut builder, "latest", cursor, limit);
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let rows: Vec<ThreadRow> = builder.build_query_as().fetch_all(&*pool).await?;
+
+    let page = pagination::page(rows, limit, |row| Cursor {
+        created_at: row.latest.created_at.clone(),
+        id: row.latest.id,
+    });
+
+    Ok(Json(Page {
+        items: page
+            .items
+            .into_iter()
+            .map(|row| ThreadSummary {
+                id: row.thread_id,
+                subject: row.thread_subject,
+                count: row.count,
+                unread: row.unread,
+                latest: MailSummary::from(row.latest),
+            })
+            .collect(),
+        next_cursor: page.next_cursor,
+    }))
+}
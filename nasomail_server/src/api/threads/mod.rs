@@ -0,0 +1,26 @@
+mod get;
+mod list;
+
+use axum::Router;
+
+use nasomail_shared::api;
+
+use crate::{
+    api::threads::{get::RouterApiThreadsGet, list::RouterApiThreadsList},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiThreads {
+    /// Registers routes for
+    /// thread related APIs
+    fn with_api_threads(self) -> Self;
+}
+
+impl RouterApiThreads for Router<AppContextGuard> {
+    fn with_api_threads(self) -> Self {
+        self.nest(
+            api::API_THREADS,
+            Router::new().with_api_threads_list().with_api_threads_get(),
+        )
+    }
+}
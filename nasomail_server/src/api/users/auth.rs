@@ -12,7 +12,7 @@ use nasomail_shared::payload::BoolPayload;
 use nasomail_shared::query::user::UserQuery;
 use nasomail_shared::{api, payload::auth::PassOnlyAuthPayload};
 
-use crate::app::AppContextGuard;
+use crate::{app::AppContextGuard, passphrase};
 
 pub trait RouterApiUsersAuth {
     /// Registers the `/api/users/has` endpoint
@@ -28,8 +28,10 @@ impl RouterApiUsersAuth for Router<AppContextGuard> {
 }
 
 /// Checks if the `passphrase` of the provided `PassOnlyAuthPayload`
-/// matches the `passphrase` of the user in the `users` table of the database
-/// specified by the `id` or `name` fields of the provided `UserQuery`.
+/// matches the Argon2id hash stored in the `users` table of the database
+/// for the user specified by the `id` or `name` fields of the provided `UserQuery`.
+///
+/// See `passphrase::authenticate` for how outdated hashes are upgraded.
 #[instrument(skip(app, query, payload))]
 async fn handle(
     State(app): State<AppContextGuard>,
@@ -38,33 +40,16 @@ async fn handle(
 ) -> response::Result<Json<BoolPayload>, StatusCode> {
     let ctx = app.ctx().await;
     let pool = ctx.pool().await;
+    let argon2 = ctx.cfg().await.argon2().await.clone();
 
-    let result: Option<i64> = match query {
-        UserQuery::ById { id } => {
-            sqlx::query_scalar(
-                "SELECT CASE WHEN passphrase = ? THEN 1 ELSE 0 END FROM users WHERE id = ?",
-            )
-            .bind(payload.passphrase)
-            .bind(id)
-            .fetch_optional(&*pool)
-            .await
-        }
-        UserQuery::ByName { name } => {
-            sqlx::query_scalar(
-                "SELECT CASE WHEN passphrase = ? THEN 1 ELSE 0 END FROM users WHERE name = ?",
-            )
-            .bind(payload.passphrase)
-            .bind(name)
-            .fetch_optional(&*pool)
-            .await
-        }
-    }
-    .map_err(|e| {
-        tracing::info!(err = ?e, "internal server error");
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let id = passphrase::authenticate(&pool, argon2, query, payload.passphrase)
+        .await
+        .map_err(|e| {
+            tracing::error!(err = ?e, "internal server error");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
 
     Ok(Json(BoolPayload {
-        result: result.unwrap_or(0) != 0,
+        result: id.is_some(),
     }))
 }
@@ -1,22 +1,95 @@
-use axum::{Router, routing::get};
+use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+use sqlx::error::ErrorKind;
 use tracing::instrument;
 
-use nasomail_shared::api;
+use nasomail_shared::{
+    api,
+    payload::{
+        IdPayload,
+        auth::AuthPayload,
+        error::{FieldError, FieldErrorKind},
+    },
+};
 
-use crate::app::AppContextGuard;
+use crate::{
+    api::{error::ApiError, validate::Validator},
+    app::AppContextGuard,
+    delivery, passphrase,
+};
 
 pub trait RouterApiUsersRegister {
-    /// Registers the `/api/users/has` endpoint
-    /// which checks whether or not the database
-    /// has the specified user.
+    /// Registers the `/api/users/register` endpoint
+    /// which creates a new user account.
     fn with_api_users_register(self) -> Self;
 }
 
 impl RouterApiUsersRegister for Router<AppContextGuard> {
     fn with_api_users_register(self) -> Self {
-        self.route(api::API_USERS_REGISTER, get(handle))
+        self.route(api::API_USERS_REGISTER, post(handle))
     }
 }
 
-#[instrument]
-async fn handle() {}
+/// Inserts a new user into the `users` table of the database using the
+/// `username` and an Argon2id hash of the `passphrase` of the provided `AuthPayload`,
+/// then returns an `IdPayload` containing the `id` of the new user.
+///
+/// Payloads that violate the constraints of the `users` table or whose
+/// `username` contains anything but ASCII letters, digits, `.`, `_` and `-`
+/// are rejected with a `422 Unprocessable Entity`, usernames that are
+/// already taken or reserved, see `delivery::RESERVED_USERNAMES`, (both
+/// case-insensitively) with a `409 Conflict`.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    Json(payload): Json<AuthPayload>,
+) -> Result<(StatusCode, Json<IdPayload>), ApiError> {
+    Validator::new()
+        .trimmed_len("username", &payload.username, 3, 20)
+        .check(
+            "username",
+            is_valid_username(&payload.username),
+            FieldErrorKind::Invalid,
+        )
+        .trimmed_len("passphrase", &payload.passphrase, 8, 128)
+        .finish()?;
+
+    if delivery::RESERVED_USERNAMES
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(&payload.username))
+    {
+        return Err(ApiError::Conflict(vec![FieldError::new(
+            "username",
+            FieldErrorKind::Taken,
+        )]));
+    }
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let argon2 = ctx.cfg().await.argon2().await.clone();
+    let hash = passphrase::hash(argon2, payload.passphrase).await?;
+
+    let id: i64 =
+        sqlx::query_scalar("INSERT INTO users (name, passphrase) VALUES (?, ?) RETURNING id")
+            .bind(&payload.username)
+            .bind(hash)
+            .fetch_one(&*pool)
+            .await
+            .map_err(|e| match e.as_database_error().map(|e| e.kind()) {
+                Some(ErrorKind::UniqueViolation) => {
+                    ApiError::Conflict(vec![FieldError::new("username", FieldErrorKind::Taken)])
+                }
+                Some(ErrorKind::CheckViolation) => ApiError::Validation(Vec::new()),
+                _ => ApiError::Database(e),
+            })?;
+
+    Ok((StatusCode::CREATED, Json(IdPayload { id })))
+}
+
+/// Returns whether `username` only consists of ASCII letters, digits, `.`,
+/// `_` and `-`, so it can never be mistaken for a `user@host` address.
+fn is_valid_username(username: &str) -> bool {
+    username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
@@ -0,0 +1,54 @@
+use axum::{Router, extract::State, http::StatusCode, routing::delete};
+use tracing::instrument;
+
+use nasomail_shared::api;
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiVacationDelete {
+    /// Registers the `DELETE /api/vacation` endpoint which
+    /// removes the out-of-office reply of the authenticated user.
+    fn with_api_vacation_delete(self) -> Self;
+}
+
+impl RouterApiVacationDelete for Router<AppContextGuard> {
+    fn with_api_vacation_delete(self) -> Self {
+        self.route(api::API_VACATION_ROOT, delete(handle))
+    }
+}
+
+/// Removes the out-of-office reply of the authenticated user along
+/// with the record of who got it, so nobody gets it from now on.
+///
+/// Responds with a `404 Not Found` if the user has none.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+) -> Result<StatusCode, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let mut tx = pool.begin().await?;
+
+    let deleted = sqlx::query("DELETE FROM vacations WHERE user_id = ?")
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    sqlx::query("DELETE FROM vacation_replies WHERE user_id = ?")
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,52 @@
+use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::vacation::VacationPayload};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiVacationGet {
+    /// Registers the `GET /api/vacation` endpoint which
+    /// returns the out-of-office reply of the authenticated user.
+    fn with_api_vacation_get(self) -> Self;
+}
+
+impl RouterApiVacationGet for Router<AppContextGuard> {
+    fn with_api_vacation_get(self) -> Self {
+        self.route(api::API_VACATION_ROOT, get(handle))
+    }
+}
+
+/// Returns the out-of-office reply of the
+/// authenticated user as a `VacationPayload`.
+///
+/// Responds with a `404 Not Found` if the user has none,
+/// regardless of whether its dates have passed.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+) -> Result<Json<VacationPayload>, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let vacation: Option<(String, String, String, String, u32)> = sqlx::query_as(
+        "SELECT starts_on, ends_on, subject, body, days FROM vacations WHERE user_id = ?",
+    )
+    .bind(user.id)
+    .fetch_optional(&*pool)
+    .await?;
+
+    let (start, end, subject, body, days) = vacation.ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(VacationPayload {
+        start,
+        end,
+        subject,
+        body,
+        days,
+    }))
+}
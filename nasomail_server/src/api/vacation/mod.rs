@@ -0,0 +1,69 @@
+mod delete;
+mod get;
+mod update;
+
+use axum::Router;
+
+use nasomail_shared::{
+    api,
+    payload::{
+        error::FieldErrorKind,
+        vacation::{BODY_MAX_LEN, DAYS_MAX, SUBJECT_MAX_LEN, VacationPayload},
+    },
+};
+
+use crate::{
+    api::{
+        error::ApiError,
+        vacation::{
+            delete::RouterApiVacationDelete, get::RouterApiVacationGet,
+            update::RouterApiVacationUpdate,
+        },
+        validate::{Validator, is_date},
+    },
+    app::AppContextGuard,
+};
+
+pub trait RouterApiVacation {
+    /// Registers routes for
+    /// vacation related APIs
+    fn with_api_vacation(self) -> Self;
+}
+
+impl RouterApiVacation for Router<AppContextGuard> {
+    fn with_api_vacation(self) -> Self {
+        self.nest(
+            api::API_VACATION,
+            Router::new()
+                .with_api_vacation_get()
+                .with_api_vacation_update()
+                .with_api_vacation_delete(),
+        )
+    }
+}
+
+/// Validates `payload` against the constraints of the `vacations` table.
+///
+/// `subject` and `body` are expected to already be trimmed.
+fn validate(payload: &VacationPayload) -> Result<(), ApiError> {
+    let start_valid = is_date(&payload.start);
+    let end_valid = is_date(&payload.end);
+
+    Validator::new()
+        .check("start", start_valid, FieldErrorKind::Malformed)
+        .check("end", end_valid, FieldErrorKind::Malformed)
+        // Dates in this format compare like strings
+        .check(
+            "end",
+            !start_valid || !end_valid || payload.end >= payload.start,
+            FieldErrorKind::Invalid,
+        )
+        .trimmed_len("subject", &payload.subject, 1, SUBJECT_MAX_LEN)
+        .trimmed_len("body", &payload.body, 1, BODY_MAX_LEN)
+        .check(
+            "days",
+            (1..=DAYS_MAX).contains(&payload.days),
+            FieldErrorKind::Invalid,
+        )
+        .finish()
+}
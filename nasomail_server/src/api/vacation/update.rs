@@ -0,0 +1,74 @@
+use axum::{Json, Router, extract::State, http::StatusCode, routing::put};
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::vacation::VacationPayload};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, vacation},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiVacationUpdate {
+    /// Registers the `PUT /api/vacation` endpoint which
+    /// sets the out-of-office reply of the authenticated user.
+    fn with_api_vacation_update(self) -> Self;
+}
+
+impl RouterApiVacationUpdate for Router<AppContextGuard> {
+    fn with_api_vacation_update(self) -> Self {
+        self.route(api::API_VACATION_ROOT, put(handle))
+    }
+}
+
+/// Replaces the out-of-office reply of the authenticated user with the
+/// one in the provided `VacationPayload`, which is sent to the senders of
+/// the mails the user receives from `start` through `end`.
+///
+/// The `subject` and `body` are trimmed before being validated. Since the
+/// reply may have changed, senders that already got the previous one get
+/// the new one as well.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Json(payload): Json<VacationPayload>,
+) -> Result<StatusCode, ApiError> {
+    let payload = VacationPayload {
+        subject: payload.subject.trim().to_owned(),
+        body: payload.body.trim().to_owned(),
+        ..payload
+    };
+
+    vacation::validate(&payload)?;
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO vacations (user_id, starts_on, ends_on, subject, body, days)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (user_id) DO UPDATE
+            SET starts_on = excluded.starts_on, ends_on = excluded.ends_on,
+                subject = excluded.subject, body = excluded.body, days = excluded.days,
+                updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(user.id)
+    .bind(&payload.start)
+    .bind(&payload.end)
+    .bind(&payload.subject)
+    .bind(&payload.body)
+    .bind(payload.days)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("DELETE FROM vacation_replies WHERE user_id = ?")
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
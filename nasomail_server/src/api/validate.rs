@@ -0,0 +1,110 @@
+//! Helpers for validating request payloads
+//! against the constraints in `migrations/`
+//! before they ever reach the database.
+
+use nasomail_shared::payload::error::{FieldError, FieldErrorKind};
+
+use crate::api::error::ApiError;
+
+/// Collects every `FieldError` found while
+/// validating a single request payload.
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mirrors `CHECK (x = TRIM(x) AND LENGTH(x) >= min AND LENGTH(x) <= max)`.
+    pub fn trimmed_len(&mut self, field: &str, value: &str, min: usize, max: usize) -> &mut Self {
+        if value != value.trim() {
+            self.errors
+                .push(FieldError::new(field, FieldErrorKind::Untrimmed));
+        }
+
+        self.len(field, value, min, max)
+    }
+
+    /// Mirrors `CHECK (LENGTH(x) >= min AND LENGTH(x) <= max)`.
+    pub fn len(&mut self, field: &str, value: &str, min: usize, max: usize) -> &mut Self {
+        let len = value.chars().count();
+        if len < min {
+            self.errors
+                .push(FieldError::new(field, FieldErrorKind::TooShort { min }));
+        } else if len > max {
+            self.errors
+                .push(FieldError::new(field, FieldErrorKind::TooLong { max }));
+        }
+
+        self
+    }
+
+    /// Records a `kind` error for `field` unless `valid`, for
+    /// rules that no `CHECK` constraint can express.
+    pub fn check(&mut self, field: &str, valid: bool, kind: FieldErrorKind) -> &mut Self {
+        if !valid {
+            self.errors.push(FieldError::new(field, kind));
+        }
+
+        self
+    }
+
+    /// Returns `Err(ApiError::Validation)` if any field failed validation.
+    pub fn finish(&mut self) -> Result<(), ApiError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::Validation(std::mem::take(&mut self.errors)))
+        }
+    }
+}
+
+/// Returns whether `value` is a valid `YYYY-MM-DD` date, the way SQLite's `DATE` formats them.
+pub fn is_date(value: &str) -> bool {
+    let [year, month, day] = match value.split('-').collect::<Vec<_>>()[..] {
+        [year, month, day]
+            if year.len() == 4
+                && month.len() == 2
+                && day.len() == 2
+                && value.chars().all(|c| c.is_ascii_digit() || c == '-') =>
+        {
+            [year, month, day].map(|part| part.parse::<u32>().unwrap_or_default())
+        }
+        _ => return false,
+    };
+
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+
+    (1..=days).contains(&day)
+}
+
+/// Returns whether `value` is a valid `YYYY-MM-DD HH:MM:SS`
+/// date and time, the way SQLite's `DATETIME` formats them.
+pub fn is_datetime(value: &str) -> bool {
+    let Some((date, time)) = value.split_once(' ') else {
+        return false;
+    };
+
+    let [hour, minute, second] = match time.split(':').collect::<Vec<_>>()[..] {
+        [hour, minute, second]
+            if [hour, minute, second]
+                .iter()
+                .all(|part| part.len() == 2 && part.chars().all(|c| c.is_ascii_digit())) =>
+        {
+            [hour, minute, second].map(|part| part.parse::<u32>().unwrap_or_default())
+        }
+        _ => return false,
+    };
+
+    is_date(date) && hour < 24 && minute < 60 && second < 60
+}
@@ -0,0 +1,54 @@
+use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{IdPayload, webhook::WebhookPayload},
+};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, webhooks},
+    app::AppContextGuard,
+    webhook,
+};
+
+pub trait RouterApiWebhooksCreate {
+    /// Registers the `POST /api/webhooks` endpoint
+    /// which creates a new webhook for the authenticated user.
+    fn with_api_webhooks_create(self) -> Self;
+}
+
+impl RouterApiWebhooksCreate for Router<AppContextGuard> {
+    fn with_api_webhooks_create(self) -> Self {
+        self.route(api::API_WEBHOOKS_ROOT, post(handle))
+    }
+}
+
+/// Saves the provided `WebhookPayload` as a new webhook owned by the
+/// authenticated user, then returns an `IdPayload` containing its `id`.
+///
+/// From then on, every event of the user that the webhook is subscribed
+/// to is posted to its URL.
+#[instrument(skip(app, payload))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Json(payload): Json<WebhookPayload>,
+) -> Result<(StatusCode, Json<IdPayload>), ApiError> {
+    webhooks::validate(&payload).await?;
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO webhooks (user_id, url, events, secret) VALUES (?, ?, ?, ?) RETURNING id",
+    )
+    .bind(user.id)
+    .bind(&payload.url)
+    .bind(webhook::join_events(&payload.events))
+    .bind(&payload.secret)
+    .fetch_one(&*pool)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(IdPayload { id })))
+}
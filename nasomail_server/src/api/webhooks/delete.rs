@@ -0,0 +1,53 @@
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::delete,
+};
+use tracing::instrument;
+
+use nasomail_shared::api;
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiWebhooksDelete {
+    /// Registers the `DELETE /api/webhooks/{id}` endpoint
+    /// which deletes a webhook of the authenticated user.
+    fn with_api_webhooks_delete(self) -> Self;
+}
+
+impl RouterApiWebhooksDelete for Router<AppContextGuard> {
+    fn with_api_webhooks_delete(self) -> Self {
+        self.route(api::API_WEBHOOKS_ID, delete(handle))
+    }
+}
+
+/// Deletes the webhook with the given `id` along with its delivery log,
+/// so deliveries that are still pending are never posted.
+///
+/// Responds with a `404 Not Found` if the webhook does not exist
+/// or is not owned by the authenticated user.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let deleted = sqlx::query("DELETE FROM webhooks WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user.id)
+        .execute(&*pool)
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
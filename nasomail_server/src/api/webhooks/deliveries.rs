@@ -0,0 +1,116 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+};
+use sqlx::{QueryBuilder, Sqlite};
+use tracing::instrument;
+
+use nasomail_shared::{
+    api,
+    payload::{Page, event::EventPayload, webhook::WebhookDeliverySummary},
+    query::PageQuery,
+};
+
+use crate::{
+    api::{
+        bearer::AuthUser,
+        error::ApiError,
+        pagination::{self, Cursor},
+    },
+    app::AppContextGuard,
+};
+
+pub trait RouterApiWebhooksDeliveries {
+    /// Registers the `GET /api/webhooks/{id}/deliveries` endpoint
+    /// which lists the delivery log of a webhook of the authenticated user.
+    fn with_api_webhooks_deliveries(self) -> Self;
+}
+
+impl RouterApiWebhooksDeliveries for Router<AppContextGuard> {
+    fn with_api_webhooks_deliveries(self) -> Self {
+        self.route(api::API_WEBHOOKS_ID_DELIVERIES, get(handle))
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct DeliveryRow {
+    id: i64,
+    event: String,
+    status: String,
+    attempts: u32,
+    last_status_code: Option<u16>,
+    last_error: Option<String>,
+    created_at: String,
+}
+
+impl From<DeliveryRow> for WebhookDeliverySummary {
+    fn from(value: DeliveryRow) -> Self {
+        Self {
+            id: value.id,
+            // Only ever written from a serialized `EventPayload`
+            event: serde_json::from_str(&value.event).unwrap_or(EventPayload::Resync),
+            status: value.status,
+            attempts: value.attempts,
+            last_status_code: value.last_status_code,
+            last_error: value.last_error,
+            created_at: value.created_at,
+        }
+    }
+}
+
+/// Lists the deliveries of the webhook with the given `id`, newest first,
+/// as a `Page` of `WebhookDeliverySummary`s along with the outcome of
+/// their latest attempt.
+///
+/// Responds with a `404 Not Found` if the webhook does not exist
+/// or is not owned by the authenticated user.
+#[instrument(skip(app, query))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Page<WebhookDeliverySummary>>, ApiError> {
+    let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+    let limit = pagination::limit(query.limit);
+
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let owned: Option<i64> =
+        sqlx::query_scalar("SELECT id FROM webhooks WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user.id)
+            .fetch_optional(&*pool)
+            .await?;
+
+    if owned.is_none() {
+        return Err(ApiError::Status(StatusCode::NOT_FOUND));
+    }
+
+    let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT d.id, d.event, d.status, d.attempts,
+                a.status_code AS last_status_code, a.error AS last_error, d.created_at
+            FROM webhook_deliveries d
+            LEFT JOIN webhook_attempts a ON a.id = (
+                SELECT MAX(id) FROM webhook_attempts WHERE delivery_id = d.id
+            )
+            WHERE d.webhook_id = ",
+    );
+    builder.push_bind(id);
+
+    pagination::push_keyset(&mut builder, "d", cursor, limit);
+
+    let rows: Vec<DeliveryRow> = builder.build_query_as().fetch_all(&*pool).await?;
+
+    Ok(Json(pagination::page(
+        rows.into_iter().map(WebhookDeliverySummary::from).collect(),
+        limit,
+        |delivery| Cursor {
+            created_at: delivery.created_at.clone(),
+            id: delivery.id,
+        },
+    )))
+}
@@ -0,0 +1,43 @@
+use axum::{Json, Router, extract::State, routing::get};
+use tracing::instrument;
+
+use nasomail_shared::{api, payload::webhook::WebhookSummary};
+
+use crate::{
+    api::{bearer::AuthUser, error::ApiError, webhooks::WebhookRow},
+    app::AppContextGuard,
+};
+
+pub trait RouterApiWebhooksList {
+    /// Registers the `GET /api/webhooks` endpoint
+    /// which lists the webhooks of the authenticated user.
+    fn with_api_webhooks_list(self) -> Self;
+}
+
+impl RouterApiWebhooksList for Router<AppContextGuard> {
+    fn with_api_webhooks_list(self) -> Self {
+        self.route(api::API_WEBHOOKS_ROOT, get(handle))
+    }
+}
+
+/// Lists the webhooks owned by the authenticated user, oldest first,
+/// as `WebhookSummary`s, which never contain their secret.
+///
+/// Server-wide webhooks are not listed.
+#[instrument(skip(app))]
+async fn handle(
+    State(app): State<AppContextGuard>,
+    user: AuthUser,
+) -> Result<Json<Vec<WebhookSummary>>, ApiError> {
+    let ctx = app.ctx().await;
+    let pool = ctx.pool().await;
+
+    let rows: Vec<WebhookRow> = sqlx::query_as(
+        "SELECT id, url, events, created_at FROM webhooks WHERE user_id = ? ORDER BY id",
+    )
+    .bind(user.id)
+    .fetch_all(&*pool)
+    .await?;
+
+    Ok(Json(rows.into_iter().map(WebhookSummary::from).collect()))
+}
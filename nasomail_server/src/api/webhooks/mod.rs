@@ -0,0 +1,93 @@
+mod create;
+mod delete;
+mod deliveries;
+mod list;
+
+use axum::Router;
+use reqwest::Url;
+
+use nasomail_shared::{
+    api,
+    payload::{
+        error::FieldErrorKind,
+        webhook::{SECRET_MAX_LEN, URL_MAX_LEN, WebhookPayload, WebhookSummary},
+    },
+};
+
+use crate::{
+    api::{
+        error::ApiError,
+        validate::Validator,
+        webhooks::{
+            create::RouterApiWebhooksCreate, delete::RouterApiWebhooksDelete,
+            deliveries::RouterApiWebhooksDeliveries, list::RouterApiWebhooksList,
+        },
+    },
+    app::AppContextGuard,
+    net, webhook,
+};
+
+pub trait RouterApiWebhooks {
+    /// Registers routes for
+    /// webhook related APIs
+    fn with_api_webhooks(self) -> Self;
+}
+
+impl RouterApiWebhooks for Router<AppContextGuard> {
+    fn with_api_webhooks(self) -> Self {
+        self.nest(
+            api::API_WEBHOOKS,
+            Router::new()
+                .with_api_webhooks_create()
+                .with_api_webhooks_list()
+                .with_api_webhooks_delete()
+                .with_api_webhooks_deliveries(),
+        )
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct WebhookRow {
+    id: i64,
+    url: String,
+    events: String,
+    created_at: String,
+}
+
+impl From<WebhookRow> for WebhookSummary {
+    fn from(value: WebhookRow) -> Self {
+        Self {
+            id: value.id,
+            url: value.url,
+            events: webhook::split_events(&value.events),
+            created_at: value.created_at,
+        }
+    }
+}
+
+/// Validates `payload` against the constraints of the `webhooks` table,
+/// additionally requiring `url` to be an `http` or `https` URL on a public
+/// host, see `net::check`, and `events` to contain at least one event.
+async fn validate(payload: &WebhookPayload) -> Result<(), ApiError> {
+    let url = Url::parse(&payload.url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host());
+
+    // Malformed URLs are reported as such, not as private hosts
+    let is_public = match url.as_ref().and_then(webhook::host) {
+        Some(host) => net::check(&host).await.is_ok(),
+        None => true,
+    };
+
+    Validator::new()
+        .trimmed_len("url", &payload.url, 1, URL_MAX_LEN)
+        .check("url", url.is_some(), FieldErrorKind::Malformed)
+        .check("url", is_public, FieldErrorKind::Invalid)
+        .check(
+            "events",
+            !payload.events.is_empty(),
+            FieldErrorKind::TooShort { min: 1 },
+        )
+        .len("secret", &payload.secret, 1, SECRET_MAX_LEN)
+        .finish()
+}
@@ -1,15 +1,16 @@
 use ctxguard::tokio::ContextGuard;
 use sqlx::sqlite::SqlitePool;
-use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::{RwLock, RwLockReadGuard};
 use uuid::Uuid;
 
-use crate::config::Config;
+use crate::{config::Config, events::Events};
 
 pub type AppContextGuard = ContextGuard<AppContext>;
 
 pub struct AppContext {
     pool: RwLock<SqlitePool>,
     cfg: RwLock<Config>,
+    events: Events,
 
     test_code: RwLock<String>,
 }
@@ -19,6 +20,7 @@ impl AppContext {
         ContextGuard::new(Self {
             pool: RwLock::new(pool),
             cfg: RwLock::new(cfg),
+            events: Events::new(),
 
             test_code: RwLock::new(Uuid::new_v4().to_string()),
         })
@@ -27,15 +29,13 @@ impl AppContext {
     pub async fn pool(&self) -> RwLockReadGuard<'_, SqlitePool> {
         self.pool.read().await
     }
-    pub async fn pool_mut(&self) -> RwLockWriteGuard<'_, SqlitePool> {
-        self.pool.write().await
-    }
 
     pub async fn cfg(&self) -> RwLockReadGuard<'_, Config> {
         self.cfg.read().await
     }
-    pub async fn cfg_mut(&self) -> RwLockWriteGuard<'_, Config> {
-        self.cfg.write().await
+
+    pub fn events(&self) -> &Events {
+        &self.events
     }
 
     pub async fn test_code(&self) -> RwLockReadGuard<'_, String> {
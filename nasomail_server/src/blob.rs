@@ -0,0 +1,253 @@
+//! Incremental I/O on the `data` column of the `attachments` table.
+//!
+//! sqlx can only bind and fetch whole values, which would mean holding
+//! an entire attachment (up to ~1000 MiB) in memory at once. These
+//! helpers use SQLite's incremental blob API on the raw connection
+//! handle instead, so attachments can be streamed in small chunks.
+
+use std::{
+    ffi::{CStr, c_int},
+    ptr,
+};
+
+use anyhow::{anyhow, bail};
+use futures_util::{Stream, StreamExt, stream};
+use libsqlite3_sys as ffi;
+use sha2::{Digest, Sha256};
+use sqlx::{SqliteConnection, sqlite::SqlitePool};
+
+/// The size of the chunks attachments are streamed in.
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// How many bytes at the start of an attachment `write_stream` keeps around,
+/// which is plenty for `mime::sniff`.
+pub const HEAD_LEN: usize = 512;
+
+/// What `write_stream` learned about the data while writing it.
+pub struct Written {
+    /// The first `HEAD_LEN` bytes, for guessing the MIME type.
+    pub head: Vec<u8>,
+
+    /// A hex-encoded SHA-256 digest of all of the data.
+    pub sha256: String,
+}
+
+/// A custom error type for `write_stream`.
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError {
+    #[error("failed to read the data")]
+    Read,
+
+    #[error("the data is not exactly {0} bytes long")]
+    Length(u64),
+
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("blob error: {0}")]
+    Blob(#[from] anyhow::Error),
+}
+
+/// Opens the `data` blob of the attachment `rowid`, runs `f` on it and closes it.
+///
+/// # Errors
+///
+/// Returns `Err` if the blob could not be opened or `f` returns an SQLite error code.
+///
+async fn with_blob(
+    conn: &mut SqliteConnection,
+    rowid: i64,
+    writable: bool,
+    f: impl FnOnce(*mut ffi::sqlite3_blob) -> c_int,
+) -> anyhow::Result<()> {
+    let mut handle = conn.lock_handle().await?;
+    let db = handle.as_raw_handle().as_ptr();
+
+    let mut blob = ptr::null_mut();
+
+    // SAFETY: `db` is a valid connection for as long as `handle` is held,
+    // the strings are nul-terminated and `blob` is a valid out-pointer.
+    let rc = unsafe {
+        ffi::sqlite3_blob_open(
+            db,
+            c"main".as_ptr(),
+            c"attachments".as_ptr(),
+            c"data".as_ptr(),
+            rowid,
+            writable as c_int,
+            &mut blob,
+        )
+    };
+
+    let rc = if rc == ffi::SQLITE_OK {
+        let rc = f(blob);
+
+        // SAFETY: `blob` was successfully opened above and is closed exactly once.
+        unsafe { ffi::sqlite3_blob_close(blob) };
+
+        rc
+    } else {
+        rc
+    };
+
+    if rc != ffi::SQLITE_OK {
+        // SAFETY: `db` is still valid and `sqlite3_errstr` returns a static string.
+        let msg = unsafe { CStr::from_ptr(ffi::sqlite3_errstr(rc)) };
+        bail!(
+            "blob i/o on attachment {rowid} failed: {}",
+            msg.to_string_lossy()
+        );
+    }
+
+    Ok(())
+}
+
+fn to_c_int(value: usize) -> anyhow::Result<c_int> {
+    c_int::try_from(value).map_err(|_| anyhow!("blob offset out of range: {value}"))
+}
+
+/// Writes `data` into the attachment `rowid` starting at `offset`.
+///
+/// The blob must already be large enough, i.e, it should have been
+/// inserted as a `ZEROBLOB` of its final size.
+///
+/// # Errors
+///
+/// Returns `Err` if the blob could not be opened or the write is out of bounds.
+///
+pub async fn write_at(
+    conn: &mut SqliteConnection,
+    rowid: i64,
+    offset: usize,
+    data: &[u8],
+) -> anyhow::Result<()> {
+    let offset = to_c_int(offset)?;
+    let len = to_c_int(data.len())?;
+
+    with_blob(conn, rowid, true, |blob| {
+        // SAFETY: `blob` is open for writing and `data` is valid for `len` bytes.
+        unsafe { ffi::sqlite3_blob_write(blob, data.as_ptr().cast(), len, offset) }
+    })
+    .await
+}
+
+/// Reads `len` bytes of the attachment `rowid` starting at `offset`.
+///
+/// # Errors
+///
+/// Returns `Err` if the blob could not be opened or the read is out of bounds.
+///
+pub async fn read_at(
+    conn: &mut SqliteConnection,
+    rowid: i64,
+    offset: usize,
+    len: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    let offset = to_c_int(offset)?;
+    let len = to_c_int(len)?;
+
+    with_blob(conn, rowid, false, |blob| {
+        // SAFETY: `blob` is open and `buf` is valid for `len` bytes.
+        unsafe { ffi::sqlite3_blob_read(blob, buf.as_mut_ptr().cast(), len, offset) }
+    })
+    .await?;
+
+    Ok(buf)
+}
+
+/// Writes exactly `len` bytes from `data` into the attachment `rowid`,
+/// buffering at most `CHUNK_SIZE` bytes at a time.
+///
+/// # Errors
+///
+/// Returns `Err(Read)`     if `data` yields an error.
+/// Returns `Err(Length)`   if `data` yields more or less than `len` bytes.
+/// Returns `Err(Database)` if a connection could not be acquired.
+/// Returns `Err(Blob)`     if the blob could not be written.
+///
+pub async fn write_stream<S, B, E>(
+    pool: &SqlitePool,
+    rowid: i64,
+    len: u64,
+    data: S,
+) -> Result<Written, StreamError>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+{
+    let mut data = std::pin::pin!(data);
+    let mut buf = Vec::with_capacity(CHUNK_SIZE);
+    let mut offset = 0usize;
+
+    let mut head = Vec::with_capacity(HEAD_LEN);
+    let mut hasher = Sha256::new();
+
+    loop {
+        let chunk = data.next().await;
+
+        if let Some(chunk) = &chunk {
+            let chunk = chunk.as_ref().map_err(|_| StreamError::Read)?;
+            buf.extend_from_slice(chunk.as_ref());
+        }
+
+        if buf.len() >= CHUNK_SIZE || (chunk.is_none() && !buf.is_empty()) {
+            if (offset + buf.len()) as u64 > len {
+                return Err(StreamError::Length(len));
+            }
+
+            let mut conn = pool.acquire().await?;
+            write_at(&mut conn, rowid, offset, &buf).await?;
+
+            if head.len() < HEAD_LEN {
+                head.extend(buf.iter().take(HEAD_LEN - head.len()));
+            }
+            hasher.update(&buf);
+
+            offset += buf.len();
+            buf.clear();
+        }
+
+        if chunk.is_none() {
+            break;
+        }
+    }
+
+    if offset as u64 != len {
+        return Err(StreamError::Length(len));
+    }
+
+    Ok(Written {
+        head,
+        sha256: hex::encode(hasher.finalize()),
+    })
+}
+
+/// Streams the bytes `start..end` of the attachment `rowid`
+/// in chunks of at most `CHUNK_SIZE` bytes.
+///
+/// The caller is expected to have checked that the range is within the blob,
+/// reads past its end make the stream yield an `Err`.
+pub fn read_stream(
+    pool: SqlitePool,
+    rowid: i64,
+    start: u64,
+    end: u64,
+) -> impl Stream<Item = anyhow::Result<Vec<u8>>> {
+    stream::try_unfold(start, move |offset| {
+        let pool = pool.clone();
+
+        async move {
+            if offset >= end {
+                return Ok(None);
+            }
+
+            let len = (end - offset).min(CHUNK_SIZE as u64);
+
+            let mut conn = pool.acquire().await?;
+            let chunk = read_at(&mut conn, rowid, offset as usize, len as usize).await?;
+
+            Ok(Some((chunk, offset + len)))
+        }
+    })
+}
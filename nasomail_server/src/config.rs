@@ -1,86 +1,132 @@
 use serde::{Deserialize, Serialize};
-use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::{RwLock, RwLockReadGuard};
+
+use nasomail_shared::payload::webhook::WebhookEvent;
 
 #[derive(Debug)]
 pub struct Config {
     db_path: RwLock<String>,
-    schema_path: RwLock<String>,
 
     addr: RwLock<String>,
     pub_addr: RwLock<String>,
-}
+    smtp_addr: RwLock<Option<String>>,
+    imap_addr: RwLock<Option<String>>,
+    pop3_addr: RwLock<Option<String>>,
 
-impl Config {
-    pub fn new(db_path: String, schema_path: String, addr: String, pub_addr: String) -> Self {
-        Self {
-            db_path: RwLock::new(db_path),
-            schema_path: RwLock::new(schema_path),
+    argon2: RwLock<Argon2Config>,
 
-            addr: RwLock::new(addr),
-            pub_addr: RwLock::new(pub_addr),
-        }
-    }
+    session_lifetime_secs: RwLock<u64>,
+
+    max_attachment_size: RwLock<u64>,
+
+    trash_retention_days: RwLock<u64>,
+
+    undo_send_secs: RwLock<u64>,
+
+    allow_private_hosts: RwLock<bool>,
+
+    delivery: RwLock<DeliveryConfig>,
+
+    relay: RwLock<Option<RelayConfig>>,
+
+    webhooks: RwLock<Vec<WebhookConfig>>,
+}
 
+impl Config {
     pub async fn to_ser(&self) -> ConfigSerializable {
         ConfigSerializable {
             db_path: self.db_path.read().await.clone(),
-            schema_path: self.schema_path.read().await.clone(),
 
             addr: self.addr.read().await.clone(),
             pub_addr: self.pub_addr.read().await.clone(),
+            smtp_addr: self.smtp_addr.read().await.clone(),
+            imap_addr: self.imap_addr.read().await.clone(),
+            pop3_addr: self.pop3_addr.read().await.clone(),
+
+            argon2: self.argon2.read().await.clone(),
+
+            session_lifetime_secs: *self.session_lifetime_secs.read().await,
+
+            max_attachment_size: *self.max_attachment_size.read().await,
+
+            trash_retention_days: *self.trash_retention_days.read().await,
+
+            undo_send_secs: *self.undo_send_secs.read().await,
+
+            allow_private_hosts: *self.allow_private_hosts.read().await,
+
+            delivery: self.delivery.read().await.clone(),
+
+            relay: self.relay.read().await.clone(),
+
+            webhooks: self.webhooks.read().await.clone(),
         }
     }
 
     pub async fn db_path(&self) -> RwLockReadGuard<'_, String> {
         self.db_path.read().await
     }
-    pub async fn db_path_mut(&self) -> RwLockWriteGuard<'_, String> {
-        self.db_path.write().await
+
+    pub async fn addr(&self) -> RwLockReadGuard<'_, String> {
+        self.addr.read().await
     }
-    pub async fn set_db_path(&mut self, value: String) {
-        self.db_path = RwLock::new(value);
+
+    pub async fn pub_addr(&self) -> RwLockReadGuard<'_, String> {
+        self.pub_addr.read().await
     }
 
-    pub async fn schema_path(&self) -> RwLockReadGuard<'_, String> {
-        self.schema_path.read().await
+    pub async fn smtp_addr(&self) -> RwLockReadGuard<'_, Option<String>> {
+        self.smtp_addr.read().await
     }
-    pub async fn schema_path_mut(&self) -> RwLockWriteGuard<'_, String> {
-        self.schema_path.write().await
+
+    pub async fn imap_addr(&self) -> RwLockReadGuard<'_, Option<String>> {
+        self.imap_addr.read().await
     }
-    pub async fn set_schema_path(&mut self, value: String) {
-        self.schema_path = RwLock::new(value);
+
+    pub async fn pop3_addr(&self) -> RwLockReadGuard<'_, Option<String>> {
+        self.pop3_addr.read().await
     }
 
-    pub async fn addr(&self) -> RwLockReadGuard<'_, String> {
-        self.addr.read().await
+    pub async fn argon2(&self) -> RwLockReadGuard<'_, Argon2Config> {
+        self.argon2.read().await
+    }
+
+    pub async fn session_lifetime_secs(&self) -> RwLockReadGuard<'_, u64> {
+        self.session_lifetime_secs.read().await
     }
-    pub async fn addr_mut(&self) -> RwLockWriteGuard<'_, String> {
-        self.addr.write().await
+
+    pub async fn max_attachment_size(&self) -> RwLockReadGuard<'_, u64> {
+        self.max_attachment_size.read().await
     }
-    pub async fn set_addr(&mut self, value: String) {
-        self.addr = RwLock::new(value);
+
+    pub async fn trash_retention_days(&self) -> RwLockReadGuard<'_, u64> {
+        self.trash_retention_days.read().await
     }
 
-    pub async fn pub_addr(&self) -> RwLockReadGuard<'_, String> {
-        self.pub_addr.read().await
+    pub async fn undo_send_secs(&self) -> RwLockReadGuard<'_, u64> {
+        self.undo_send_secs.read().await
     }
-    pub async fn pub_addr_mut(&self) -> RwLockWriteGuard<'_, String> {
-        self.pub_addr.write().await
+
+    pub async fn allow_private_hosts(&self) -> RwLockReadGuard<'_, bool> {
+        self.allow_private_hosts.read().await
     }
-    pub async fn set_pub_addr(&mut self, value: String) {
-        self.pub_addr = RwLock::new(value);
+
+    pub async fn delivery(&self) -> RwLockReadGuard<'_, DeliveryConfig> {
+        self.delivery.read().await
+    }
+
+    pub async fn relay(&self) -> RwLockReadGuard<'_, Option<RelayConfig>> {
+        self.relay.read().await
+    }
+
+    pub async fn webhooks(&self) -> RwLockReadGuard<'_, Vec<WebhookConfig>> {
+        self.webhooks.read().await
     }
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self {
-            db_path: RwLock::new("database.sqlite".to_owned()),
-            schema_path: RwLock::new("sql/schema.sql".to_owned()),
-
-            addr: RwLock::new("0.0.0.0:8080".to_owned()),
-            pub_addr: RwLock::new("mail.example.com:8080".to_owned()),
-        }
+        Self::from(ConfigSerializable::default())
     }
 }
 
@@ -88,31 +134,203 @@ impl From<ConfigSerializable> for Config {
     fn from(value: ConfigSerializable) -> Self {
         Self {
             db_path: RwLock::new(value.db_path),
-            schema_path: RwLock::new(value.schema_path),
 
             addr: RwLock::new(value.addr),
             pub_addr: RwLock::new(value.pub_addr),
+            smtp_addr: RwLock::new(value.smtp_addr),
+            imap_addr: RwLock::new(value.imap_addr),
+            pop3_addr: RwLock::new(value.pop3_addr),
+
+            argon2: RwLock::new(value.argon2),
+
+            session_lifetime_secs: RwLock::new(value.session_lifetime_secs),
+
+            max_attachment_size: RwLock::new(value.max_attachment_size),
+
+            trash_retention_days: RwLock::new(value.trash_retention_days),
+
+            undo_send_secs: RwLock::new(value.undo_send_secs),
+
+            allow_private_hosts: RwLock::new(value.allow_private_hosts),
+
+            delivery: RwLock::new(value.delivery),
+
+            relay: RwLock::new(value.relay),
+
+            webhooks: RwLock::new(value.webhooks),
         }
     }
 }
 
+/// The on-disk representation of `Config`.
+///
+/// Fields missing from an existing config file
+/// fall back to their default values, so older
+/// config files keep working as new fields are added.
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct ConfigSerializable {
     pub db_path: String,
-    pub schema_path: String,
 
     pub addr: String,
     pub pub_addr: String,
+    pub smtp_addr: Option<String>,
+    pub imap_addr: Option<String>,
+    pub pop3_addr: Option<String>,
+
+    pub argon2: Argon2Config,
+
+    pub session_lifetime_secs: u64,
+
+    pub max_attachment_size: u64,
+
+    /// How many days mails stay in the trash before they are deleted for good.
+    pub trash_retention_days: u64,
+
+    /// For how many seconds a mail that was just sent can still be
+    /// cancelled back into a draft, `0` sends mails right away.
+    pub undo_send_secs: u64,
+
+    /// Whether other servers and webhooks may be reached on loopback,
+    /// private and link-local addresses, e.g, to run several servers on
+    /// one machine. Only read on startup, see `net`.
+    pub allow_private_hosts: bool,
+
+    pub delivery: DeliveryConfig,
+
+    pub relay: Option<RelayConfig>,
+
+    pub webhooks: Vec<WebhookConfig>,
 }
 
-impl ConfigSerializable {
-    pub async fn from_cfg(value: &Config) -> Self {
+impl Default for ConfigSerializable {
+    fn default() -> Self {
         Self {
-            db_path: value.db_path().await.clone(),
-            schema_path: value.schema_path().await.clone(),
+            db_path: "database.sqlite".to_owned(),
+
+            addr: "0.0.0.0:8080".to_owned(),
+            pub_addr: "mail.example.com:8080".to_owned(),
+            smtp_addr: None,
+            imap_addr: None,
+            pop3_addr: None,
 
-            addr: value.addr().await.clone(),
-            pub_addr: value.pub_addr().await.clone(),
+            argon2: Argon2Config::default(),
+
+            session_lifetime_secs: 60 * 60 * 24 * 30,
+
+            max_attachment_size: 25 * 1024 * 1024,
+
+            trash_retention_days: 30,
+
+            undo_send_secs: 0,
+
+            allow_private_hosts: false,
+
+            delivery: DeliveryConfig::default(),
+
+            relay: None,
+
+            webhooks: Vec::new(),
         }
     }
 }
+
+/// The cost parameters used when hashing passphrases with Argon2id.
+///
+/// Existing hashes made with different parameters are
+/// rehashed the next time their user authenticates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Argon2Config {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Argon2Config {
+    pub fn params(&self) -> Result<argon2::Params, argon2::Error> {
+        argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+    }
+}
+
+impl Default for Argon2Config {
+    fn default() -> Self {
+        Self {
+            memory_kib: argon2::Params::DEFAULT_M_COST,
+            iterations: argon2::Params::DEFAULT_T_COST,
+            parallelism: argon2::Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// How the delivery queue retries mails that could not be delivered.
+///
+/// The delay between attempts starts at `initial_backoff_secs` and
+/// doubles after every failed attempt, up to `max_backoff_secs`.
+/// Mails still undelivered after `lifetime_secs` are bounced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeliveryConfig {
+    pub initial_backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    pub lifetime_secs: u64,
+}
+
+impl DeliveryConfig {
+    /// Returns how long to wait after the `attempts`-th failed attempt.
+    pub fn backoff_secs(&self, attempts: u32) -> u64 {
+        let factor = 1u64
+            .checked_shl(attempts.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+
+        self.initial_backoff_secs
+            .saturating_mul(factor)
+            .min(self.max_backoff_secs)
+    }
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff_secs: 60,
+            max_backoff_secs: 60 * 60 * 4,
+            lifetime_secs: 60 * 60 * 24 * 5,
+        }
+    }
+}
+
+/// The SMTP smarthost that mail for regular email addresses is relayed
+/// through, i.e, recipients like `user@example.com` whose host has no port.
+///
+/// `username` and `password` are only used if both are set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayConfig {
+    pub host: String,
+    pub port: u16,
+    pub starttls: bool,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            host: "smtp.example.com".to_owned(),
+            port: 587,
+            starttls: true,
+            username: None,
+            password: None,
+        }
+    }
+}
+
+/// A server-wide webhook, which gets the events of every user.
+///
+/// `events` are the types of events to post,
+/// e.g, `["received", "status_changed"]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub secret: String,
+}
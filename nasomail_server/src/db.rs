@@ -0,0 +1,136 @@
+//! Upgrades for databases created by versions of
+//! `nasomail_server` from before versioned migrations.
+//!
+//! These bring such databases to the state of the first
+//! migration, after which `migrate` takes over.
+
+use sqlx::{Connection, sqlite::SqlitePool};
+use tracing::{info, instrument};
+
+/// Runs every legacy upgrade on the database behind `pool`.
+///
+/// Each upgrade checks whether it is needed on its own,
+/// so this is safe to run on any legacy database.
+///
+/// # Errors
+///
+/// Returns `Err` if any of the upgrades fail.
+///
+pub async fn upgrade_legacy(pool: &SqlitePool) -> anyhow::Result<()> {
+    relax_passphrase_check(pool).await?;
+    add_attachment_metadata(pool).await?;
+
+    Ok(())
+}
+
+/// Rebuilds the `users` table of databases created before passphrases
+/// were hashed, whose `CHECK` constraint caps passphrases at 20 characters
+/// and would therefore reject every Argon2id hash.
+///
+/// Does nothing if the `users` table does not have the old constraint.
+///
+/// # Errors
+///
+/// Returns `Err` if any of the statements fail,
+/// in which case the table is left untouched.
+///
+#[instrument(skip(pool))]
+async fn relax_passphrase_check(pool: &SqlitePool) -> anyhow::Result<()> {
+    let sql: Option<String> =
+        sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'users'")
+            .fetch_optional(pool)
+            .await?;
+
+    if !sql.is_some_and(|sql| sql.contains("LENGTH(passphrase) <=")) {
+        return Ok(());
+    }
+
+    info!("rebuilding users table");
+
+    // `mails` references `users`, so foreign keys have to be off
+    // while the old table is dropped or every mail would cascade with it.
+    let mut conn = pool.acquire().await?;
+    sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut *conn)
+        .await?;
+
+    let result = async {
+        let mut tx = conn.begin().await?;
+
+        sqlx::query(
+            "CREATE TABLE users_new (
+                id         INTEGER  PRIMARY KEY,
+
+                name TEXT UNIQUE NOT NULL COLLATE NOCASE
+                    CHECK (name = TRIM(name) AND LENGTH(name) >=  3 AND LENGTH(name) <=  20),
+
+                passphrase TEXT NOT NULL,
+
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO users_new (id, name, passphrase, created_at)
+                SELECT id, name, passphrase, created_at FROM users",
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DROP TABLE users").execute(&mut *tx).await?;
+        sqlx::query("ALTER TABLE users_new RENAME TO users")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+    .await;
+
+    sqlx::query("PRAGMA foreign_keys = ON")
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(result?)
+}
+
+/// Adds the `filename`, `content_type`, `size` and `sha256` columns
+/// to the `attachments` table of databases created before attachments
+/// had any metadata, filling in `size` for existing rows.
+///
+/// Does nothing if the `attachments` table already has the columns.
+///
+/// # Errors
+///
+/// Returns `Err` if any of the statements fail,
+/// in which case the table is left untouched.
+///
+#[instrument(skip(pool))]
+async fn add_attachment_metadata(pool: &SqlitePool) -> anyhow::Result<()> {
+    let has_columns: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info('attachments') WHERE name = 'sha256')",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if has_columns {
+        return Ok(());
+    }
+
+    info!("adding attachment metadata columns");
+
+    let mut tx = pool.begin().await?;
+
+    for stmt in [
+        "ALTER TABLE attachments ADD COLUMN filename TEXT NOT NULL DEFAULT ''
+            CHECK (filename = TRIM(filename) AND LENGTH(filename) <=  255)",
+        "ALTER TABLE attachments ADD COLUMN content_type TEXT NOT NULL DEFAULT 'application/octet-stream'
+            CHECK (content_type = TRIM(content_type) AND LENGTH(content_type) <=  255)",
+        "ALTER TABLE attachments ADD COLUMN size INTEGER NOT NULL DEFAULT 0",
+        "ALTER TABLE attachments ADD COLUMN sha256 TEXT",
+        "UPDATE attachments SET size = LENGTH(data)",
+    ] {
+        sqlx::query(stmt).execute(&mut *tx).await?;
+    }
+
+    Ok(tx.commit().await?)
+}
@@ -0,0 +1,976 @@
+//! Delivery of mail into the `mails` table.
+//!
+//! Every path that puts a mail into someone's
+//! inbox should go through this module, so the
+//! filter of its owner, see `sieve`, is run on it.
+
+use sha2::{Digest, Sha256};
+use sqlx::{SqliteConnection, sqlite::SqlitePool};
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+use nasomail_shared::{
+    address::{self, Address},
+    payload::{
+        event::EventPayload,
+        mail::{BODY_MAX_LEN, MESSAGE_ID_MAX_LEN, MailStatus, REFERENCES_MAX_LEN, SUBJECT_MAX_LEN},
+    },
+};
+
+use crate::{events::Events, message, queue, sieve, vacation};
+
+/// The sender of bounces, i.e, mails about mails that could not be delivered.
+pub const MAILER_DAEMON: &str = "mailer-daemon";
+
+/// Usernames nobody may register, since mail from them
+/// would look like it came from the server itself.
+pub const RESERVED_USERNAMES: &[&str] = &[MAILER_DAEMON, "postmaster"];
+
+/// A mail that is about to be delivered.
+///
+/// `subject`, `body` and the message ids are expected
+/// to already satisfy the constraints of the `mails` table.
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub sender: String,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+
+    /// The `Message-ID`, without angle brackets, see `message_id`.
+    pub message_id: String,
+
+    /// The `message_id` of the mail this one replies to.
+    pub in_reply_to: Option<String>,
+
+    /// The `message_id`s of the mails before this one in its thread, oldest first.
+    pub references: Vec<String>,
+
+    /// Whether the mail was generated automatically, e.g, a bounce or an
+    /// out-of-office reply, which is never replied to automatically (RFC 3834).
+    pub auto_submitted: bool,
+}
+
+/// The columns of a row of the `mails` table that make up a `Mail`.
+#[derive(sqlx::FromRow)]
+pub struct MailRow {
+    pub sender: String,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub message_id: String,
+    pub in_reply_to: Option<String>,
+    pub refs: String,
+    pub auto_submitted: bool,
+}
+
+impl From<MailRow> for Mail {
+    fn from(value: MailRow) -> Self {
+        Self {
+            sender: value.sender,
+            recipient: value.recipient,
+            subject: value.subject,
+            body: value.body,
+            message_id: value.message_id,
+            in_reply_to: value.in_reply_to,
+            references: split_references(&value.refs),
+            auto_submitted: value.auto_submitted,
+        }
+    }
+}
+
+/// An attachment held in memory, e.g, one parsed out of an inbound message.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// A custom error type for mail delivery.
+#[derive(Debug, thiserror::Error)]
+pub enum DeliveryError {
+    #[error("invalid recipient: {0}")]
+    InvalidRecipient(String),
+
+    #[error("no such recipient: {0}")]
+    UnknownRecipient(String),
+
+    #[error("no such draft: {0}")]
+    NoSuchDraft(i64),
+
+    #[error("rejected by the filter of the recipient: {0}")]
+    Rejected(String),
+
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// The rows created by a successful delivery.
+#[derive(Debug, Clone, Copy)]
+pub struct Delivered {
+    /// The `id` of the `sent` copy owned by the sender.
+    pub sent_id: i64,
+
+    pub destination: Destination,
+}
+
+/// A delivery whose transaction has not been committed yet, see `send_in`.
+#[derive(Debug)]
+pub struct Staged {
+    sent_id: i64,
+    destination: Destination,
+    inbound: Option<Inbound>,
+}
+
+impl Staged {
+    /// Finishes the delivery once its transaction has been committed, see `delivered`.
+    pub fn finish(self, pool: &SqlitePool, events: &Events, origin: &str) -> Delivered {
+        delivered(
+            pool,
+            events,
+            origin,
+            self.sent_id,
+            self.destination,
+            self.inbound,
+        )
+    }
+}
+
+/// Where a sent mail went.
+#[derive(Debug, Clone, Copy)]
+pub enum Destination {
+    /// Into the inbox of a local user, as the `new` row `received_id`.
+    Local { received_id: i64 },
+
+    /// Into the `delivery_queue` for a remote server, as the row `queue_id`.
+    Remote { queue_id: i64 },
+}
+
+/// A mail that was put into the inbox of a local user, after their filter ran.
+#[derive(Debug, Clone)]
+pub struct Inbound {
+    pub id: i64,
+    pub user_id: i64,
+
+    /// Whether the filter moved the mail to the trash.
+    pub trashed: bool,
+
+    /// The addresses the filter forwards the mail to.
+    pub redirects: Vec<String>,
+}
+
+/// The recipient of a mail that is being sent.
+enum Route {
+    /// A local user, by `id` and name as it was registered.
+    Local(i64, String),
+
+    /// A user of another NasoMail server.
+    Remote(Address),
+}
+
+impl Route {
+    /// Returns the recipient as it should be stored in the `mails` table.
+    fn recipient(&self) -> String {
+        match self {
+            Self::Local(_, name) => name.clone(),
+            Self::Remote(address) => address.to_string(),
+        }
+    }
+}
+
+/// Works out where mail for `recipient` should go, treating addresses
+/// whose host is `origin` (i.e, `pub_addr`) like bare usernames.
+///
+/// # Errors
+///
+/// Returns `Err(InvalidRecipient)` if `recipient` is not a valid address.
+/// Returns `Err(UnknownRecipient)` if `recipient` is local but not a registered user.
+/// Returns `Err(Database)`         if the database could not be queried.
+///
+async fn route(
+    conn: &mut SqliteConnection,
+    origin: &str,
+    recipient: &str,
+) -> Result<Route, DeliveryError> {
+    let address: Address = recipient
+        .parse()
+        .map_err(|_| DeliveryError::InvalidRecipient(recipient.to_owned()))?;
+
+    if !address.is_local(origin) {
+        return Ok(Route::Remote(address));
+    }
+
+    match resolve_local(conn, &address.user).await? {
+        Some((id, name)) => Ok(Route::Local(id, name)),
+        None => Err(DeliveryError::UnknownRecipient(recipient.to_owned())),
+    }
+}
+
+/// Checks that mail can be sent to `recipient` as the server at `origin`,
+/// without sending anything, e.g, before a draft is scheduled.
+///
+/// Returns the recipient as it would be stored in the `mails` table.
+///
+/// # Errors
+///
+/// Returns `Err(InvalidRecipient)` if `recipient` is not a valid address.
+/// Returns `Err(UnknownRecipient)` if `recipient` is local but not a registered user.
+/// Returns `Err(Database)`         if the database could not be queried.
+///
+pub async fn check_recipient(
+    conn: &mut SqliteConnection,
+    origin: &str,
+    recipient: &str,
+) -> Result<String, DeliveryError> {
+    Ok(route(conn, origin, recipient).await?.recipient())
+}
+
+/// Generates a new globally unique `Message-ID` for
+/// a mail written on the server at `origin`.
+pub fn message_id(origin: &str) -> String {
+    format!("{}@{}", Uuid::new_v4().simple(), address::host_name(origin))
+}
+
+/// Turns a `Message-ID` as it appears in a header, i.e, with or without
+/// angle brackets, into a `message_id`.
+///
+/// Returns `None` if it is empty, too long or contains
+/// whitespace, angle brackets or control characters.
+///
+pub fn parse_message_id(value: &str) -> Option<String> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix('<')
+        .and_then(|value| value.strip_suffix('>'))
+        .unwrap_or(value);
+
+    let valid = !value.is_empty()
+        && value.len() <= MESSAGE_ID_MAX_LEN
+        && value
+            .chars()
+            .all(|c| c.is_ascii_graphic() && c != '<' && c != '>');
+
+    valid.then(|| value.to_owned())
+}
+
+/// Returns the `references` of a reply to (or forward of) a
+/// mail with `message_id` and `references`.
+pub fn references_of(message_id: &str, references: &[String]) -> Vec<String> {
+    let mut references = references.to_vec();
+    references.push(message_id.to_owned());
+
+    fit_references(references)
+}
+
+/// Cuts `references` down to fit the `mails` table by dropping the ones
+/// right after the first, so the thread can still be told apart.
+pub fn fit_references(mut references: Vec<String>) -> Vec<String> {
+    while join_references(&references).len() > REFERENCES_MAX_LEN && references.len() > 1 {
+        references.remove(if references.len() > 2 { 1 } else { 0 });
+    }
+
+    references
+}
+
+/// Formats `references` for the `refs` column of the `mails` table.
+pub fn join_references(references: &[String]) -> String {
+    references.join(" ")
+}
+
+/// Parses the `refs` column of the `mails` table.
+pub fn split_references(refs: &str) -> Vec<String> {
+    refs.split_whitespace().map(str::to_owned).collect()
+}
+
+/// Looks up the local user named `name` (case-insensitively).
+///
+/// Returns `Ok(Some((id, name)))` with the name as it was registered.
+/// Returns `Ok(None)` if there is no such user.
+///
+pub async fn resolve_local(
+    conn: &mut SqliteConnection,
+    name: &str,
+) -> Result<Option<(i64, String)>, sqlx::Error> {
+    sqlx::query_as("SELECT id, name FROM users WHERE name = ?")
+        .bind(name)
+        .fetch_optional(conn)
+        .await
+}
+
+/// Inserts `mail` into the `mails` table as a row
+/// owned by `user_id` with the given `status`.
+///
+/// The mail joins the thread of the first mail of the user that shares its
+/// `message_id`, is the one it replies to or one of its `references`, or
+/// starts a new one if there is none. Replies that arrive before the mail
+/// they reply to therefore start a thread of their own.
+pub async fn insert(
+    conn: &mut SqliteConnection,
+    user_id: i64,
+    mail: &Mail,
+    status: &str,
+) -> Result<i64, sqlx::Error> {
+    let related: Vec<&str> = [mail.message_id.as_str()]
+        .into_iter()
+        .chain(mail.in_reply_to.as_deref())
+        .chain(mail.references.iter().map(String::as_str))
+        .collect();
+
+    sqlx::query_scalar(
+        "INSERT INTO mails (
+                user_id, subject, body, sender, recipient, status,
+                message_id, in_reply_to, refs, auto_submitted, thread_id
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, (
+                SELECT thread_id FROM mails
+                    WHERE user_id = ? AND message_id IN (SELECT value FROM JSON_EACH(?))
+                    ORDER BY id LIMIT 1
+            ))
+            RETURNING id",
+    )
+    .bind(user_id)
+    .bind(&mail.subject)
+    .bind(&mail.body)
+    .bind(&mail.sender)
+    .bind(&mail.recipient)
+    .bind(status)
+    .bind(&mail.message_id)
+    .bind(&mail.in_reply_to)
+    .bind(join_references(&mail.references))
+    .bind(mail.auto_submitted)
+    .bind(user_id)
+    .bind(serde_json::to_string(&related).unwrap_or_default())
+    .fetch_one(conn)
+    .await
+}
+
+/// Copies every attachment of the mail `from` onto the mail `to`.
+pub async fn copy_attachments(
+    conn: &mut SqliteConnection,
+    from: i64,
+    to: i64,
+) -> Result<(), sqlx::Error> {
+    // The data is copied inside SQLite, never through this process
+    sqlx::query(
+        "INSERT INTO attachments (mail_id, data, filename, content_type, size, sha256, created_at)
+            SELECT ?, data, filename, content_type, size, sha256, created_at
+                FROM attachments WHERE mail_id = ? ORDER BY id",
+    )
+    .bind(to)
+    .bind(from)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the total `size` of the attachments of the mail `mail_id`.
+pub async fn attachments_size(
+    conn: &mut SqliteConnection,
+    mail_id: i64,
+) -> Result<u64, sqlx::Error> {
+    let size: i64 =
+        sqlx::query_scalar("SELECT COALESCE(SUM(size), 0) FROM attachments WHERE mail_id = ?")
+            .bind(mail_id)
+            .fetch_one(conn)
+            .await?;
+
+    Ok(size as u64)
+}
+
+/// Inserts `mail`, whose attachments take up `attachments_size` bytes, into
+/// the inbox of `user_id` as a `new` row, after running their filter on it.
+///
+/// The filter may mark the mail as `read`, move it to the trash, add labels
+/// to it, creating the ones that do not exist yet, or ask for it to be
+/// forwarded, which is up to `announce` once it is committed. A filter that
+/// no longer compiles is skipped, so the mail is delivered as usual.
+///
+/// # Errors
+///
+/// Returns `Err(Rejected)` if the filter rejects the mail, nothing is inserted then.
+/// Returns `Err(Database)` if the database could not be queried.
+///
+async fn insert_filtered(
+    conn: &mut SqliteConnection,
+    user_id: i64,
+    mail: &Mail,
+    attachments_size: u64,
+) -> Result<Inbound, DeliveryError> {
+    let script: Option<String> = sqlx::query_scalar("SELECT script FROM filters WHERE user_id = ?")
+        .bind(user_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    let message = sieve::Message {
+        sender: &mail.sender,
+        recipient: &mail.recipient,
+        subject: &mail.subject,
+        body: &mail.body,
+        size: mail.body.len() as u64 + attachments_size,
+    };
+
+    let outcome = match script.as_deref().map(sieve::compile) {
+        Some(Ok(script)) => sieve::evaluate(&script, &message),
+        Some(Err(errors)) => {
+            warn!(user_id = user_id, errors = ?errors, "skipping filter that does not compile");
+            sieve::Outcome::default()
+        }
+        None => sieve::Outcome::default(),
+    };
+
+    if let Some(reason) = outcome.reject {
+        return Err(DeliveryError::Rejected(reason));
+    }
+
+    let id = insert(
+        conn,
+        user_id,
+        mail,
+        if outcome.seen { "read" } else { "new" },
+    )
+    .await?;
+
+    if !outcome.keep {
+        sqlx::query("UPDATE mails SET deleted_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    for label in &outcome.labels {
+        sqlx::query("INSERT OR IGNORE INTO labels (user_id, name, color) VALUES (?, ?, ?)")
+            .bind(user_id)
+            .bind(label)
+            .bind(sieve::LABEL_COLOR)
+            .execute(&mut *conn)
+            .await?;
+
+        sqlx::query(
+            "INSERT OR IGNORE INTO mail_labels (mail_id, label_id)
+                SELECT ?, id FROM labels WHERE user_id = ? AND name = ?",
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(label)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(Inbound {
+        id,
+        user_id,
+        trashed: !outcome.keep,
+        redirects: outcome.redirects,
+    })
+}
+
+/// Publishes the committed `inbound` mail to `events`, then, in the background
+/// and as the server at `origin`, sends the out-of-office reply of its owner,
+/// see `vacation`, unless it went to the trash, and forwards it as its filter
+/// asked for.
+///
+/// Every path that delivers a mail into an inbox ends up here, except for
+/// `bounce`, since nothing is ever done with bounces automatically.
+pub fn announce(pool: &SqlitePool, events: &Events, origin: &str, inbound: Inbound) {
+    events.publish(inbound.user_id, EventPayload::Received { id: inbound.id });
+
+    if inbound.trashed {
+        events.publish(inbound.user_id, EventPayload::Trashed { id: inbound.id });
+    } else {
+        tokio::spawn(vacation::respond(
+            pool.clone(),
+            events.clone(),
+            origin.to_owned(),
+            inbound.id,
+            inbound.user_id,
+        ));
+    }
+
+    if !inbound.redirects.is_empty() {
+        tokio::spawn(redirect(
+            pool.clone(),
+            events.clone(),
+            origin.to_owned(),
+            inbound,
+        ));
+    }
+}
+
+/// Forwards the mail `inbound` to each of its `redirects` on behalf of its
+/// owner, unless the owner has already sent a mail with its `message_id`.
+///
+/// Forwarded mails keep their `message_id`, so a mail that is redirected back
+/// and forth between users is only ever forwarded once by each of them.
+async fn redirect(pool: SqlitePool, events: Events, origin: String, inbound: Inbound) {
+    let result = async {
+        let mut conn = pool.acquire().await?;
+
+        let Some(mail): Option<MailRow> = sqlx::query_as(
+            "SELECT sender, recipient, subject, body, message_id, in_reply_to, refs,
+                    auto_submitted
+                FROM mails WHERE id = ?",
+        )
+        .bind(inbound.id)
+        .fetch_optional(&mut *conn)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let (name, sent): (String, bool) = sqlx::query_as(
+            "SELECT name, EXISTS (
+                    SELECT 1 FROM mails
+                        WHERE user_id = users.id AND message_id = ? AND status = 'sent'
+                )
+                FROM users WHERE id = ?",
+        )
+        .bind(&mail.message_id)
+        .bind(inbound.user_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok::<_, sqlx::Error>((!sent).then(|| Mail {
+            sender: name,
+            ..Mail::from(mail)
+        }))
+    }
+    .await;
+
+    let mail = match result {
+        Ok(Some(mail)) => mail,
+        Ok(None) => return,
+        Err(e) => {
+            warn!(err = ?e, id = inbound.id, "failed to look up mail to redirect");
+            return;
+        }
+    };
+
+    for address in &inbound.redirects {
+        let mail = Mail {
+            recipient: address.clone(),
+            ..mail.clone()
+        };
+
+        match send(
+            &pool,
+            &events,
+            &origin,
+            inbound.user_id,
+            &mail,
+            Some(inbound.id),
+        )
+        .await
+        {
+            Ok(delivered) => {
+                info!(id = inbound.id, sent_id = delivered.sent_id, "redirected");
+            }
+            Err(e) => {
+                warn!(err = ?e, id = inbound.id, recipient = %address, "failed to redirect");
+            }
+        }
+    }
+}
+
+/// Delivers `mail`, whose attachments take up `attachments_size` bytes,
+/// into the inbox of its local recipient as a `new` row, after running
+/// their filter on it, see `insert_filtered`.
+///
+/// The returned `Inbound` should be passed to `announce` once committed.
+///
+/// # Errors
+///
+/// Returns `Err(UnknownRecipient)` if `mail.recipient` is not a registered user.
+/// Returns `Err(Rejected)`         if the filter of the recipient rejects the mail.
+/// Returns `Err(Database)`         if the database could not be queried.
+///
+pub async fn deliver_local(
+    conn: &mut SqliteConnection,
+    mail: &Mail,
+    attachments_size: u64,
+) -> Result<Inbound, DeliveryError> {
+    let Some((recipient_id, recipient)) = resolve_local(conn, &mail.recipient).await? else {
+        return Err(DeliveryError::UnknownRecipient(mail.recipient.clone()));
+    };
+
+    let mail = Mail {
+        recipient,
+        ..mail.clone()
+    };
+
+    insert_filtered(conn, recipient_id, &mail, attachments_size).await
+}
+
+/// Delivers `mail` along with its `attachments` into the inbox of its local
+/// recipient as a `new` row, in a single transaction, and announces it as the
+/// server at `origin`, see `announce`.
+///
+/// Returns the `id` of the new row.
+///
+/// # Errors
+///
+/// Returns `Err(UnknownRecipient)` if `mail.recipient` is not a registered user.
+/// Returns `Err(Rejected)`         if the filter of the recipient rejects the mail.
+/// Returns `Err(Database)`         if the database could not be queried.
+///
+pub async fn receive(
+    pool: &SqlitePool,
+    events: &Events,
+    origin: &str,
+    mail: &Mail,
+    attachments: &[Attachment],
+) -> Result<i64, DeliveryError> {
+    let mut tx = pool.begin().await?;
+
+    let size = attachments
+        .iter()
+        .map(|attachment| attachment.data.len() as u64)
+        .sum();
+
+    let inbound = deliver_local(&mut tx, mail, size).await?;
+    let id = inbound.id;
+
+    for attachment in attachments {
+        sqlx::query(
+            "INSERT INTO attachments (mail_id, data, filename, content_type, size, sha256)
+                VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(id)
+        .bind(&attachment.data)
+        .bind(&attachment.filename)
+        .bind(&attachment.content_type)
+        .bind(attachment.data.len() as i64)
+        .bind(hex::encode(Sha256::digest(&attachment.data)))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    info!(id = id, attachments = attachments.len(), "received");
+    announce(pool, events, origin, inbound);
+
+    Ok(id)
+}
+
+/// Sends `mail` on behalf of the user `sender_id`, as the server at `origin`,
+/// along with the attachments of the mail `attachments_of`, if it is given.
+///
+/// Creates a `sent` copy owned by the sender along with either a `new` row owned
+/// by a local recipient or a `delivery_queue` row for a remote one, in a single
+/// transaction, so either both or neither of them exist.
+///
+/// # Errors
+///
+/// Returns `Err(InvalidRecipient)` if `mail.recipient` is not a valid address.
+/// Returns `Err(UnknownRecipient)` if `mail.recipient` is local but not a registered user.
+/// Returns `Err(Rejected)`         if the filter of a local recipient rejects the mail.
+/// Returns `Err(Database)`         if the database could not be queried.
+///
+#[instrument(skip(pool, events, mail), fields(recipient = %mail.recipient))]
+pub async fn send(
+    pool: &SqlitePool,
+    events: &Events,
+    origin: &str,
+    sender_id: i64,
+    mail: &Mail,
+    attachments_of: Option<i64>,
+) -> Result<Delivered, DeliveryError> {
+    let mut tx = pool.begin().await?;
+
+    let staged = send_in(&mut tx, origin, sender_id, mail, attachments_of).await?;
+
+    tx.commit().await?;
+
+    Ok(staged.finish(pool, events, origin))
+}
+
+/// Like `send`, but within the transaction of `conn`, so the delivery
+/// only happens if the transaction is committed along with whatever
+/// else it does. Call `Staged::finish` once it has been committed.
+///
+/// # Errors
+///
+/// See `send`.
+///
+pub async fn send_in(
+    conn: &mut SqliteConnection,
+    origin: &str,
+    sender_id: i64,
+    mail: &Mail,
+    attachments_of: Option<i64>,
+) -> Result<Staged, DeliveryError> {
+    let route = route(conn, origin, &mail.recipient).await?;
+
+    let mail = Mail {
+        recipient: route.recipient(),
+        ..mail.clone()
+    };
+
+    let sent_id = insert(conn, sender_id, &mail, "sent").await?;
+
+    if let Some(attachments_of) = attachments_of {
+        copy_attachments(conn, attachments_of, sent_id).await?;
+    }
+
+    let (destination, inbound) = match route {
+        Route::Local(recipient_id, _) => {
+            let size = attachments_size(conn, sent_id).await?;
+            let inbound = insert_filtered(conn, recipient_id, &mail, size).await?;
+            copy_attachments(conn, sent_id, inbound.id).await?;
+
+            let destination = Destination::Local {
+                received_id: inbound.id,
+            };
+
+            (destination, Some(inbound))
+        }
+        Route::Remote(address) => {
+            let destination = Destination::Remote {
+                queue_id: queue::enqueue(conn, sent_id, &address).await?,
+            };
+
+            (destination, None)
+        }
+    };
+
+    Ok(Staged {
+        sent_id,
+        destination,
+        inbound,
+    })
+}
+
+/// Sends the draft `draft_id` of the user `sender_id`
+/// to its recipient, as the server at `origin`.
+///
+/// The draft row itself becomes the `sent` copy, so its `id` is kept, but
+/// it gets a new IMAP UID like any mail that moves mailboxes, see `imap`. Its
+/// attachments are copied onto the row of a local recipient, remote servers
+/// fetch them from the `sent` copy instead. The recipient is only validated
+/// now, never while the draft is edited.
+///
+/// Any `send_jobs` row of the draft is removed along with it. If `scheduled`,
+/// the draft is only sent if such a row exists and is due, so a draft whose
+/// job was cancelled in the meantime, see `schedule`, is never sent.
+///
+/// # Errors
+///
+/// Returns `Err(NoSuchDraft)`      if `draft_id` is not a draft owned by `sender_id`,
+///                                 or it is not due to be sent while `scheduled`.
+/// Returns `Err(InvalidRecipient)` if the recipient is not a valid address.
+/// Returns `Err(UnknownRecipient)` if the recipient is local but not a registered user.
+/// Returns `Err(Rejected)`         if the filter of a local recipient rejects the mail.
+/// Returns `Err(Database)`         if the database could not be queried.
+///
+#[instrument(skip(pool, events))]
+pub async fn send_draft(
+    pool: &SqlitePool,
+    events: &Events,
+    origin: &str,
+    sender_id: i64,
+    draft_id: i64,
+    scheduled: bool,
+) -> Result<Delivered, DeliveryError> {
+    let mut tx = pool.begin().await?;
+
+    let Some(draft): Option<MailRow> = sqlx::query_as(
+        "SELECT sender, recipient, subject, body, message_id, in_reply_to, refs, auto_submitted
+            FROM mails
+            WHERE id = ? AND user_id = ? AND status = 'draft' AND deleted_at IS NULL
+              AND (NOT ? OR EXISTS (
+                  SELECT 1 FROM send_jobs
+                      WHERE mail_id = mails.id AND run_at <= CURRENT_TIMESTAMP
+              ))",
+    )
+    .bind(draft_id)
+    .bind(sender_id)
+    .bind(scheduled)
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Err(DeliveryError::NoSuchDraft(draft_id));
+    };
+
+    let route = route(&mut tx, origin, &draft.recipient).await?;
+
+    let mail = Mail {
+        recipient: route.recipient(),
+        ..Mail::from(draft)
+    };
+
+    let (destination, inbound) = match route {
+        Route::Local(recipient_id, _) => {
+            let size = attachments_size(&mut tx, draft_id).await?;
+            let inbound = insert_filtered(&mut tx, recipient_id, &mail, size).await?;
+            copy_attachments(&mut tx, draft_id, inbound.id).await?;
+
+            let destination = Destination::Local {
+                received_id: inbound.id,
+            };
+
+            (destination, Some(inbound))
+        }
+        Route::Remote(address) => {
+            let destination = Destination::Remote {
+                queue_id: queue::enqueue(&mut tx, draft_id, &address).await?,
+            };
+
+            (destination, None)
+        }
+    };
+
+    sqlx::query(
+        "UPDATE mails SET status = 'sent', recipient = ?, created_at = CURRENT_TIMESTAMP
+            WHERE id = ?",
+    )
+    .bind(&mail.recipient)
+    .bind(draft_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("DELETE FROM send_jobs WHERE mail_id = ?")
+        .bind(draft_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    events.publish(
+        sender_id,
+        EventPayload::StatusChanged {
+            id: draft_id,
+            status: MailStatus::Sent,
+        },
+    );
+
+    Ok(delivered(
+        pool,
+        events,
+        origin,
+        draft_id,
+        destination,
+        inbound,
+    ))
+}
+
+/// Logs a committed delivery, announces the `inbound` row of local ones,
+/// see `announce`, and wakes the `queue` worker up for remote ones.
+fn delivered(
+    pool: &SqlitePool,
+    events: &Events,
+    origin: &str,
+    sent_id: i64,
+    destination: Destination,
+    inbound: Option<Inbound>,
+) -> Delivered {
+    match destination {
+        Destination::Local { received_id, .. } => {
+            info!(sent_id = sent_id, received_id = received_id, "delivered");
+        }
+        Destination::Remote { queue_id } => {
+            info!(sent_id = sent_id, queue_id = queue_id, "queued");
+            queue::wake();
+        }
+    }
+
+    if let Some(inbound) = inbound {
+        announce(pool, events, origin, inbound);
+    }
+
+    Delivered {
+        sent_id,
+        destination,
+    }
+}
+
+/// Puts a bounce for the queued mail `queue_id`, which could not be delivered
+/// because of `reason`, into the inbox of its sender, see `bounce_mail`.
+///
+/// Returns `Ok(Some((id, user_id)))` with the `id` of the bounce and its owner.
+/// Returns `Ok(None)`                if there is no such queued mail.
+///
+pub async fn bounce(
+    conn: &mut SqliteConnection,
+    origin: &str,
+    queue_id: i64,
+    reason: &str,
+) -> Result<Option<(i64, i64)>, sqlx::Error> {
+    let Some(mail_id): Option<i64> =
+        sqlx::query_scalar("SELECT mail_id FROM delivery_queue WHERE id = ?")
+            .bind(queue_id)
+            .fetch_optional(&mut *conn)
+            .await?
+    else {
+        return Ok(None);
+    };
+
+    let bounced = bounce_mail(conn, origin, mail_id, reason).await?;
+
+    if let Some((id, _)) = bounced {
+        info!(id = id, queue_id = queue_id, "bounced");
+    }
+
+    Ok(bounced)
+}
+
+/// Puts a bounce for the mail `mail_id`, which could not be delivered because
+/// of `reason`, into the inbox of its owner, as the server at `origin`.
+///
+/// The bounce is a reply to the mail, so it ends up in its thread.
+///
+/// Returns `Ok(Some((id, user_id)))` with the `id` of the bounce and its owner.
+/// Returns `Ok(None)`                if there is no such mail.
+///
+pub async fn bounce_mail(
+    conn: &mut SqliteConnection,
+    origin: &str,
+    mail_id: i64,
+    reason: &str,
+) -> Result<Option<(i64, i64)>, sqlx::Error> {
+    let Some((user_id, sender, recipient, subject, created_at, message_id, refs)): Option<(
+        i64,
+        String,
+        String,
+        String,
+        String,
+        String,
+        String,
+    )> = sqlx::query_as(
+        "SELECT user_id, sender, recipient, subject, created_at, message_id, refs
+            FROM mails WHERE id = ?",
+    )
+    .bind(mail_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let mut body = format!(
+        "Your mail to {recipient} could not be delivered.\n\n\
+         Subject: {subject}\n\
+         Sent: {created_at}"
+    );
+
+    let reason = reason.trim();
+    if !reason.is_empty() {
+        body.push_str(&format!("\nReason: {reason}"));
+    }
+
+    let bounce = Mail {
+        sender: MAILER_DAEMON.to_owned(),
+        recipient: sender,
+        subject: message::fit(&format!("Undeliverable: {subject}"), SUBJECT_MAX_LEN),
+        body: message::fit(&body, BODY_MAX_LEN),
+        message_id: self::message_id(origin),
+        in_reply_to: Some(message_id.clone()),
+        references: references_of(&message_id, &split_references(&refs)),
+        auto_submitted: true,
+    };
+
+    let id = insert(conn, user_id, &bounce, "new").await?;
+
+    Ok(Some((id, user_id)))
+}
@@ -0,0 +1,125 @@
+//! The in-process stream of events behind `/api/events`.
+//!
+//! Everything that changes the mails of a user publishes an event here
+//! once its transaction has been committed. Subscribers get every event
+//! through a broadcast channel and filter them by user themselves, and the
+//! most recent events are kept in a ring buffer, so a client reconnecting
+//! with `Last-Event-ID` gets what it missed in the meantime.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::sync::broadcast;
+
+use nasomail_shared::payload::event::EventPayload;
+
+/// How many of the most recent events are kept for replaying.
+const RECENT_LEN: usize = 1024;
+
+/// How many events a subscriber may fall behind before it misses some.
+const CHANNEL_LEN: usize = 256;
+
+/// An event for the user `user_id`.
+#[derive(Debug)]
+pub struct Event {
+    pub id: u64,
+    pub user_id: i64,
+    pub payload: EventPayload,
+}
+
+/// A cheaply cloneable handle to publish and subscribe to events.
+#[derive(Clone)]
+pub struct Events {
+    sender: broadcast::Sender<Arc<Event>>,
+    recent: Arc<Mutex<Recent>>,
+}
+
+struct Recent {
+    events: VecDeque<Arc<Event>>,
+    next_id: u64,
+}
+
+/// A new subscription to events.
+pub struct Subscription {
+    /// The events after the requested one, or `None` if some
+    /// of them are no longer (or never were) in the ring buffer.
+    pub missed: Option<Vec<Arc<Event>>>,
+
+    /// The `id` of the latest event published so far.
+    pub last_id: u64,
+
+    pub receiver: broadcast::Receiver<Arc<Event>>,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        // Start counting at the current time so ids handed out before a
+        // restart are older than every new one and are never mistaken for them
+        let next_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |elapsed| elapsed.as_micros() as u64);
+
+        Self {
+            sender: broadcast::channel(CHANNEL_LEN).0,
+            recent: Arc::new(Mutex::new(Recent {
+                events: VecDeque::with_capacity(RECENT_LEN),
+                next_id,
+            })),
+        }
+    }
+
+    /// Publishes `payload` to the subscribers of `user_id`.
+    pub fn publish(&self, user_id: i64, payload: EventPayload) {
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+
+        let event = Arc::new(Event {
+            id: recent.next_id,
+            user_id,
+            payload,
+        });
+        recent.next_id += 1;
+
+        if recent.events.len() >= RECENT_LEN {
+            recent.events.pop_front();
+        }
+        recent.events.push_back(event.clone());
+
+        // Sending while holding the lock means `subscribe` can never
+        // see an event in both the ring buffer and its receiver
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribes to every event published from now on, along with
+    /// the ones after `last_id` if it is given.
+    pub fn subscribe(&self, last_id: Option<u64>) -> Subscription {
+        let recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+
+        let missed = match last_id {
+            None => Some(Vec::new()),
+            Some(last_id) => {
+                let oldest = recent
+                    .events
+                    .front()
+                    .map_or(recent.next_id, |event| event.id);
+
+                (oldest <= last_id.saturating_add(1) && last_id < recent.next_id).then(|| {
+                    recent
+                        .events
+                        .iter()
+                        .filter(|event| event.id > last_id)
+                        .cloned()
+                        .collect()
+                })
+            }
+        };
+
+        Subscription {
+            missed,
+            last_id: recent.next_id - 1,
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
@@ -0,0 +1,399 @@
+//! Delivery of mail between NasoMail servers.
+//!
+//! Mails for remote recipients are put into the `delivery_queue` under a
+//! random token. The sending server then tells the recipient's server where
+//! the mail is waiting (`POST /api/federation/deliver`) and the recipient's
+//! server fetches it from there (`GET /api/federation/outbound/{token}`).
+//!
+//! Since mails are always fetched from the `origin` they claim to come from,
+//! a server cannot deliver mail in the name of another server without being
+//! able to answer requests at that server's `pub_addr`.
+
+use std::{env, fs, io, path::PathBuf, sync::LazyLock, time::Duration};
+
+use futures_util::StreamExt;
+use reqwest::{Client, StatusCode, redirect};
+use sha2::{Digest, Sha256};
+use sqlx::{SqliteConnection, sqlite::SqlitePool};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+use nasomail_shared::{
+    address::Address,
+    api,
+    payload::{
+        error::ErrorPayload,
+        federation::{DeliverPayload, FederatedAttachment, FederatedMail},
+        mail::BODY_MAX_LEN,
+    },
+};
+
+use crate::{
+    blob::{self, StreamError},
+    delivery::{self, DeliveryError, Inbound, Mail},
+    net::{self, NetError},
+    queue::{Attempt, Outcome},
+};
+
+/// How long to wait for a remote server to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The longest response accepted from `fetch`, in bytes, which leaves room
+/// for a body of `BODY_MAX_LEN` characters that all had to be escaped, i.e,
+/// `\u0000`, along with the rest of the mail and its list of attachments.
+const MAX_MAIL_LEN: usize = BODY_MAX_LEN * 6 + 1024 * 1024;
+
+static CLIENT: LazyLock<Client> = LazyLock::new(|| {
+    Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .dns_resolver(net::PublicResolver)
+        // Redirects could point anywhere, including addresses `net::check` refuses
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap_or_default()
+});
+
+/// A custom error type for federated delivery.
+#[derive(Debug, thiserror::Error)]
+pub enum FederationError {
+    #[error("request to remote server failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("remote server responded with {0}")]
+    Remote(StatusCode),
+
+    #[error("remote server responded with a malformed mail: {0}")]
+    Malformed(#[from] serde_json::Error),
+
+    #[error("mail is larger than allowed")]
+    MailTooLarge,
+
+    #[error("attachment {0} is larger than allowed")]
+    TooLarge(usize),
+
+    #[error("attachment {0} does not match its checksum")]
+    Checksum(usize),
+
+    #[error("failed to store attachment: {0}")]
+    Stream(#[from] StreamError),
+
+    #[error("failed to spool attachment: {0}")]
+    Spool(#[from] io::Error),
+
+    #[error("refusing to contact remote server: {0}")]
+    Host(#[from] NetError),
+
+    #[error("delivery failed: {0}")]
+    Delivery(#[from] DeliveryError),
+
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+// ##############
+// ## Outbound ##
+// ##############
+
+/// Tells the server of `recipient` to fetch the mail queued
+/// under `token` from `origin`, i.e, this server's `pub_addr`.
+///
+/// Unreachable servers are worth retrying, error responses
+/// are classified by `Outcome::from_status`.
+#[instrument(skip(token))]
+pub async fn push(origin: &str, recipient: &Address, token: &str) -> Attempt {
+    let host = recipient.host.as_deref().unwrap_or_default();
+
+    if let Err(e) = net::check(host).await {
+        return Attempt {
+            status_code: None,
+            outcome: match e {
+                NetError::Resolve(..) => Outcome::Retry(e.to_string()),
+                NetError::NotPublic(_) => Outcome::Fail(e.to_string()),
+            },
+        };
+    }
+
+    let result = CLIENT
+        .post(format!(
+            "http://{}{}",
+            host,
+            api::api_federation_deliver_absolute()
+        ))
+        .json(&DeliverPayload {
+            origin: origin.to_owned(),
+            token: token.to_owned(),
+        })
+        .send()
+        .await;
+
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => {
+            return Attempt {
+                status_code: None,
+                outcome: Outcome::Retry(format!("could not reach {host}: {e}")),
+            };
+        }
+    };
+
+    let status = response.status();
+    let mut error = format!("{host} responded with {status}");
+
+    // E.g, the reason a filter of the recipient rejected the mail for
+    if !status.is_success()
+        && let Ok(payload) = response.json::<ErrorPayload>().await
+    {
+        error = format!("{error}: {}", payload.error);
+    }
+
+    Attempt {
+        status_code: Some(status.as_u16()),
+        outcome: Outcome::from_status(status, error),
+    }
+}
+
+// #############
+// ## Inbound ##
+// #############
+
+/// Fetches the mail waiting on the server at `origin` under `token`.
+///
+/// # Errors
+///
+/// Returns `Err(Host)`         if `origin` is not a public host, see `net::check`.
+/// Returns `Err(Request)`      if `origin` could not be reached.
+/// Returns `Err(Remote)`       if `origin` responded with anything but a `200 OK`.
+/// Returns `Err(MailTooLarge)` if the response is longer than `MAX_MAIL_LEN`.
+/// Returns `Err(Malformed)`    if the response is not a `FederatedMail`.
+///
+pub async fn fetch(origin: &str, token: &str) -> Result<FederatedMail, FederationError> {
+    net::check(origin).await?;
+
+    let response = CLIENT
+        .get(format!(
+            "http://{}{}",
+            origin,
+            api::api_federation_outbound_token_absolute(token)
+        ))
+        .send()
+        .await?;
+
+    if response.status() != StatusCode::OK {
+        return Err(FederationError::Remote(response.status()));
+    }
+
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_MAIL_LEN as u64)
+    {
+        return Err(FederationError::MailTooLarge);
+    }
+
+    // Read the body up to the limit, since the `Content-Length` may be missing or wrong
+    let mut body = Vec::new();
+    let mut data = response.bytes_stream();
+
+    while let Some(chunk) = data.next().await {
+        let chunk = chunk?;
+
+        if body.len() + chunk.len() > MAX_MAIL_LEN {
+            return Err(FederationError::MailTooLarge);
+        }
+
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Fetches each of the `attachments` of `mail`, as fetched from `origin`
+/// under `token`, in order, then delivers `mail` into the inbox of its local
+/// recipient along with them, in a single transaction.
+///
+/// Attachments are spooled to temporary files while they are fetched, so
+/// no transaction is held open while waiting for the remote server and
+/// the mail never shows up in the inbox without all of its attachments.
+///
+/// `mail.sender` is expected to already be qualified with `origin`,
+/// and every field is expected to satisfy the constraints of the tables.
+///
+/// Returns the `Inbound` row owned by the recipient, to be announced, see
+/// `delivery::announce`.
+///
+/// # Errors
+///
+/// Returns `Err(TooLarge)` if an attachment is larger than `max_attachment_size`.
+/// Returns `Err(Checksum)` if an attachment does not match its `sha256`.
+/// Returns `Err`           if anything else fails, in which case nothing is kept.
+///
+#[instrument(skip(pool, token, mail, attachments), fields(sender = %mail.sender))]
+pub async fn receive(
+    pool: &SqlitePool,
+    origin: &str,
+    token: &str,
+    mail: &Mail,
+    attachments: &[FederatedAttachment],
+    max_attachment_size: u64,
+) -> Result<Inbound, FederationError> {
+    if let Some(index) = attachments
+        .iter()
+        .position(|attachment| attachment.size < 0 || attachment.size as u64 > max_attachment_size)
+    {
+        return Err(FederationError::TooLarge(index));
+    }
+
+    let size = attachments
+        .iter()
+        .map(|attachment| attachment.size as u64)
+        .sum();
+
+    let mut spools = Vec::with_capacity(attachments.len());
+    for (index, attachment) in attachments.iter().enumerate() {
+        spools.push(fetch_attachment(origin, token, index, attachment).await?);
+    }
+
+    let mut tx = pool.begin().await?;
+    let inbound = delivery::deliver_local(&mut tx, mail, size).await?;
+
+    for (attachment, spool) in attachments.iter().zip(&spools) {
+        store_attachment(&mut tx, inbound.id, attachment, spool).await?;
+    }
+
+    tx.commit().await?;
+
+    info!(id = inbound.id, "received from remote server");
+
+    Ok(inbound)
+}
+
+/// An attachment that was fetched into a temporary file,
+/// which is removed once this is dropped.
+struct Spool {
+    path: PathBuf,
+    sha256: String,
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!(err = ?e, path = ?self.path, "failed to remove spooled attachment");
+        }
+    }
+}
+
+/// Fetches the attachment at `index` of the mail waiting on
+/// the server at `origin` under `token` into a `Spool`.
+///
+/// # Errors
+///
+/// Returns `Err(Stream)`   if the data is not exactly `attachment.size` bytes long.
+/// Returns `Err(Checksum)` if the data does not match `attachment.sha256`.
+/// Returns `Err`           if `origin` could not be reached or the file could not be written.
+///
+async fn fetch_attachment(
+    origin: &str,
+    token: &str,
+    index: usize,
+    attachment: &FederatedAttachment,
+) -> Result<Spool, FederationError> {
+    let response = CLIENT
+        .get(format!(
+            "http://{}{}",
+            origin,
+            api::api_federation_outbound_token_attachments_index_absolute(token, index)
+        ))
+        .send()
+        .await?;
+
+    if response.status() != StatusCode::OK {
+        return Err(FederationError::Remote(response.status()));
+    }
+
+    let path = env::temp_dir().join(format!("nasomail-{}", Uuid::new_v4().simple()));
+    let mut file = File::create(&path).await?;
+
+    // Created before anything is written, so the file is removed on every error
+    let mut spool = Spool {
+        path,
+        sha256: String::new(),
+    };
+
+    let len = attachment.size as u64;
+    let mut written = 0u64;
+    let mut hasher = Sha256::new();
+    let mut data = response.bytes_stream();
+
+    while let Some(chunk) = data.next().await {
+        let chunk = chunk.map_err(|_| StreamError::Read)?;
+
+        written += chunk.len() as u64;
+        if written > len {
+            return Err(StreamError::Length(len).into());
+        }
+
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+
+    if written != len {
+        return Err(StreamError::Length(len).into());
+    }
+
+    file.flush().await?;
+    spool.sha256 = hex::encode(hasher.finalize());
+
+    if attachment
+        .sha256
+        .as_ref()
+        .is_some_and(|sha256| !sha256.eq_ignore_ascii_case(&spool.sha256))
+    {
+        return Err(FederationError::Checksum(index));
+    }
+
+    Ok(spool)
+}
+
+/// Stores the fetched `attachment` from `spool` as an attachment of `mail_id`,
+/// copying it in chunks of at most `blob::CHUNK_SIZE` bytes.
+async fn store_attachment(
+    conn: &mut SqliteConnection,
+    mail_id: i64,
+    attachment: &FederatedAttachment,
+    spool: &Spool,
+) -> Result<(), FederationError> {
+    let attachment_id: i64 = sqlx::query_scalar(
+        "INSERT INTO attachments (mail_id, data, filename, content_type, size, sha256)
+            VALUES (?, ZEROBLOB(?), ?, ?, ?, ?)
+            RETURNING id",
+    )
+    .bind(mail_id)
+    .bind(attachment.size)
+    .bind(&attachment.filename)
+    .bind(&attachment.content_type)
+    .bind(attachment.size)
+    .bind(&spool.sha256)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let mut file = File::open(&spool.path).await?;
+    let mut buf = vec![0u8; blob::CHUNK_SIZE];
+    let mut offset = 0;
+
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+
+        blob::write_at(conn, attachment_id, offset, &buf[..read])
+            .await
+            .map_err(StreamError::Blob)?;
+        offset += read;
+    }
+
+    Ok(())
+}
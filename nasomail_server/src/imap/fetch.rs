@@ -0,0 +1,524 @@
+//! The data items of `FETCH` and how they are produced from stored mails.
+//!
+//! Messages are rendered by `message::render` whenever their content is
+//! needed, which always yields the same bytes for the same mail, so sizes
+//! and sections stay consistent between requests. The MIME structure is
+//! taken from parsing the rendered message again.
+
+use mail_builder::headers::date::Date;
+use mail_parser::{Message, MessageParser, MimeHeaders, PartType};
+use sqlx::sqlite::SqlitePool;
+
+use crate::{
+    delivery::{Mail, MailRow},
+    imap::{
+        Error,
+        parse::{self, Token},
+    },
+    message,
+};
+
+/// A data item requested with `FETCH`.
+#[derive(Debug, Clone)]
+pub enum Item {
+    Uid,
+    Flags,
+    InternalDate,
+    Rfc822Size,
+    Envelope,
+
+    /// `BODY` without a section, i.e, the structure without extension data.
+    Body,
+    BodyStructure,
+
+    /// `BODY[...]`, `BODY.PEEK[...]` and the `RFC822` items.
+    Section {
+        /// The name of the item in the response, e.g, `BODY[1.MIME]`.
+        name: String,
+        part: Vec<usize>,
+        kind: Kind,
+        partial: Option<(usize, usize)>,
+        peek: bool,
+    },
+}
+
+/// An enum of the kinds of `BODY[...]` sections.
+#[derive(Debug, Clone)]
+pub enum Kind {
+    /// The whole (part of the) message, e.g, `BODY[]` or `BODY[1]`.
+    Whole,
+    Header,
+    Text,
+    Mime,
+
+    /// `HEADER.FIELDS` or, with `true`, `HEADER.FIELDS.NOT`.
+    Fields(Vec<String>, bool),
+}
+
+impl Item {
+    /// Returns whether the item needs the mail itself
+    /// rather than just its UID and flags.
+    pub fn needs_mail(&self) -> bool {
+        !matches!(self, Self::Uid | Self::Flags)
+    }
+
+    /// Returns whether fetching the item sets the `\Seen` flag.
+    pub fn sets_seen(&self) -> bool {
+        matches!(self, Self::Section { peek: false, .. })
+    }
+}
+
+/// Parses the data items of a `FETCH`, i.e, a single item,
+/// a parenthesized list of items or one of the macros.
+///
+/// Returns `None` if any item is unknown or malformed.
+///
+pub fn items(tokens: &[Token]) -> Option<Vec<Item>> {
+    let atoms: Vec<String> = match tokens {
+        [Token::Atom(atom)] => match atom.to_ascii_uppercase().as_str() {
+            "ALL" => {
+                return Some(vec![
+                    Item::Flags,
+                    Item::InternalDate,
+                    Item::Rfc822Size,
+                    Item::Envelope,
+                ]);
+            }
+            "FAST" => return Some(vec![Item::Flags, Item::InternalDate, Item::Rfc822Size]),
+            "FULL" => {
+                return Some(vec![
+                    Item::Flags,
+                    Item::InternalDate,
+                    Item::Rfc822Size,
+                    Item::Envelope,
+                    Item::Body,
+                ]);
+            }
+            _ => vec![atom.clone()],
+        },
+        [Token::List(list)] => list
+            .iter()
+            .map(|token| match token {
+                Token::Atom(atom) => Some(atom.clone()),
+                _ => None,
+            })
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+
+    atoms.iter().map(|atom| item(atom)).collect()
+}
+
+fn item(atom: &str) -> Option<Item> {
+    let upper = atom.to_ascii_uppercase();
+
+    let simple = match upper.as_str() {
+        "UID" => Some(Item::Uid),
+        "FLAGS" => Some(Item::Flags),
+        "INTERNALDATE" => Some(Item::InternalDate),
+        "RFC822.SIZE" => Some(Item::Rfc822Size),
+        "ENVELOPE" => Some(Item::Envelope),
+        "BODY" => Some(Item::Body),
+        "BODYSTRUCTURE" => Some(Item::BodyStructure),
+        "RFC822" => Some(section("RFC822", vec![], Kind::Whole, false)),
+        "RFC822.HEADER" => Some(section("RFC822.HEADER", vec![], Kind::Header, true)),
+        "RFC822.TEXT" => Some(section("RFC822.TEXT", vec![], Kind::Text, false)),
+        _ => None,
+    };
+
+    if simple.is_some() {
+        return simple;
+    }
+
+    let (peek, rest) = if let Some(rest) = upper.strip_prefix("BODY.PEEK[") {
+        (true, rest)
+    } else {
+        (false, upper.strip_prefix("BODY[")?)
+    };
+
+    let close = rest.rfind(']')?;
+    let (spec, partial) = (&rest[..close], &rest[close + 1..]);
+
+    let partial = if partial.is_empty() {
+        None
+    } else {
+        let (start, len) = partial
+            .strip_prefix('<')?
+            .strip_suffix('>')?
+            .split_once('.')?;
+        Some((start.parse().ok()?, len.parse().ok()?))
+    };
+
+    let (part, kind) = section_spec(spec)?;
+
+    let name = match partial {
+        Some((start, _)) => format!("BODY[{}]<{}>", spec, start),
+        None => format!("BODY[{}]", spec),
+    };
+
+    Some(Item::Section {
+        name,
+        part,
+        kind,
+        partial,
+        peek,
+    })
+}
+
+fn section(name: &str, part: Vec<usize>, kind: Kind, peek: bool) -> Item {
+    Item::Section {
+        name: name.to_owned(),
+        part,
+        kind,
+        partial: None,
+        peek,
+    }
+}
+
+/// Parses a section specification, e.g, `1.2.MIME` or `HEADER.FIELDS (FROM TO)`.
+fn section_spec(spec: &str) -> Option<(Vec<usize>, Kind)> {
+    let mut part = Vec::new();
+    let mut rest = spec;
+
+    while let Some(digits) = rest
+        .split(['.', ' '])
+        .next()
+        .filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))
+    {
+        part.push(digits.parse::<usize>().ok().filter(|&n| n > 0)?);
+        rest = rest[digits.len()..].strip_prefix('.').unwrap_or("");
+    }
+
+    let kind = match rest {
+        "" => Kind::Whole,
+        "HEADER" => Kind::Header,
+        "TEXT" => Kind::Text,
+        "MIME" if !part.is_empty() => Kind::Mime,
+        _ => {
+            let (not, fields) = if let Some(fields) = rest.strip_prefix("HEADER.FIELDS.NOT") {
+                (true, fields)
+            } else {
+                (false, rest.strip_prefix("HEADER.FIELDS")?)
+            };
+
+            let fields = fields.trim().strip_prefix('(')?.strip_suffix(')')?;
+            let fields = fields
+                .split_whitespace()
+                .map(|field| field.trim_matches('"').to_owned())
+                .collect();
+
+            Kind::Fields(fields, not)
+        }
+    };
+
+    Some((part, kind))
+}
+
+/// A stored mail along with its rendering, once it was needed.
+pub struct Stored {
+    id: i64,
+    mail: Mail,
+    created_at: String,
+    date: i64,
+    hostname: String,
+    raw: Option<Vec<u8>>,
+}
+
+impl Stored {
+    /// Loads the mail `id` owned by `user_id`.
+    ///
+    /// Returns `Ok(None)` if there is no such mail.
+    ///
+    pub async fn load(
+        pool: &SqlitePool,
+        user_id: i64,
+        id: i64,
+        hostname: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        #[derive(sqlx::FromRow)]
+        struct StoredRow {
+            #[sqlx(flatten)]
+            mail: MailRow,
+            created_at: String,
+            date: i64,
+        }
+
+        let row: Option<StoredRow> = sqlx::query_as(
+            "SELECT sender, recipient, subject, body, message_id, in_reply_to, refs, auto_submitted,
+                    created_at, UNIXEPOCH(created_at) AS date
+                FROM mails WHERE id = ? AND user_id = ?",
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|row| Self {
+            id,
+            mail: Mail::from(row.mail),
+            created_at: row.created_at,
+            date: row.date,
+            hostname: hostname.to_owned(),
+            raw: None,
+        }))
+    }
+
+    /// Renders the mail along with its attachments, once.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TooLarge)` if the attachments are too large to render,
+    ///                         see `message::attachments`.
+    /// Returns `Err(Database)` if the database could not be queried.
+    ///
+    async fn raw(&mut self, pool: &SqlitePool) -> Result<&[u8], Error> {
+        if self.raw.is_none() {
+            let attachments = message::attachments(pool, self.id)
+                .await?
+                .ok_or(Error::TooLarge)?;
+
+            let raw = message::render(&self.mail, self.date, &attachments, &self.hostname)
+                .unwrap_or_default();
+            self.raw = Some(raw);
+        }
+
+        Ok(self.raw.as_deref().unwrap_or_default())
+    }
+
+    /// Appends the value of the data item `item` to `out`,
+    /// for every item other than `UID` and `FLAGS`.
+    pub async fn write(
+        &mut self,
+        pool: &SqlitePool,
+        item: &Item,
+        out: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        match item {
+            Item::Uid | Item::Flags => {}
+            Item::InternalDate => {
+                out.extend(format!("INTERNALDATE {}", parse::date_time(&self.created_at)).bytes());
+            }
+            Item::Rfc822Size => {
+                let len = self.raw(pool).await?.len();
+                out.extend(format!("RFC822.SIZE {}", len).bytes());
+            }
+            Item::Envelope => {
+                out.extend(b"ENVELOPE ");
+                out.extend(self.envelope().bytes());
+            }
+            Item::Body | Item::BodyStructure => {
+                let extended = matches!(item, Item::BodyStructure);
+                let raw = self.raw(pool).await?;
+
+                let structure = MessageParser::default()
+                    .parse(raw)
+                    .map(|message| structure(&message, 0, extended))
+                    .unwrap_or_else(|| "(\"TEXT\" \"PLAIN\" NIL NIL NIL \"7BIT\" 0 0)".to_owned());
+
+                out.extend(if extended { "BODYSTRUCTURE " } else { "BODY " }.bytes());
+                out.extend(structure.bytes());
+            }
+            Item::Section {
+                name,
+                part,
+                kind,
+                partial,
+                ..
+            } => {
+                let raw = self.raw(pool).await?;
+                let data = MessageParser::default()
+                    .parse(raw)
+                    .and_then(|message| extract(&message, raw, part, kind))
+                    .unwrap_or_default();
+
+                let data = match partial {
+                    Some((start, len)) => {
+                        let start = (*start).min(data.len());
+                        &data[start..start.saturating_add(*len).min(data.len())]
+                    }
+                    None => &data[..],
+                };
+
+                out.extend(format!("{} {{{}}}\r\n", name, data.len()).bytes());
+                out.extend(data);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn envelope(&self) -> String {
+        let address = |address: &str| {
+            let address = message::qualify(address, &self.hostname);
+            let (mailbox, host) = address.split_once('@').unwrap_or((&address, ""));
+            format!(
+                "((NIL NIL {} {}))",
+                parse::string(mailbox),
+                parse::string(host)
+            )
+        };
+
+        let from = address(&self.mail.sender);
+        let subject = if self.mail.subject.is_empty() {
+            "NIL".to_owned()
+        } else {
+            parse::string(&self.mail.subject)
+        };
+
+        let in_reply_to = match &self.mail.in_reply_to {
+            Some(in_reply_to) => parse::string(&format!("<{in_reply_to}>")),
+            None => "NIL".to_owned(),
+        };
+
+        format!(
+            "({} {} {} {} {} {} NIL NIL {} {})",
+            parse::string(&Date::new(self.date).to_rfc822()),
+            subject,
+            from,
+            from,
+            from,
+            address(&self.mail.recipient),
+            in_reply_to,
+            parse::string(&format!("<{}>", self.mail.message_id)),
+        )
+    }
+}
+
+/// Returns the `BODYSTRUCTURE` (or, without `extended`, the `BODY`)
+/// of the part `id` of `message`.
+fn structure(message: &Message, id: usize, extended: bool) -> String {
+    let Some(part) = message.parts.get(id) else {
+        return "NIL".to_owned();
+    };
+
+    let content_type = part.content_type();
+    let ctype = content_type.map_or("text", |ct| ct.ctype());
+    let subtype = content_type.and_then(|ct| ct.subtype()).unwrap_or("plain");
+
+    let params = |attributes: Option<&[mail_parser::Attribute]>| match attributes {
+        Some(attributes) if !attributes.is_empty() => format!(
+            "({})",
+            attributes
+                .iter()
+                .map(|a| format!("{} {}", parse::string(&a.name), parse::string(&a.value)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+        _ => "NIL".to_owned(),
+    };
+
+    if let PartType::Multipart(children) = &part.body {
+        let children: String = children
+            .iter()
+            .map(|&child| structure(message, child as usize, extended))
+            .collect();
+
+        let mut structure = format!("({} {}", children, parse::string(subtype));
+        if extended {
+            structure.push_str(&format!(
+                " {} NIL NIL",
+                params(content_type.and_then(|ct| ct.attributes()))
+            ));
+        }
+        structure.push(')');
+
+        return structure;
+    }
+
+    let (start, end) = (part.offset_body as usize, part.offset_end as usize);
+    let body = message.raw_message.get(start..end).unwrap_or_default();
+
+    let mut structure = format!(
+        "({} {} {} NIL NIL {} {}",
+        parse::string(ctype),
+        parse::string(subtype),
+        params(content_type.and_then(|ct| ct.attributes())),
+        parse::string(part.content_transfer_encoding().unwrap_or("7bit")),
+        body.len()
+    );
+
+    if ctype.eq_ignore_ascii_case("text") {
+        let lines = body.iter().filter(|&&b| b == b'\n').count();
+        structure.push_str(&format!(" {}", lines));
+    }
+
+    if extended {
+        let disposition = match part.content_disposition() {
+            Some(disposition) => format!(
+                "({} {})",
+                parse::string(disposition.ctype()),
+                params(disposition.attributes())
+            ),
+            None => "NIL".to_owned(),
+        };
+        structure.push_str(&format!(" NIL {} NIL", disposition));
+    }
+
+    structure.push(')');
+    structure
+}
+
+/// Extracts the section `part` and `kind` of the rendered message `raw`.
+///
+/// Returns `None` if there is no such part.
+///
+fn extract(message: &Message, raw: &[u8], part: &[usize], kind: &Kind) -> Option<Vec<u8>> {
+    let mut id = 0;
+
+    for &number in part {
+        id = match &message.parts.get(id)?.body {
+            PartType::Multipart(children) => *children.get(number - 1)? as usize,
+            // A message that is not multipart has its body as its only part
+            _ if number == 1 && id == 0 => 0,
+            _ => return None,
+        };
+    }
+
+    let target = message.parts.get(id)?;
+    let (header, body, end) = (
+        target.offset_header as usize,
+        target.offset_body as usize,
+        target.offset_end as usize,
+    );
+
+    let header_block = || raw.get(if part.is_empty() { 0 } else { header }..body);
+
+    Some(match kind {
+        Kind::Whole if part.is_empty() => raw.to_vec(),
+        Kind::Whole => raw.get(body..end)?.to_vec(),
+        Kind::Text if part.is_empty() => raw.get(body..)?.to_vec(),
+        Kind::Text => raw.get(body..end)?.to_vec(),
+        Kind::Header | Kind::Mime => header_block()?.to_vec(),
+        Kind::Fields(fields, not) => filter_fields(header_block()?, fields, *not),
+    })
+}
+
+/// Keeps only the header fields named in `fields`
+/// (or, with `not`, every other one) of `header`.
+fn filter_fields(header: &[u8], fields: &[String], not: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut keep = false;
+
+    for line in header.split_inclusive(|&b| b == b'\n') {
+        if line == b"\r\n" || line == b"\n" {
+            break;
+        }
+
+        if !line.starts_with(b" ") && !line.starts_with(b"\t") {
+            let name = line
+                .split(|&b| b == b':')
+                .next()
+                .map(|name| String::from_utf8_lossy(name).trim().to_owned())
+                .unwrap_or_default();
+
+            keep = fields.iter().any(|field| field.eq_ignore_ascii_case(&name)) != not;
+        }
+
+        if keep {
+            out.extend_from_slice(line);
+        }
+    }
+
+    out.extend_from_slice(b"\r\n");
+    out
+}
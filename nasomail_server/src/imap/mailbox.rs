@@ -0,0 +1,176 @@
+//! The mapping of the `status` of mails onto IMAP mailboxes.
+
+use std::collections::{HashMap, HashSet};
+
+use sqlx::sqlite::SqlitePool;
+
+/// An enum of the mailboxes every user has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mailbox {
+    /// Received mails, i.e, `new` and `read` ones.
+    Inbox,
+    Sent,
+    Drafts,
+
+    /// Mails of any `status` that were moved to the trash.
+    Trash,
+}
+
+impl Mailbox {
+    pub const ALL: [Self; 4] = [Self::Inbox, Self::Sent, Self::Drafts, Self::Trash];
+
+    /// Looks up a mailbox by `name`, which
+    /// is case-insensitive for `INBOX` only.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mailbox| {
+            if *mailbox == Self::Inbox {
+                name.eq_ignore_ascii_case(mailbox.name())
+            } else {
+                name == mailbox.name()
+            }
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Inbox => "INBOX",
+            Self::Sent => "Sent",
+            Self::Drafts => "Drafts",
+            Self::Trash => "Trash",
+        }
+    }
+
+    /// Returns the attributes of the mailbox in a `LIST` response,
+    /// with the special-use attributes of RFC 6154.
+    pub fn attributes(&self) -> &'static str {
+        match self {
+            Self::Inbox => "\\HasNoChildren",
+            Self::Sent => "\\HasNoChildren \\Sent",
+            Self::Drafts => "\\HasNoChildren \\Drafts",
+            Self::Trash => "\\HasNoChildren \\Trash",
+        }
+    }
+
+    /// Returns the condition on `mails.status` and `mails.deleted_at` of the mailbox.
+    pub fn condition(&self) -> &'static str {
+        match self {
+            Self::Inbox => "status IN ('new', 'read') AND deleted_at IS NULL",
+            Self::Sent => "status = 'sent' AND deleted_at IS NULL",
+            Self::Drafts => "status = 'draft' AND deleted_at IS NULL",
+            Self::Trash => "deleted_at IS NOT NULL",
+        }
+    }
+
+    /// Returns whether flag changes of the mailbox are stored, which
+    /// is only the case for the `\Seen` flag of the `INBOX`.
+    pub fn is_writable(&self) -> bool {
+        *self == Self::Inbox
+    }
+}
+
+/// A message of a mailbox as last reported to the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    /// The `id` of the mail, which is not its UID, see `mails.uid`.
+    pub id: i64,
+    pub uid: u32,
+    pub seen: bool,
+}
+
+/// Returns the flags of a message of `mailbox` as an IMAP flag list.
+pub fn flags(mailbox: Mailbox, seen: bool) -> &'static str {
+    match (mailbox, seen) {
+        (Mailbox::Drafts, _) => "(\\Seen \\Draft)",
+        (_, true) => "(\\Seen)",
+        (_, false) => "()",
+    }
+}
+
+/// Loads the messages of `mailbox` owned by `user_id`, by ascending UID.
+pub async fn entries(
+    pool: &SqlitePool,
+    user_id: i64,
+    mailbox: Mailbox,
+) -> Result<Vec<Entry>, sqlx::Error> {
+    let rows: Vec<(i64, i64, String)> = sqlx::query_as(&format!(
+        "SELECT id, uid, status FROM mails WHERE user_id = ? AND {} ORDER BY uid",
+        mailbox.condition()
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, uid, status)| Entry {
+            id,
+            uid: uid as u32,
+            seen: status != "new",
+        })
+        .collect())
+}
+
+/// Returns the `UIDNEXT` shared by every mailbox, since UIDs
+/// are handed out from a single counter and never reused.
+pub async fn uid_next(pool: &SqlitePool) -> Result<u32, sqlx::Error> {
+    let next: i64 = sqlx::query_scalar("SELECT next FROM imap_uids")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(next as u32)
+}
+
+/// Returns the `UIDVALIDITY` of the mailboxes of `user_id`, which is
+/// when the user registered, so it changes if the name is ever reused.
+pub async fn uid_validity(pool: &SqlitePool, user_id: i64) -> Result<u32, sqlx::Error> {
+    let created: i64 = sqlx::query_scalar("SELECT UNIXEPOCH(created_at) FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(created.max(1) as u32)
+}
+
+/// The changes between two snapshots of a mailbox, as untagged responses.
+///
+/// `EXPUNGE`s are listed first, by descending sequence number, so every
+/// one of them is still valid when the client gets to it, then the
+/// `EXISTS` and `FETCH` responses for what is left.
+pub fn diff(mailbox: Mailbox, old: &[Entry], new: &[Entry], with_uid: bool) -> Vec<String> {
+    let old_seen: HashMap<u32, bool> = old.iter().map(|e| (e.uid, e.seen)).collect();
+    let new_uids: HashSet<u32> = new.iter().map(|e| e.uid).collect();
+
+    let mut responses = Vec::new();
+
+    for (index, entry) in old.iter().enumerate().rev() {
+        if !new_uids.contains(&entry.uid) {
+            responses.push(format!("* {} EXPUNGE\r\n", index + 1));
+        }
+    }
+
+    if new.iter().any(|entry| !old_seen.contains_key(&entry.uid)) {
+        responses.push(format!("* {} EXISTS\r\n", new.len()));
+    }
+
+    for (index, entry) in new.iter().enumerate() {
+        if old_seen
+            .get(&entry.uid)
+            .is_some_and(|&seen| seen != entry.seen)
+        {
+            let uid = if with_uid {
+                format!("UID {} ", entry.uid)
+            } else {
+                String::new()
+            };
+
+            responses.push(format!(
+                "* {} FETCH ({}FLAGS {})\r\n",
+                index + 1,
+                uid,
+                flags(mailbox, entry.seen)
+            ));
+        }
+    }
+
+    responses
+}
@@ -0,0 +1,850 @@
+//! An optional IMAP4rev1 listener (RFC 3501) for reading mail with
+//! regular mail clients.
+//!
+//! Users log in with their name and passphrase and see the `status` of
+//! their mails as four mailboxes: `INBOX` (`new` and `read`), `Sent`,
+//! `Drafts` and `Trash`. The `\Seen` flag of the `INBOX` maps onto `read`
+//! and is the only change that is stored. Mailboxes cannot be created,
+//! and nothing can be appended, copied or expunged.
+//!
+//! UIDs are kept apart from the `id`s of mails in `mails.uid`, which is
+//! replaced whenever a mail moves into another mailbox, e.g, when a draft
+//! is sent, so every mail showing up in a mailbox gets a higher UID than
+//! the ones already there, as clients expect.
+
+mod fetch;
+mod mailbox;
+mod parse;
+mod search;
+
+use std::io;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use sqlx::sqlite::SqlitePool;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        TcpListener, TcpStream,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    sync::broadcast::error::RecvError,
+    time::{self, Duration},
+};
+use tracing::{info, instrument, warn};
+
+use nasomail_shared::{
+    address,
+    payload::{event::EventPayload, mail::MailStatus},
+    query::user::UserQuery,
+};
+
+use crate::{
+    app::AppContextGuard,
+    config::Argon2Config,
+    events::Events,
+    imap::{
+        fetch::{Item, Stored},
+        mailbox::{Entry, Mailbox},
+        parse::{SequenceSet, Token},
+        search::Query,
+    },
+    passphrase,
+};
+
+const CAPABILITY: &str =
+    "IMAP4rev1 LITERAL+ SASL-IR AUTH=PLAIN IDLE NAMESPACE UNSELECT SPECIAL-USE";
+
+/// The longest command accepted, including its literals.
+const MAX_COMMAND_LEN: usize = 1024 * 64;
+
+/// How long a client may stay silent, including while idling,
+/// before the connection is closed (see RFC 3501, section 5.4).
+const TIMEOUT: Duration = Duration::from_secs(60 * 30);
+
+/// How often mailboxes are checked for changes while idling, besides
+/// whenever an event is published for the user, since not every change is.
+const IDLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Accepts connections on `listener` until the process exits,
+/// handling each session in a task of its own.
+pub async fn serve(listener: TcpListener, app: AppContextGuard) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!(err = ?e, "failed to accept imap connection");
+                continue;
+            }
+        };
+
+        let app = app.clone();
+        tokio::spawn(async move {
+            if let Err(e) = session(stream, app).await {
+                info!(peer = %peer, err = %e, "imap session ended");
+            }
+        });
+    }
+}
+
+/// A custom error type for IMAP sessions.
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    /// The connection failed, which ends the session.
+    #[error("connection error: {0}")]
+    Io(#[from] io::Error),
+
+    /// A command failed, which the client is told about.
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    /// A message is too large to render, see `message::attachments`.
+    #[error("message too large")]
+    TooLarge,
+}
+
+/// The mailbox selected in a session.
+struct Selected {
+    mailbox: Mailbox,
+    read_only: bool,
+
+    /// The messages as last reported to the client, by sequence number.
+    entries: Vec<Entry>,
+}
+
+impl Selected {
+    /// Returns whether flag changes are stored.
+    fn is_writable(&self) -> bool {
+        !self.read_only && self.mailbox.is_writable()
+    }
+
+    /// Returns the indices into `entries` matching `set`,
+    /// which holds UIDs with `uid` and sequence numbers otherwise.
+    fn resolve(&self, set: &SequenceSet, uid: bool) -> Vec<usize> {
+        let max = if uid {
+            self.entries.last().map_or(0, |entry| entry.uid)
+        } else {
+            self.entries.len() as u32
+        };
+
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(index, entry)| {
+                let n = if uid { entry.uid } else { *index as u32 + 1 };
+                set.contains(n, max)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+struct Session {
+    pool: SqlitePool,
+    events: Events,
+    argon2: Argon2Config,
+    hostname: String,
+
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+
+    /// The `id` of the authenticated user.
+    user_id: Option<i64>,
+    selected: Option<Selected>,
+}
+
+/// What to do after a command.
+enum Flow {
+    Continue,
+    Logout,
+}
+
+#[instrument(skip_all, fields(peer = ?stream.peer_addr().ok()))]
+async fn session(stream: TcpStream, app: AppContextGuard) -> Result<(), Error> {
+    // Clone what is needed so the context is not held for the whole session
+    let (pool, events, argon2, pub_addr) = {
+        let ctx = app.ctx().await;
+        let cfg = ctx.cfg().await;
+
+        (
+            ctx.pool().await.clone(),
+            ctx.events().clone(),
+            cfg.argon2().await.clone(),
+            cfg.pub_addr().await.clone(),
+        )
+    };
+
+    let (reader, writer) = stream.into_split();
+
+    let mut session = Session {
+        pool,
+        events,
+        argon2,
+        hostname: address::host_name(&pub_addr).to_owned(),
+        reader: BufReader::new(reader),
+        writer,
+        user_id: None,
+        selected: None,
+    };
+
+    session
+        .write(&format!(
+            "* OK [CAPABILITY {}] NasoMail ready\r\n",
+            CAPABILITY
+        ))
+        .await?;
+
+    loop {
+        let Some(command) = session.read_command().await? else {
+            session.write("* BYE command too long\r\n").await?;
+            return Ok(());
+        };
+
+        let tokens = parse::tokenize(&command);
+        let (tag, name, args) = match tokens.as_deref() {
+            Some([Token::Atom(tag), Token::Atom(name), args @ ..]) => {
+                (tag.clone(), name.to_ascii_uppercase(), args.to_vec())
+            }
+            Some([Token::Atom(tag), ..]) => {
+                session
+                    .write(&format!("{} BAD missing command\r\n", tag))
+                    .await?;
+                continue;
+            }
+            _ => {
+                session.write("* BAD malformed command\r\n").await?;
+                continue;
+            }
+        };
+
+        match session.execute(&tag, &name, &args).await {
+            Ok(Flow::Continue) => {}
+            Ok(Flow::Logout) => return Ok(()),
+            Err(Error::Io(e)) => return Err(Error::Io(e)),
+            Err(Error::Database(e)) => {
+                warn!(err = ?e, command = %name, "imap command failed");
+                session
+                    .write(&format!("{} NO [SERVERBUG] internal error\r\n", tag))
+                    .await?;
+            }
+            Err(Error::TooLarge) => {
+                session
+                    .write(&format!("{} NO [LIMIT] message too large\r\n", tag))
+                    .await?;
+            }
+        }
+    }
+}
+
+impl Session {
+    async fn write(&mut self, data: &str) -> io::Result<()> {
+        self.writer.write_all(data.as_bytes()).await
+    }
+
+    /// Reads a line, appending it to `buf`, which is kept
+    /// intact when the future is dropped halfway.
+    async fn read_line(&mut self, buf: &mut Vec<u8>) -> io::Result<()> {
+        let limit = (MAX_COMMAND_LEN + 1).saturating_sub(buf.len()) as u64;
+
+        let read = time::timeout(
+            TIMEOUT,
+            (&mut self.reader).take(limit).read_until(b'\n', buf),
+        )
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "client timed out"))??;
+
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        Ok(())
+    }
+
+    /// Reads a command along with its literals.
+    ///
+    /// Returns `Ok(None)` if it is longer than `MAX_COMMAND_LEN`.
+    ///
+    async fn read_command(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let mut command = Vec::new();
+
+        loop {
+            let start = command.len();
+            self.read_line(&mut command).await?;
+
+            if !command.ends_with(b"\n") {
+                return Ok(None);
+            }
+
+            let Some((len, non_sync)) = parse::literal_len(&command[start..]) else {
+                return Ok(Some(command));
+            };
+
+            if len > MAX_COMMAND_LEN.saturating_sub(command.len()) {
+                return Ok(None);
+            }
+
+            if !non_sync {
+                self.write("+ Ready for literal data\r\n").await?;
+            }
+
+            let mut literal = vec![0; len];
+            time::timeout(TIMEOUT, self.reader.read_exact(&mut literal))
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "client timed out"))??;
+            command.extend(literal);
+        }
+    }
+
+    async fn execute(&mut self, tag: &str, name: &str, args: &[Token]) -> Result<Flow, Error> {
+        let authenticated = self.user_id.is_some();
+        let selected = self.selected.is_some();
+
+        let (name, uid) = match name {
+            "UID" => match args.first().and_then(|token| token.as_str()) {
+                Some(name) => (name.to_ascii_uppercase(), true),
+                None => {
+                    self.write(&format!("{} BAD missing command\r\n", tag))
+                        .await?;
+                    return Ok(Flow::Continue);
+                }
+            },
+            _ => (name.to_owned(), false),
+        };
+        let args = if uid { &args[1..] } else { args };
+
+        let status = match name.as_str() {
+            "CAPABILITY" => {
+                self.write(&format!("* CAPABILITY {}\r\n", CAPABILITY))
+                    .await?;
+                "OK CAPABILITY completed".to_owned()
+            }
+            "NOOP" | "CHECK" => {
+                self.sync().await?;
+                format!("OK {} completed", name)
+            }
+            "LOGOUT" => {
+                self.write("* BYE logging out\r\n").await?;
+                self.write(&format!("{} OK LOGOUT completed\r\n", tag))
+                    .await?;
+                return Ok(Flow::Logout);
+            }
+            "STARTTLS" => "NO STARTTLS is not supported".to_owned(),
+            "LOGIN" | "AUTHENTICATE" if authenticated => "BAD already authenticated".to_owned(),
+            "LOGIN" => self.login(args).await?,
+            "AUTHENTICATE" => self.authenticate(args).await?,
+            _ if !authenticated => "BAD log in first".to_owned(),
+            "SELECT" | "EXAMINE" => self.select(args, name == "EXAMINE").await?,
+            "CREATE" | "DELETE" | "RENAME" | "APPEND" => {
+                "NO [CANNOT] mailboxes are read-only".to_owned()
+            }
+            "SUBSCRIBE" | "UNSUBSCRIBE" => format!("OK {} completed", name),
+            "LIST" | "LSUB" => self.list(&name, args).await?,
+            "NAMESPACE" => {
+                self.write("* NAMESPACE ((\"\" \"/\")) NIL NIL\r\n").await?;
+                "OK NAMESPACE completed".to_owned()
+            }
+            "STATUS" => self.status(args).await?,
+            "IDLE" => self.idle().await?,
+            _ if !selected => "BAD select a mailbox first".to_owned(),
+            "CLOSE" | "UNSELECT" => {
+                self.selected = None;
+                format!("OK {} completed", name)
+            }
+            "EXPUNGE" => "OK EXPUNGE completed".to_owned(),
+            "COPY" | "MOVE" => "NO [CANNOT] mailboxes are read-only".to_owned(),
+            "SEARCH" => self.search(args, uid).await?,
+            "FETCH" => self.fetch(args, uid).await?,
+            "STORE" => self.store(args, uid).await?,
+            _ => "BAD unknown command".to_owned(),
+        };
+
+        self.write(&format!("{} {}\r\n", tag, status)).await?;
+
+        Ok(Flow::Continue)
+    }
+
+    /// Reports the changes to the selected mailbox since it was last reported.
+    async fn sync(&mut self) -> Result<(), Error> {
+        let (Some(user_id), Some(selected)) = (self.user_id, &mut self.selected) else {
+            return Ok(());
+        };
+
+        let entries = mailbox::entries(&self.pool, user_id, selected.mailbox).await?;
+        let responses = mailbox::diff(selected.mailbox, &selected.entries, &entries, false);
+        selected.entries = entries;
+
+        for response in responses {
+            self.write(&response).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn verify(&mut self, name: String, passphrase: String) -> Result<String, Error> {
+        let query = UserQuery::ByName { name };
+
+        match passphrase::authenticate(&self.pool, self.argon2.clone(), query, passphrase).await {
+            Ok(Some(user_id)) => {
+                self.user_id = Some(user_id);
+                Ok(format!("OK [CAPABILITY {}] logged in", CAPABILITY))
+            }
+            Ok(None) => Ok("NO [AUTHENTICATIONFAILED] invalid credentials".to_owned()),
+            Err(e) => {
+                warn!(err = ?e, "failed to authenticate");
+                Ok("NO [UNAVAILABLE] authentication is unavailable".to_owned())
+            }
+        }
+    }
+
+    async fn login(&mut self, args: &[Token]) -> Result<String, Error> {
+        let (Some(name), Some(passphrase)) = (
+            args.first().and_then(|token| token.as_str()),
+            args.get(1).and_then(|token| token.as_str()),
+        ) else {
+            return Ok("BAD expected a name and a passphrase".to_owned());
+        };
+
+        self.verify(name, passphrase).await
+    }
+
+    async fn authenticate(&mut self, args: &[Token]) -> Result<String, Error> {
+        if !args
+            .first()
+            .and_then(|token| token.as_str())
+            .is_some_and(|mechanism| mechanism.eq_ignore_ascii_case("PLAIN"))
+        {
+            return Ok("NO unsupported authentication mechanism".to_owned());
+        }
+
+        let response = match args.get(1).and_then(|token| token.as_str()) {
+            Some(initial) => initial,
+            None => {
+                self.write("+ \r\n").await?;
+
+                let mut line = Vec::new();
+                self.read_line(&mut line).await?;
+                String::from_utf8_lossy(&line).trim().to_owned()
+            }
+        };
+
+        if response == "*" {
+            return Ok("BAD authentication cancelled".to_owned());
+        }
+
+        let Ok(decoded) = BASE64.decode(if response == "=" { "" } else { &response }) else {
+            return Ok("BAD invalid base64".to_owned());
+        };
+
+        // `authzid NUL authcid NUL passwd`, where `authzid` must be empty or `authcid`
+        let decoded = String::from_utf8_lossy(&decoded);
+        let mut fields = decoded.split('\0');
+
+        match (fields.next(), fields.next(), fields.next(), fields.next()) {
+            (Some(authzid), Some(authcid), Some(passwd), None)
+                if authzid.is_empty() || authzid.eq_ignore_ascii_case(authcid) =>
+            {
+                self.verify(authcid.to_owned(), passwd.to_owned()).await
+            }
+            _ => Ok("NO [AUTHENTICATIONFAILED] invalid credentials".to_owned()),
+        }
+    }
+
+    async fn select(&mut self, args: &[Token], read_only: bool) -> Result<String, Error> {
+        self.selected = None;
+
+        let user_id = self.user_id.unwrap_or_default();
+        let Some(mailbox) = args
+            .first()
+            .and_then(|token| token.as_str())
+            .and_then(|name| Mailbox::from_name(&name))
+        else {
+            return Ok("NO [NONEXISTENT] no such mailbox".to_owned());
+        };
+
+        let entries = mailbox::entries(&self.pool, user_id, mailbox).await?;
+        let uid_validity = mailbox::uid_validity(&self.pool, user_id).await?;
+        let uid_next = mailbox::uid_next(&self.pool).await?;
+
+        let read_only = read_only || !mailbox.is_writable();
+        let permanent = if read_only { "()" } else { "(\\Seen)" };
+
+        let mut response = format!(
+            "* FLAGS (\\Seen \\Draft)\r\n\
+             * {} EXISTS\r\n\
+             * 0 RECENT\r\n\
+             * OK [PERMANENTFLAGS {}] flags\r\n\
+             * OK [UIDVALIDITY {}] UIDs valid\r\n\
+             * OK [UIDNEXT {}] predicted next UID\r\n",
+            entries.len(),
+            permanent,
+            uid_validity,
+            uid_next
+        );
+
+        if let Some(index) = entries.iter().position(|entry| !entry.seen) {
+            response.push_str(&format!("* OK [UNSEEN {}] first unseen\r\n", index + 1));
+        }
+
+        self.write(&response).await?;
+
+        self.selected = Some(Selected {
+            mailbox,
+            read_only,
+            entries,
+        });
+
+        Ok(if read_only {
+            "OK [READ-ONLY] EXAMINE completed".to_owned()
+        } else {
+            "OK [READ-WRITE] SELECT completed".to_owned()
+        })
+    }
+
+    async fn list(&mut self, name: &str, args: &[Token]) -> Result<String, Error> {
+        let (Some(reference), Some(pattern)) = (
+            args.first().and_then(|token| token.as_str()),
+            args.get(1).and_then(|token| token.as_str()),
+        ) else {
+            return Ok("BAD expected a reference and a pattern".to_owned());
+        };
+
+        if pattern.is_empty() {
+            self.write(&format!("* {} (\\Noselect) \"/\" \"\"\r\n", name))
+                .await?;
+        } else {
+            let pattern = format!("{}{}", reference, pattern);
+
+            for mailbox in Mailbox::ALL {
+                if parse::matches(&pattern, mailbox.name()) {
+                    self.write(&format!(
+                        "* {} ({}) \"/\" {}\r\n",
+                        name,
+                        mailbox.attributes(),
+                        parse::string(mailbox.name())
+                    ))
+                    .await?;
+                }
+            }
+        }
+
+        Ok(format!("OK {} completed", name))
+    }
+
+    async fn status(&mut self, args: &[Token]) -> Result<String, Error> {
+        let user_id = self.user_id.unwrap_or_default();
+
+        let Some(mailbox) = args
+            .first()
+            .and_then(|token| token.as_str())
+            .and_then(|name| Mailbox::from_name(&name))
+        else {
+            return Ok("NO [NONEXISTENT] no such mailbox".to_owned());
+        };
+
+        let Some(Token::List(items)) = args.get(1) else {
+            return Ok("BAD expected a list of status items".to_owned());
+        };
+
+        let entries = mailbox::entries(&self.pool, user_id, mailbox).await?;
+
+        let mut values = Vec::new();
+        for item in items {
+            let item = item.as_str().unwrap_or_default().to_ascii_uppercase();
+
+            let value = match item.as_str() {
+                "MESSAGES" => entries.len() as u32,
+                "RECENT" => 0,
+                "UIDNEXT" => mailbox::uid_next(&self.pool).await?,
+                "UIDVALIDITY" => mailbox::uid_validity(&self.pool, user_id).await?,
+                "UNSEEN" => entries.iter().filter(|entry| !entry.seen).count() as u32,
+                _ => return Ok("BAD unknown status item".to_owned()),
+            };
+
+            values.push(format!("{} {}", item, value));
+        }
+
+        self.write(&format!(
+            "* STATUS {} ({})\r\n",
+            parse::string(mailbox.name()),
+            values.join(" ")
+        ))
+        .await?;
+
+        Ok("OK STATUS completed".to_owned())
+    }
+
+    async fn idle(&mut self) -> Result<String, Error> {
+        self.write("+ idling\r\n").await?;
+
+        let user_id = self.user_id.unwrap_or_default();
+        let mut receiver = self.events.subscribe(None).receiver;
+
+        let mut line = Vec::new();
+        let deadline = time::Instant::now() + TIMEOUT;
+
+        loop {
+            // Only read up to the limit, so a line without an end cannot fill up memory
+            let limit = (MAX_COMMAND_LEN + 1).saturating_sub(line.len()) as u64;
+            let mut reader = (&mut self.reader).take(limit);
+
+            let changed = tokio::select! {
+                result = reader.read_until(b'\n', &mut line) => {
+                    if result? == 0 {
+                        return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+                    }
+
+                    if line.ends_with(b"\n") || line.len() > MAX_COMMAND_LEN {
+                        break;
+                    }
+
+                    false
+                }
+                event = receiver.recv() => match event {
+                    Ok(event) => event.user_id == user_id,
+                    Err(RecvError::Lagged(_)) => true,
+                    Err(RecvError::Closed) => false,
+                },
+                _ = time::sleep(IDLE_INTERVAL) => true,
+                _ = time::sleep_until(deadline) => {
+                    return Err(Error::Io(io::Error::new(io::ErrorKind::TimedOut, "client timed out")));
+                }
+            };
+
+            if changed {
+                self.sync().await?;
+            }
+        }
+
+        Ok(
+            if String::from_utf8_lossy(&line)
+                .trim()
+                .eq_ignore_ascii_case("DONE")
+            {
+                "OK IDLE terminated".to_owned()
+            } else {
+                "BAD expected DONE".to_owned()
+            },
+        )
+    }
+
+    async fn search(&mut self, args: &[Token], uid: bool) -> Result<String, Error> {
+        let Some(query) = Query::parse(args) else {
+            return Ok("BAD unsupported or malformed search".to_owned());
+        };
+
+        let user_id = self.user_id.unwrap_or_default();
+        let Some(selected) = &self.selected else {
+            return Ok("BAD select a mailbox first".to_owned());
+        };
+
+        let indices = query
+            .run(&self.pool, user_id, selected.mailbox, &selected.entries)
+            .await?;
+
+        let mut response = "* SEARCH".to_owned();
+        for index in indices {
+            let n = if uid {
+                selected.entries[index].uid
+            } else {
+                index as u32 + 1
+            };
+            response.push_str(&format!(" {}", n));
+        }
+        response.push_str("\r\n");
+
+        self.write(&response).await?;
+
+        Ok(format!(
+            "OK {}SEARCH completed",
+            if uid { "UID " } else { "" }
+        ))
+    }
+
+    async fn fetch(&mut self, args: &[Token], uid: bool) -> Result<String, Error> {
+        let (Some(set), Some(mut items)) = (
+            args.first()
+                .and_then(|token| token.as_str())
+                .and_then(|set| SequenceSet::parse(&set)),
+            args.get(1..).and_then(fetch::items),
+        ) else {
+            return Ok("BAD malformed FETCH".to_owned());
+        };
+
+        if uid && !items.iter().any(|item| matches!(item, Item::Uid)) {
+            items.insert(0, Item::Uid);
+        }
+
+        let user_id = self.user_id.unwrap_or_default();
+        let Some(selected) = &mut self.selected else {
+            return Ok("BAD select a mailbox first".to_owned());
+        };
+
+        let sets_seen = selected.is_writable() && items.iter().any(Item::sets_seen);
+        let needs_mail = items.iter().any(Item::needs_mail);
+
+        for index in selected.resolve(&set, uid) {
+            let entry = selected.entries[index];
+
+            let mut stored = None;
+            if needs_mail {
+                stored = Stored::load(&self.pool, user_id, entry.id, &self.hostname).await?;
+                if stored.is_none() {
+                    // Gone since the last sync, which is reported on the next one
+                    continue;
+                }
+            }
+
+            let mut flags_changed = false;
+            if sets_seen && !entry.seen {
+                let updated =
+                    sqlx::query("UPDATE mails SET status = 'read' WHERE id = ? AND status = 'new'")
+                        .bind(entry.id)
+                        .execute(&self.pool)
+                        .await?;
+
+                if updated.rows_affected() > 0 {
+                    self.events.publish(
+                        user_id,
+                        EventPayload::StatusChanged {
+                            id: entry.id,
+                            status: MailStatus::Read,
+                        },
+                    );
+                }
+
+                selected.entries[index].seen = true;
+                flags_changed = true;
+            }
+
+            let seen = selected.entries[index].seen;
+            let mut response = format!("* {} FETCH (", index + 1).into_bytes();
+            let mut first = true;
+
+            let flags_requested = items.iter().any(|item| matches!(item, Item::Flags));
+
+            for item in &items {
+                if !first {
+                    response.push(b' ');
+                }
+                first = false;
+
+                match item {
+                    Item::Uid => response.extend(format!("UID {}", entry.uid).bytes()),
+                    Item::Flags => response.extend(
+                        format!("FLAGS {}", mailbox::flags(selected.mailbox, seen)).bytes(),
+                    ),
+                    _ => {
+                        if let Some(stored) = &mut stored {
+                            stored.write(&self.pool, item, &mut response).await?;
+                        }
+                    }
+                }
+            }
+
+            if flags_changed && !flags_requested {
+                response
+                    .extend(format!(" FLAGS {}", mailbox::flags(selected.mailbox, seen)).bytes());
+            }
+
+            response.extend(b")\r\n");
+            self.writer.write_all(&response).await?;
+        }
+
+        Ok(format!(
+            "OK {}FETCH completed",
+            if uid { "UID " } else { "" }
+        ))
+    }
+
+    async fn store(&mut self, args: &[Token], uid: bool) -> Result<String, Error> {
+        let (Some(set), Some(action), Some(flags)) = (
+            args.first()
+                .and_then(|token| token.as_str())
+                .and_then(|set| SequenceSet::parse(&set)),
+            args.get(1).and_then(|token| token.as_str()),
+            args.get(2..).filter(|flags| !flags.is_empty()),
+        ) else {
+            return Ok("BAD malformed STORE".to_owned());
+        };
+
+        let flags: Vec<String> = match flags {
+            [Token::List(list)] => list.iter().filter_map(Token::as_str).collect(),
+            _ => flags.iter().filter_map(Token::as_str).collect(),
+        };
+        let has_seen = flags.iter().any(|flag| flag.eq_ignore_ascii_case("\\Seen"));
+
+        let action = action.to_ascii_uppercase();
+        let (action, silent) = match action.strip_suffix(".SILENT") {
+            Some(action) => (action, true),
+            None => (action.as_str(), false),
+        };
+
+        let user_id = self.user_id.unwrap_or_default();
+        let Some(selected) = &mut self.selected else {
+            return Ok("BAD select a mailbox first".to_owned());
+        };
+
+        if selected.read_only {
+            return Ok("NO [READ-ONLY] mailbox is read-only".to_owned());
+        }
+
+        for index in selected.resolve(&set, uid) {
+            let entry = selected.entries[index];
+
+            let seen = match action {
+                "FLAGS" => has_seen,
+                "+FLAGS" => entry.seen || has_seen,
+                "-FLAGS" => entry.seen && !has_seen,
+                _ => return Ok("BAD unknown STORE action".to_owned()),
+            };
+
+            // Only the `\Seen` flag of the `INBOX` is stored
+            if selected.mailbox.is_writable() && seen != entry.seen {
+                let status = if seen {
+                    MailStatus::Read
+                } else {
+                    MailStatus::New
+                };
+
+                let updated = sqlx::query(
+                    "UPDATE mails SET status = ? WHERE id = ? AND status IN ('new', 'read')",
+                )
+                .bind(status.as_str())
+                .bind(entry.id)
+                .execute(&self.pool)
+                .await?;
+
+                if updated.rows_affected() > 0 {
+                    self.events.publish(
+                        user_id,
+                        EventPayload::StatusChanged {
+                            id: entry.id,
+                            status,
+                        },
+                    );
+                }
+
+                selected.entries[index].seen = seen;
+            }
+
+            if !silent {
+                let uid = if uid {
+                    format!("UID {} ", entry.uid)
+                } else {
+                    String::new()
+                };
+
+                let flags = mailbox::flags(selected.mailbox, selected.entries[index].seen);
+                self.writer
+                    .write_all(
+                        format!("* {} FETCH ({}FLAGS {})\r\n", index + 1, uid, flags).as_bytes(),
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(format!(
+            "OK {}STORE completed",
+            if uid { "UID " } else { "" }
+        ))
+    }
+}
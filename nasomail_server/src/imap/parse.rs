@@ -0,0 +1,244 @@
+//! Tokenizing IMAP commands and parsing their common arguments.
+
+/// A token of a command, after the tag and the command name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// An atom, e.g, `FLAGS` or `BODY.PEEK[HEADER.FIELDS (FROM)]<0.10>`,
+    /// with anything in square brackets kept as it is.
+    Atom(String),
+
+    /// A quoted string or a literal.
+    String(Vec<u8>),
+
+    /// A parenthesized list.
+    List(Vec<Token>),
+}
+
+impl Token {
+    /// Returns the token as a string, if it is an atom or a string.
+    pub fn as_str(&self) -> Option<String> {
+        match self {
+            Self::Atom(atom) => Some(atom.clone()),
+            Self::String(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            Self::List(_) => None,
+        }
+    }
+}
+
+/// Returns the length of the literal announced at the end of `line`
+/// (e.g, `{12}\r\n`) and whether it is non-synchronizing (`{12+}`).
+pub fn literal_len(line: &[u8]) -> Option<(usize, bool)> {
+    let line = line.strip_suffix(b"\r\n").or(line.strip_suffix(b"\n"))?;
+    let line = line.strip_suffix(b"}")?;
+    let start = line.iter().rposition(|&b| b == b'{')?;
+
+    let spec = std::str::from_utf8(&line[start + 1..]).ok()?;
+    let (digits, plus) = match spec.strip_suffix('+') {
+        Some(digits) => (digits, true),
+        None => (spec, false),
+    };
+
+    Some((digits.parse().ok()?, plus))
+}
+
+/// Splits a complete command, with the data of every literal following
+/// its `{n}\r\n`, into its tokens.
+///
+/// Returns `None` if the command is malformed.
+///
+pub fn tokenize(input: &[u8]) -> Option<Vec<Token>> {
+    let mut pos = 0;
+    let tokens = tokenize_list(input, &mut pos, false)?;
+
+    (pos >= input.len()).then_some(tokens)
+}
+
+fn tokenize_list(input: &[u8], pos: &mut usize, nested: bool) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+
+    loop {
+        while input.get(*pos) == Some(&b' ') {
+            *pos += 1;
+        }
+
+        match input.get(*pos) {
+            None | Some(b'\r') | Some(b'\n') if !nested => {
+                *pos = input.len();
+                return Some(tokens);
+            }
+            None | Some(b'\r') | Some(b'\n') => return None,
+            Some(b')') if nested => {
+                *pos += 1;
+                return Some(tokens);
+            }
+            Some(b'(') => {
+                *pos += 1;
+                tokens.push(Token::List(tokenize_list(input, pos, true)?));
+            }
+            Some(b'"') => tokens.push(Token::String(quoted(input, pos)?)),
+            Some(b'{') => tokens.push(Token::String(literal(input, pos)?)),
+            Some(_) => tokens.push(Token::Atom(atom(input, pos)?)),
+        }
+    }
+}
+
+fn quoted(input: &[u8], pos: &mut usize) -> Option<Vec<u8>> {
+    let mut value = Vec::new();
+    *pos += 1;
+
+    loop {
+        match *input.get(*pos)? {
+            b'"' => {
+                *pos += 1;
+                return Some(value);
+            }
+            b'\\' => {
+                value.push(*input.get(*pos + 1)?);
+                *pos += 2;
+            }
+            b'\r' | b'\n' => return None,
+            b => {
+                value.push(b);
+                *pos += 1;
+            }
+        }
+    }
+}
+
+fn literal(input: &[u8], pos: &mut usize) -> Option<Vec<u8>> {
+    let end = *pos + input[*pos..].iter().position(|&b| b == b'\n')? + 1;
+    let (len, _) = literal_len(&input[*pos..end])?;
+
+    let data = input.get(end..end + len)?.to_vec();
+    *pos = end + len;
+
+    Some(data)
+}
+
+fn atom(input: &[u8], pos: &mut usize) -> Option<String> {
+    let start = *pos;
+    let mut depth = 0usize;
+
+    while let Some(&b) = input.get(*pos) {
+        match b {
+            b'[' => depth += 1,
+            b']' => depth = depth.checked_sub(1)?,
+            b'\r' | b'\n' => break,
+            b' ' | b'(' | b')' | b'"' | b'{' if depth == 0 => break,
+            _ => {}
+        }
+        *pos += 1;
+    }
+
+    (depth == 0)
+        .then(|| String::from_utf8_lossy(&input[start..*pos]).into_owned())
+        .filter(|atom| !atom.is_empty())
+}
+
+/// A set of sequence numbers or UIDs, e.g, `1:4,7,9:*`.
+#[derive(Debug, Clone)]
+pub struct SequenceSet(Vec<(Option<u32>, Option<u32>)>);
+
+impl SequenceSet {
+    /// Parses a sequence set, where `None` stands for `*`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let number = |value: &str| -> Option<Option<u32>> {
+            if value == "*" {
+                Some(None)
+            } else {
+                value.parse().ok().filter(|&n| n > 0).map(Some)
+            }
+        };
+
+        value
+            .split(',')
+            .map(|range| match range.split_once(':') {
+                Some((start, end)) => Some((number(start)?, number(end)?)),
+                None => {
+                    let n = number(range)?;
+                    Some((n, n))
+                }
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(Self)
+    }
+
+    /// Returns whether `n` is in the set, with `*` standing for `max`.
+    pub fn contains(&self, n: u32, max: u32) -> bool {
+        self.0.iter().any(|&(start, end)| {
+            let (start, end) = (start.unwrap_or(max), end.unwrap_or(max));
+            start.min(end) <= n && n <= start.max(end)
+        })
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parses an IMAP date, e.g, `1-Feb-1994`, into an SQLite date, e.g, `1994-02-01`.
+pub fn date(value: &str) -> Option<String> {
+    let mut parts = value.trim_matches('"').split('-');
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let year: u32 = parts.next()?.parse().ok()?;
+
+    let month = MONTHS.iter().position(|m| m.eq_ignore_ascii_case(month))? + 1;
+
+    (parts.next().is_none() && (1..=31).contains(&day))
+        .then(|| format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+/// Formats an SQLite `DATETIME` (in UTC) as an IMAP
+/// `date-time`, e.g, `"17-Jul-1996 02:44:25 +0000"`.
+pub fn date_time(value: &str) -> String {
+    let (date, time) = value.split_once(' ').unwrap_or((value, "00:00:00"));
+    let mut parts = date.split('-');
+
+    let (year, month, day) = (
+        parts.next().unwrap_or("1970"),
+        parts
+            .next()
+            .and_then(|m| m.parse::<usize>().ok())
+            .unwrap_or(1),
+        parts.next().unwrap_or("01"),
+    );
+
+    format!(
+        "\"{:0>2}-{}-{} {} +0000\"",
+        day,
+        MONTHS[month.clamp(1, 12) - 1],
+        year,
+        time
+    )
+}
+
+/// Quotes `value` as an IMAP string, falling back to
+/// a literal if it cannot be put into a quoted string.
+pub fn string(value: &str) -> String {
+    if value.contains(['\r', '\n']) || !value.is_ascii() {
+        format!("{{{}}}\r\n{}", value.len(), value)
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Matches a mailbox `name` against a `LIST` pattern,
+/// where `*` and `%` match any number of characters.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.chars().next() {
+        None => name.is_empty(),
+        Some('*' | '%') => {
+            let rest = &pattern[1..];
+            name.char_indices()
+                .map(|(i, _)| i)
+                .chain([name.len()])
+                .any(|i| matches(rest, &name[i..]))
+        }
+        Some(c) => {
+            let mut chars = name.chars();
+            chars.next().is_some_and(|n| n.eq_ignore_ascii_case(&c))
+                && matches(&pattern[c.len_utf8()..], chars.as_str())
+        }
+    }
+}
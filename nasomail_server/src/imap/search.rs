@@ -0,0 +1,238 @@
+//! The subset of `SEARCH` keys that maps onto the `mails` table.
+
+use sqlx::sqlite::SqlitePool;
+
+use crate::imap::{
+    mailbox::{Entry, Mailbox},
+    parse::{self, SequenceSet, Token},
+};
+
+/// A search key.
+#[derive(Debug)]
+enum Key {
+    All,
+    None,
+    Seen(bool),
+    Draft(bool),
+    Sequence(SequenceSet),
+    Uid(SequenceSet),
+    Before(String),
+    On(String),
+    Since(String),
+    From(String),
+    To(String),
+    Subject(String),
+    Body(String),
+    Text(String),
+    Not(Box<Key>),
+    Or(Box<Key>, Box<Key>),
+    And(Vec<Key>),
+}
+
+/// A parsed search query, which is a conjunction of keys.
+#[derive(Debug)]
+pub struct Query(Key);
+
+impl Query {
+    /// Parses the arguments of `SEARCH`, ignoring a leading `CHARSET`
+    /// since every string is compared as UTF-8 anyway.
+    ///
+    /// Returns `None` if any key is unknown or malformed.
+    ///
+    pub fn parse(tokens: &[Token]) -> Option<Self> {
+        let mut tokens = tokens.iter().peekable();
+
+        if tokens
+            .peek()
+            .and_then(|token| token.as_str())
+            .is_some_and(|token| token.eq_ignore_ascii_case("CHARSET"))
+        {
+            tokens.next();
+            tokens.next()?;
+        }
+
+        let mut keys = Vec::new();
+        while tokens.peek().is_some() {
+            keys.push(key(&mut tokens)?);
+        }
+
+        (!keys.is_empty()).then_some(Self(Key::And(keys)))
+    }
+
+    /// Returns whether the query needs the text of mails.
+    fn needs_text(&self) -> bool {
+        fn needs(key: &Key) -> bool {
+            match key {
+                Key::From(_) | Key::To(_) | Key::Subject(_) | Key::Body(_) | Key::Text(_) => true,
+                Key::Not(key) => needs(key),
+                Key::Or(a, b) => needs(a) || needs(b),
+                Key::And(keys) => keys.iter().any(needs),
+                _ => false,
+            }
+        }
+
+        needs(&self.0)
+    }
+
+    /// Returns the indices into `entries` of the messages matching the query.
+    pub async fn run(
+        &self,
+        pool: &SqlitePool,
+        user_id: i64,
+        mailbox: Mailbox,
+        entries: &[Entry],
+    ) -> Result<Vec<usize>, sqlx::Error> {
+        // Only load what the query looks at
+        let columns = if self.needs_text() {
+            "uid, DATE(created_at), sender, recipient, subject, body"
+        } else {
+            "uid, DATE(created_at), '', '', '', ''"
+        };
+
+        let rows: Vec<(i64, String, String, String, String, String)> = sqlx::query_as(&format!(
+            "SELECT {} FROM mails WHERE user_id = ? AND {} ORDER BY uid",
+            columns,
+            mailbox.condition()
+        ))
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let max_seq = entries.len() as u32;
+        let max_uid = entries.last().map_or(0, |entry| entry.uid);
+
+        Ok(entries
+            .iter()
+            .enumerate()
+            .filter(|(index, entry)| {
+                let Some((_, date, sender, recipient, subject, body)) = rows
+                    .binary_search_by_key(&(entry.uid as i64), |row| row.0)
+                    .ok()
+                    .map(|i| &rows[i])
+                else {
+                    return false;
+                };
+
+                let message = Candidate {
+                    seq: *index as u32 + 1,
+                    max_seq,
+                    max_uid,
+                    entry,
+                    mailbox,
+                    date,
+                    sender,
+                    recipient,
+                    subject,
+                    body,
+                };
+
+                message.matches(&self.0)
+            })
+            .map(|(index, _)| index)
+            .collect())
+    }
+}
+
+fn key<'a>(tokens: &mut impl Iterator<Item = &'a Token>) -> Option<Key> {
+    let token = tokens.next()?;
+
+    let atom = match token {
+        Token::List(list) => {
+            let mut inner = list.iter().peekable();
+            let mut keys = Vec::new();
+            while inner.peek().is_some() {
+                keys.push(key(&mut inner)?);
+            }
+            return Some(Key::And(keys));
+        }
+        _ => token.as_str()?,
+    };
+
+    let mut string = || tokens.next().and_then(|token| token.as_str());
+
+    Some(match atom.to_ascii_uppercase().as_str() {
+        "ALL" | "OLD" | "UNANSWERED" | "UNDELETED" | "UNFLAGGED" => Key::All,
+        "ANSWERED" | "DELETED" | "FLAGGED" | "NEW" | "RECENT" => Key::None,
+        "KEYWORD" => {
+            string()?;
+            Key::None
+        }
+        "UNKEYWORD" => {
+            string()?;
+            Key::All
+        }
+        "SEEN" => Key::Seen(true),
+        "UNSEEN" => Key::Seen(false),
+        "DRAFT" => Key::Draft(true),
+        "UNDRAFT" => Key::Draft(false),
+        "UID" => Key::Uid(SequenceSet::parse(&string()?)?),
+        "BEFORE" | "SENTBEFORE" => Key::Before(parse::date(&string()?)?),
+        "ON" | "SENTON" => Key::On(parse::date(&string()?)?),
+        "SINCE" | "SENTSINCE" => Key::Since(parse::date(&string()?)?),
+        "FROM" => Key::From(string()?.to_lowercase()),
+        "TO" => Key::To(string()?.to_lowercase()),
+        "SUBJECT" => Key::Subject(string()?.to_lowercase()),
+        "BODY" => Key::Body(string()?.to_lowercase()),
+        "TEXT" => Key::Text(string()?.to_lowercase()),
+        "HEADER" => {
+            let field = string()?.to_ascii_lowercase();
+            let value = string()?.to_lowercase();
+
+            match field.as_str() {
+                "from" => Key::From(value),
+                "to" => Key::To(value),
+                "subject" => Key::Subject(value),
+                _ => Key::None,
+            }
+        }
+        "NOT" => Key::Not(Box::new(key(tokens)?)),
+        "OR" => {
+            let a = key(tokens)?;
+            let b = key(tokens)?;
+            Key::Or(Box::new(a), Box::new(b))
+        }
+        _ => Key::Sequence(SequenceSet::parse(&atom)?),
+    })
+}
+
+/// A message being matched against a query.
+struct Candidate<'a> {
+    seq: u32,
+    max_seq: u32,
+    max_uid: u32,
+    entry: &'a Entry,
+    mailbox: Mailbox,
+    date: &'a str,
+    sender: &'a str,
+    recipient: &'a str,
+    subject: &'a str,
+    body: &'a str,
+}
+
+impl Candidate<'_> {
+    fn matches(&self, key: &Key) -> bool {
+        let contains = |haystack: &str, needle: &str| haystack.to_lowercase().contains(needle);
+
+        match key {
+            Key::All => true,
+            Key::None => false,
+            Key::Seen(seen) => self.entry.seen == *seen,
+            Key::Draft(draft) => (self.mailbox == Mailbox::Drafts) == *draft,
+            Key::Sequence(set) => set.contains(self.seq, self.max_seq),
+            Key::Uid(set) => set.contains(self.entry.uid, self.max_uid),
+            Key::Before(date) => self.date < date.as_str(),
+            Key::On(date) => self.date == date.as_str(),
+            Key::Since(date) => self.date >= date.as_str(),
+            Key::From(value) => contains(self.sender, value),
+            Key::To(value) => contains(self.recipient, value),
+            Key::Subject(value) => contains(self.subject, value),
+            Key::Body(value) => contains(self.body, value),
+            Key::Text(value) => [self.sender, self.recipient, self.subject, self.body]
+                .iter()
+                .any(|field| contains(field, value)),
+            Key::Not(key) => !self.matches(key),
+            Key::Or(a, b) => self.matches(a) || self.matches(b),
+            Key::And(keys) => keys.iter().all(|key| self.matches(key)),
+        }
+    }
+}
@@ -1,13 +1,41 @@
 mod api;
 mod app;
+mod blob;
 mod config;
+mod db;
+mod delivery;
+mod events;
+mod federation;
+mod imap;
+mod message;
 mod meta;
+mod migrate;
+mod mime;
+mod net;
+mod passphrase;
+mod pop3;
+mod queue;
+mod relay;
+mod schedule;
+mod session;
+mod sieve;
+mod smtp;
+mod trash;
+mod vacation;
+mod webhook;
+mod worker;
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use std::path::{Path, PathBuf};
-
-use anyhow::anyhow;
 use axum::Router;
-use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{
+    Connection, SqliteConnection,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+};
 use tracing::{info, instrument};
 
 use tokio::{
@@ -27,6 +55,9 @@ use crate::{
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
 
+    // `--dry-run` prints the pending migrations instead of running the server
+    let dry_run = env::args().skip(1).any(|arg| arg == "--dry-run");
+
     // ############################
     // ## Load the configuration ##
     // ############################
@@ -63,6 +94,42 @@ async fn main() -> anyhow::Result<()> {
     // #############################
 
     let db_path = PathBuf::from(cfg.db_path().await.clone());
+    let url = format!("sqlite://{}", cfg.db_path().await);
+
+    if dry_run {
+        // Only look at the database, never create or modify it
+        let plan = if fs::try_exists(&db_path).await? {
+            let options = SqliteConnectOptions::from_str(&url)?.read_only(true);
+            let mut conn = SqliteConnection::connect_with(&options).await?;
+
+            migrate::plan(&mut conn).await?
+        } else {
+            migrate::Plan {
+                legacy: false,
+                pending: migrate::MIGRATIONS.iter().collect(),
+            }
+        };
+
+        if plan.legacy {
+            println!("legacy database would be upgraded before migrating");
+        }
+
+        if plan.pending.is_empty() {
+            println!("no pending migrations");
+        }
+
+        for migration in plan.pending {
+            println!(
+                "pending migration {:04} {} ({})",
+                migration.version,
+                migration.name,
+                migration.checksum()
+            );
+        }
+
+        return Ok(());
+    }
+
     if !fs::try_exists(&db_path).await? {
         info!(db_path = ?db_path, "creating database");
 
@@ -75,7 +142,6 @@ async fn main() -> anyhow::Result<()> {
         File::create(db_path).await?;
     }
 
-    let url = format!("sqlite://{}", cfg.db_path().await);
     info!(url = %url, "connecting to database");
 
     let pool = SqlitePoolOptions::new()
@@ -83,27 +149,15 @@ async fn main() -> anyhow::Result<()> {
         .connect(&url)
         .await?;
 
-    let schema_path = PathBuf::from(cfg.schema_path().await.clone());
-
-    if fs::try_exists(&schema_path).await? {
-        info!(schema_path = ?schema_path, "executing schema");
-
-        let schema = fs::read_to_string(schema_path).await?;
-
-        for stmt in schema.split(';').filter(|s| !s.trim().is_empty()) {
-            sqlx::query(stmt).execute(&pool).await?;
-        }
-    } else {
-        return Err(anyhow!(format!(
-            "schema file does not exist: {}",
-            schema_path.display()
-        )));
-    }
+    migrate::run(&pool).await?;
+    webhook::sync(&pool, &cfg.webhooks().await).await?;
 
     // ####################
     // ## Run the server ##
     // ####################
 
+    net::set_allow_private(*cfg.allow_private_hosts().await);
+
     let app = AppContext::new(pool, cfg);
 
     let ctx = app.ctx().await;
@@ -116,6 +170,40 @@ async fn main() -> anyhow::Result<()> {
     info!(addr = %cfg.addr().await, "listening");
     let handle = tokio::spawn(async move { axum::serve(listener, router).await });
 
+    if let Some(smtp_addr) = cfg.smtp_addr().await.clone() {
+        let listener = tokio::net::TcpListener::bind(&smtp_addr).await?;
+
+        info!(smtp_addr = %smtp_addr, "listening for smtp");
+        tokio::spawn(smtp::serve(listener, app.clone()));
+    }
+
+    if let Some(imap_addr) = cfg.imap_addr().await.clone() {
+        let listener = tokio::net::TcpListener::bind(&imap_addr).await?;
+
+        info!(imap_addr = %imap_addr, "listening for imap");
+        tokio::spawn(imap::serve(listener, app.clone()));
+    }
+
+    if let Some(pop3_addr) = cfg.pop3_addr().await.clone() {
+        let listener = tokio::net::TcpListener::bind(&pop3_addr).await?;
+
+        info!(pop3_addr = %pop3_addr, "listening for pop3");
+        tokio::spawn(pop3::serve(listener, app.clone()));
+    }
+
+    info!("starting delivery queue");
+    tokio::spawn(queue::run(app.clone()));
+
+    info!("starting scheduled send");
+    tokio::spawn(schedule::run(app.clone()));
+
+    info!("starting webhooks");
+    tokio::spawn(webhook::listen(app.clone()));
+    tokio::spawn(webhook::run(app.clone()));
+
+    info!("starting trash purge");
+    tokio::spawn(trash::run(app.clone()));
+
     time::sleep(Duration::from_secs(1)).await;
     ctest::connection_test(app.clone()).await;
     handle.await??;
@@ -0,0 +1,262 @@
+//! Conversion between RFC 5322 messages and mails.
+
+use std::io;
+
+use mail_builder::{MessageBuilder, headers::raw::Raw};
+use mail_parser::{HeaderValue, Message, MessageParser, MimeHeaders};
+use sqlx::sqlite::SqlitePool;
+
+use nasomail_shared::payload::mail::{ADDRESS_MAX_LEN, BODY_MAX_LEN, SUBJECT_MAX_LEN};
+
+use crate::{
+    delivery::{self, Attachment, Mail},
+    mime,
+};
+
+/// The most attachment data a stored mail is rendered with, in
+/// bytes, since `render` holds the whole message in memory.
+pub const MAX_RENDERED_ATTACHMENTS_SIZE: u64 = 1024 * 1024 * 50;
+
+/// The parts of an RFC 5322 message that map onto a mail.
+#[derive(Debug)]
+pub struct Parsed {
+    /// The address in the `From` header, if there is one.
+    pub from: Option<String>,
+
+    pub subject: String,
+    pub body: String,
+    pub attachments: Vec<Attachment>,
+
+    /// The `Message-ID`, if there is a valid one.
+    pub message_id: Option<String>,
+
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+
+    /// Whether the message was generated automatically
+    /// or sent to a mailing list, see `is_auto_submitted`.
+    pub auto_submitted: bool,
+}
+
+/// Trims `value` and cuts it down to at most `max` characters,
+/// so it satisfies a `CHECK` constraint of the `mails` table.
+pub fn fit(value: &str, max: usize) -> String {
+    value
+        .trim()
+        .chars()
+        .take(max)
+        .collect::<String>()
+        .trim_end()
+        .to_owned()
+}
+
+/// Parses the RFC 5322 message `raw`.
+///
+/// The first text part (or HTML part converted to text) becomes the
+/// `body` and every other non-inline part becomes an attachment.
+/// Line endings of the body are normalized to `\n` and headers
+/// and the body are cut down to fit the `mails` table. Invalid
+/// message ids are left out.
+///
+/// Returns `None` if `raw` is not a message at all.
+///
+pub fn parse(raw: &[u8]) -> Option<Parsed> {
+    let message = MessageParser::default().parse(raw)?;
+
+    let from = message
+        .from()
+        .and_then(|from| from.first())
+        .and_then(|from| from.address())
+        .map(|address| fit(address, ADDRESS_MAX_LEN))
+        .filter(|address| !address.is_empty());
+
+    let attachments = message
+        .attachments()
+        .map(|part| {
+            let filename = part.attachment_name().unwrap_or_default();
+            let filename = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+            let filename = fit(filename, 255);
+
+            let data = part.contents().to_vec();
+
+            let content_type = part
+                .content_type()
+                .and_then(|ct| Some(format!("{}/{}", ct.ctype(), ct.subtype()?)))
+                .map(|ct| fit(&ct.to_lowercase(), 255))
+                .filter(|ct| !ct.is_empty() && ct != mime::OCTET_STREAM)
+                .unwrap_or_else(|| mime::sniff(&data, &filename).to_owned());
+
+            Attachment {
+                filename,
+                content_type,
+                data,
+            }
+        })
+        .collect();
+
+    Some(Parsed {
+        from,
+        subject: fit(message.subject().unwrap_or_default(), SUBJECT_MAX_LEN),
+        body: fit(
+            &message
+                .body_text(0)
+                .unwrap_or_default()
+                .replace("\r\n", "\n"),
+            BODY_MAX_LEN,
+        ),
+        attachments,
+        message_id: message.message_id().and_then(delivery::parse_message_id),
+        in_reply_to: message_ids(message.in_reply_to()).into_iter().next(),
+        references: delivery::fit_references(message_ids(message.references())),
+        auto_submitted: is_auto_submitted(&message),
+    })
+}
+
+/// Returns whether the headers of `message` mark it as generated automatically
+/// or sent to a mailing list, so it must not be replied to automatically, see
+/// section 2 of RFC 3834.
+fn is_auto_submitted(message: &Message) -> bool {
+    let header = |name: &str| {
+        message
+            .header_raw(name)
+            .map(|value| value.trim().to_ascii_lowercase())
+    };
+
+    header("Auto-Submitted").is_some_and(|value| !value.starts_with("no"))
+        || header("Precedence")
+            .is_some_and(|value| matches!(value.as_str(), "bulk" | "list" | "junk"))
+        || header("List-Id").is_some()
+        || header("List-Unsubscribe").is_some()
+}
+
+/// Returns the valid message ids of an `In-Reply-To` or `References` header.
+fn message_ids(value: &HeaderValue) -> Vec<String> {
+    let ids = match value {
+        HeaderValue::Text(id) => std::slice::from_ref(id),
+        HeaderValue::TextList(ids) => ids.as_slice(),
+        _ => &[],
+    };
+
+    ids.iter()
+        .filter_map(|id| delivery::parse_message_id(id))
+        .collect()
+}
+
+/// Qualifies a bare username with `hostname`, so it can be used
+/// as an email address. Other addresses are returned as they are.
+pub fn qualify(address: &str, hostname: &str) -> String {
+    if address.contains('@') {
+        address.to_owned()
+    } else {
+        format!("{}@{}", address, hostname)
+    }
+}
+
+/// Loads the attachments of the mail `mail_id`, to `render` it.
+///
+/// Returns `Ok(None)` if they are larger than
+/// `MAX_RENDERED_ATTACHMENTS_SIZE` in total.
+///
+/// # Errors
+///
+/// Returns `Err` if the database could not be queried.
+///
+pub async fn attachments(
+    pool: &SqlitePool,
+    mail_id: i64,
+) -> Result<Option<Vec<Attachment>>, sqlx::Error> {
+    let size: i64 =
+        sqlx::query_scalar("SELECT COALESCE(SUM(size), 0) FROM attachments WHERE mail_id = ?")
+            .bind(mail_id)
+            .fetch_one(pool)
+            .await?;
+
+    if u64::try_from(size).unwrap_or_default() > MAX_RENDERED_ATTACHMENTS_SIZE {
+        return Ok(None);
+    }
+
+    let attachments: Vec<(String, String, Vec<u8>)> = sqlx::query_as(
+        "SELECT filename, content_type, data FROM attachments WHERE mail_id = ? ORDER BY id",
+    )
+    .bind(mail_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Some(
+        attachments
+            .into_iter()
+            .map(|(filename, content_type, data)| Attachment {
+                filename,
+                content_type,
+                data,
+            })
+            .collect(),
+    ))
+}
+
+/// Estimates the size of a mail as rendered by `render` without rendering it,
+/// from the length of its text and the number and total size of its attachments.
+///
+/// Attachments are encoded as base64, in lines of 76 characters.
+pub fn estimated_size(text_len: u64, attachments: u64, attachments_size: u64) -> u64 {
+    /// The headers of the message and of its text part.
+    const HEADERS_LEN: u64 = 256;
+
+    /// The headers and boundary of each attachment.
+    const PART_LEN: u64 = 256;
+
+    let base64_len = attachments_size.div_ceil(3) * 4;
+
+    HEADERS_LEN + text_len + attachments * PART_LEN + base64_len + base64_len.div_ceil(76) * 2
+}
+
+/// Renders a stored mail as an RFC 5322 message, with `date` as
+/// a Unix timestamp and every attachment as a part of its own.
+///
+/// Bare usernames are qualified with `hostname`, so the
+/// same mail is always rendered the same way.
+///
+/// # Errors
+///
+/// Returns `Err` if the message could not be written.
+///
+pub fn render(
+    mail: &Mail,
+    date: i64,
+    attachments: &[Attachment],
+    hostname: &str,
+) -> io::Result<Vec<u8>> {
+    let mut builder = MessageBuilder::new()
+        .message_id(mail.message_id.as_str())
+        .from(qualify(&mail.sender, hostname))
+        .to(qualify(&mail.recipient, hostname))
+        .subject(mail.subject.as_str())
+        .date(date)
+        .text_body(mail.body.as_str());
+
+    if let Some(in_reply_to) = &mail.in_reply_to {
+        builder = builder.in_reply_to(in_reply_to.as_str());
+    }
+    if !mail.references.is_empty() {
+        builder = builder.references(mail.references.as_slice());
+    }
+    if mail.auto_submitted {
+        builder = builder.header("Auto-Submitted", Raw::new("auto-replied"));
+    }
+
+    for attachment in attachments {
+        let filename = if attachment.filename.is_empty() {
+            "attachment"
+        } else {
+            attachment.filename.as_str()
+        };
+
+        builder = builder.attachment(
+            attachment.content_type.as_str(),
+            filename,
+            attachment.data.as_slice(),
+        );
+    }
+
+    builder.write_to_vec()
+}
@@ -0,0 +1,255 @@
+//! Versioned migrations of the database schema.
+//!
+//! Migrations are numbered SQL files in `migrations/` which are embedded
+//! into the binary and applied in order, each in its own transaction.
+//! Applied migrations are recorded in the `schema_version` table along
+//! with a checksum of their SQL, so an edited migration is noticed
+//! instead of silently diverging from what the database actually holds.
+
+use anyhow::bail;
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, SqliteConnection, sqlite::SqlitePool};
+use tracing::{info, instrument};
+
+use crate::db;
+
+/// A single embedded migration.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+impl Migration {
+    /// Returns a hex-encoded SHA-256 digest of the SQL of the migration.
+    pub fn checksum(&self) -> String {
+        hex::encode(Sha256::digest(self.sql))
+    }
+}
+
+/// Every migration known to this version of `nasomail_server`,
+/// ordered by version. Migrations must never be edited or
+/// reordered once released, only appended to.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("../migrations/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "federation",
+        sql: include_str!("../migrations/0002_federation.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "delivery_retries",
+        sql: include_str!("../migrations/0003_delivery_retries.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "webhooks",
+        sql: include_str!("../migrations/0004_webhooks.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "mail_search",
+        sql: include_str!("../migrations/0005_mail_search.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "threads",
+        sql: include_str!("../migrations/0006_threads.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "labels",
+        sql: include_str!("../migrations/0007_labels.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "trash",
+        sql: include_str!("../migrations/0008_trash.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "filters",
+        sql: include_str!("../migrations/0009_filters.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "vacation",
+        sql: include_str!("../migrations/0010_vacation.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "scheduled_send",
+        sql: include_str!("../migrations/0011_scheduled_send.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "imap_uids",
+        sql: include_str!("../migrations/0012_imap_uids.sql"),
+    },
+];
+
+/// What `run` would do to a database.
+pub struct Plan {
+    /// Whether the database was created before versioned migrations
+    /// existed and has to be upgraded by `db::upgrade_legacy` first.
+    pub legacy: bool,
+
+    /// The migrations that have not been applied yet, in order.
+    pub pending: Vec<&'static Migration>,
+}
+
+/// Works out which migrations still have to be applied to the database behind `conn`.
+///
+/// # Errors
+///
+/// Returns `Err` if the database could not be queried, was migrated by
+/// a newer version of `nasomail_server` or an applied migration
+/// does not match its embedded counterpart.
+///
+pub async fn plan(conn: &mut SqliteConnection) -> anyhow::Result<Plan> {
+    let versioned: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_version')",
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if !versioned {
+        let legacy: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'users')",
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        return Ok(Plan {
+            legacy,
+            pending: MIGRATIONS.iter().collect(),
+        });
+    }
+
+    let applied: Vec<(i64, String, String)> =
+        sqlx::query_as("SELECT version, name, checksum FROM schema_version ORDER BY version")
+            .fetch_all(&mut *conn)
+            .await?;
+
+    let latest = MIGRATIONS.last().map_or(0, |migration| migration.version);
+
+    for (version, name, checksum) in &applied {
+        let Some(migration) = MIGRATIONS.iter().find(|m| m.version == *version) else {
+            bail!(
+                "database is at schema version {version} ({name}) but this server only knows \
+                 versions up to {latest}, refusing to start; upgrade nasomail_server"
+            );
+        };
+
+        if migration.checksum() != *checksum {
+            bail!(
+                "migration {version} ({name}) was modified after it was applied: \
+                 expected checksum {checksum}, found {}",
+                migration.checksum()
+            );
+        }
+    }
+
+    Ok(Plan {
+        legacy: false,
+        pending: MIGRATIONS
+            .iter()
+            .filter(|m| !applied.iter().any(|(version, _, _)| *version == m.version))
+            .collect(),
+    })
+}
+
+/// Brings the database behind `pool` up to the latest schema version.
+///
+/// Foreign keys are turned off while migrating so tables can be rebuilt
+/// without cascading, and checked with `PRAGMA foreign_key_check`
+/// before each migration is committed instead.
+///
+/// # Errors
+///
+/// Returns `Err` if `plan` fails or any migration fails, in which case
+/// the database is left at the last successfully applied version.
+///
+#[instrument(skip(pool))]
+pub async fn run(pool: &SqlitePool) -> anyhow::Result<()> {
+    let mut conn = pool.acquire().await?;
+    let plan = plan(&mut conn).await?;
+
+    if plan.legacy {
+        info!("upgrading database created before versioned migrations");
+        db::upgrade_legacy(pool).await?;
+    }
+
+    if plan.pending.is_empty() {
+        info!("database schema is up to date");
+        return Ok(());
+    }
+
+    sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut *conn)
+        .await?;
+
+    let result = async {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS schema_version (
+                version    INTEGER  PRIMARY KEY,
+                name       TEXT     NOT NULL,
+
+                -- A hex-encoded SHA-256 digest of the SQL of the migration
+                checksum   TEXT     NOT NULL,
+
+                applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        for migration in plan.pending {
+            info!(
+                version = migration.version,
+                name = migration.name,
+                "applying migration"
+            );
+
+            let mut tx = conn.begin().await?;
+
+            sqlx::raw_sql(migration.sql).execute(&mut *tx).await?;
+
+            let violations: Vec<(String, Option<i64>, String, i64)> =
+                sqlx::query_as("PRAGMA foreign_key_check")
+                    .fetch_all(&mut *tx)
+                    .await?;
+
+            if let Some((table, _, parent, _)) = violations.first() {
+                bail!(
+                    "migration {} ({}) violates a foreign key from {table} to {parent}",
+                    migration.version,
+                    migration.name
+                );
+            }
+
+            sqlx::query("INSERT INTO schema_version (version, name, checksum) VALUES (?, ?, ?)")
+                .bind(migration.version)
+                .bind(migration.name)
+                .bind(migration.checksum())
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await?;
+        }
+
+        anyhow::Ok(())
+    }
+    .await;
+
+    sqlx::query("PRAGMA foreign_keys = ON")
+        .execute(&mut *conn)
+        .await?;
+
+    result
+}
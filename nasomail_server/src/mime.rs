@@ -0,0 +1,163 @@
+//! Guessing the MIME type of attachments
+//! whose uploader did not provide one.
+
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Magic numbers at the start of common file formats.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"BM", "image/bmp"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"ID3", "audio/mpeg"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+];
+
+/// File extensions of common formats without a reliable magic number.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("svg", "image/svg+xml"),
+    ("ics", "text/calendar"),
+    ("tar", "application/x-tar"),
+];
+
+/// File extensions of common formats that are ZIP archives, so their
+/// magic number is the one of ZIP.
+const ZIP_EXTENSIONS: &[(&str, &str)] = &[
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("jar", "application/java-archive"),
+];
+
+/// Guesses the MIME type of an attachment from the first bytes of
+/// its data (`head`) and, failing that, from its `filename`.
+///
+/// The extension only decides between formats that share a magic number,
+/// e.g, a `.docx` file is a ZIP archive, so a `.png` file that holds HTML
+/// is never labelled as an image.
+///
+/// Data that is valid UTF-8 without any control characters other
+/// than whitespace is assumed to be `text/plain`.
+///
+/// Returns `OCTET_STREAM` if nothing matches.
+///
+pub fn sniff(head: &[u8], filename: &str) -> &'static str {
+    let ext = filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase())
+        .unwrap_or_default();
+    let by_extension = |extensions: &[(&str, &'static str)]| {
+        extensions
+            .iter()
+            .find(|(e, _)| *e == ext)
+            .map(|(_, mime)| *mime)
+    };
+
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(magic, _)| head.starts_with(magic)) {
+        if *mime == "application/zip" {
+            return by_extension(ZIP_EXTENSIONS).unwrap_or(mime);
+        }
+
+        return mime;
+    }
+
+    // RIFF containers carry their actual format at offset 8
+    if head.starts_with(b"RIFF") && head.len() >= 12 {
+        match &head[8..12] {
+            b"WEBP" => return "image/webp",
+            b"WAVE" => return "audio/wav",
+            b"AVI " => return "video/x-msvideo",
+            _ => {}
+        }
+    }
+
+    if head.len() >= 12 && &head[4..8] == b"ftyp" {
+        return "video/mp4";
+    }
+
+    if let Some(mime) = by_extension(EXTENSIONS) {
+        return mime;
+    }
+
+    if !head.is_empty() && is_text(head) {
+        return "text/plain";
+    }
+
+    OCTET_STREAM
+}
+
+fn is_text(head: &[u8]) -> bool {
+    // The head may end in the middle of a multi-byte character
+    let valid = match std::str::from_utf8(head) {
+        Ok(s) => s,
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return false,
+    };
+
+    valid
+        .chars()
+        .all(|c| !c.is_control() || c.is_ascii_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_wins_over_extension() {
+        assert_eq!(
+            sniff(b"<html><body>hi</body></html>", "image.png"),
+            "text/plain"
+        );
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n....", "notes.txt"), "image/png");
+        assert_eq!(sniff(b"%PDF-1.7\n", "page.html"), "application/pdf");
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 ", "photo.jpg"), "image/webp");
+    }
+
+    #[test]
+    fn extension_tells_zip_based_formats_apart() {
+        assert_eq!(
+            sniff(b"PK\x03\x04\x14\0", "report.docx"),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+        );
+        assert_eq!(sniff(b"PK\x03\x04\x14\0", "archive.zip"), "application/zip");
+        assert_eq!(sniff(b"PK\x03\x04\x14\0", "data.json"), "application/zip");
+    }
+
+    #[test]
+    fn extension_is_the_fallback() {
+        assert_eq!(sniff(b"<html></html>", "index.HTML"), "text/html");
+        assert_eq!(sniff(b"a,b\n1,2\n", "table.csv"), "text/csv");
+        assert_eq!(sniff(b"just text", "notes"), "text/plain");
+        assert_eq!(sniff(b"\0\x01\x02", "blob.bin"), OCTET_STREAM);
+        assert_eq!(sniff(b"", "empty.docx"), OCTET_STREAM);
+    }
+}
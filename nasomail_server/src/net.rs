@@ -0,0 +1,161 @@
+//! Guarding requests to hosts picked by someone else, e.g, the `origin` of a
+//! federated delivery, the host of a remote recipient or the `url` of a
+//! webhook, so they cannot be used to reach into the network the server
+//! runs in (SSRF).
+//!
+//! Hosts are checked with `check` before a request is made, and clients
+//! that talk to such hosts resolve names with `PublicResolver`, so a name
+//! that resolves to a public address when checked and to a private one
+//! when connecting is refused all the same. Both can be turned off with
+//! `Config::allow_private_hosts`, which is read once on startup.
+
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use tokio::net;
+
+static ALLOW_PRIVATE: AtomicBool = AtomicBool::new(false);
+
+/// A custom error type for host checks.
+#[derive(Debug, thiserror::Error)]
+pub enum NetError {
+    #[error("failed to resolve {0}: {1}")]
+    Resolve(String, io::Error),
+
+    #[error("{0} does not resolve to a public address")]
+    NotPublic(String),
+}
+
+/// Sets whether loopback, private and link-local addresses may be reached.
+pub fn set_allow_private(allow: bool) {
+    ALLOW_PRIVATE.store(allow, Ordering::Relaxed);
+}
+
+/// Returns whether `ip` may be reached from the public internet, i.e,
+/// it is not a loopback, private, link-local, shared or unspecified address.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || a == 0
+                // Shared address space of carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    // Unique local, fc00::/7
+                    || (first & 0xfe00) == 0xfc00
+                    // Link-local, fe80::/10
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Resolves `host`, a `host` or `host:port`, and checks that
+/// every address it resolves to is public, see `is_public`.
+///
+/// # Errors
+///
+/// Returns `Err(Resolve)`   if `host` could not be resolved.
+/// Returns `Err(NotPublic)` if any of its addresses is not public.
+///
+pub async fn check(host: &str) -> Result<(), NetError> {
+    if ALLOW_PRIVATE.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    // The port does not matter, so fill one in if there is none
+    let target = match host.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => host.to_owned(),
+        _ => format!("{host}:0"),
+    };
+
+    let addrs: Vec<SocketAddr> = net::lookup_host(&target)
+        .await
+        .map_err(|e| NetError::Resolve(host.to_owned(), e))?
+        .collect();
+
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public(addr.ip())) {
+        return Err(NetError::NotPublic(host.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Resolves names like the system does, but drops every address that is
+/// not public, see `is_public`, unless private hosts are allowed.
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let name = name.as_str().to_owned();
+
+        Box::pin(async move {
+            let allow_private = ALLOW_PRIVATE.load(Ordering::Relaxed);
+
+            let addrs: Vec<SocketAddr> = net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| allow_private || is_public(addr.ip()))
+                .collect();
+
+            if addrs.is_empty() {
+                return Err(NetError::NotPublic(name).into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(ip: &str) -> bool {
+        is_public(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn private_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!public(ip), "{ip}");
+        }
+    }
+
+    #[test]
+    fn public_addresses_are_public() {
+        for ip in ["1.1.1.1", "93.184.216.34", "100.128.0.1", "2606:4700::1111"] {
+            assert!(public(ip), "{ip}");
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Hashing and verification of user passphrases.
+//!
+//! Passphrases are stored in the `users` table as
+//! PHC-format Argon2id hashes. Rows created before hashing
+//! was introduced still hold plaintext and are recognized
+//! by not parsing as a PHC string.
+
+use anyhow::anyhow;
+use argon2::{
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
+    password_hash::{SaltString, rand_core::OsRng},
+};
+use nasomail_shared::query::user::UserQuery;
+use sqlx::sqlite::SqlitePool;
+use tokio::task;
+use tracing::warn;
+
+use crate::config::Argon2Config;
+
+/// An enum of results for passphrase verification.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// The passphrase does not match.
+    Invalid,
+
+    /// The passphrase matches and the stored hash is up to date.
+    Valid,
+
+    /// The passphrase matches but the stored value is either
+    /// plaintext or was hashed with different parameters,
+    /// so it should be replaced with a fresh hash.
+    Outdated,
+}
+
+impl Verification {
+    pub fn is_valid(&self) -> bool {
+        *self != Self::Invalid
+    }
+}
+
+fn argon2(cfg: &Argon2Config) -> anyhow::Result<Argon2<'static>> {
+    let params = cfg
+        .params()
+        .map_err(|e| anyhow!("invalid argon2 parameters: {e}"))?;
+
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+/// Hashes `passphrase` with Argon2id using the cost parameters in `cfg`.
+///
+/// Hashing is done on the blocking thread pool
+/// since it is deliberately slow.
+///
+/// # Errors
+///
+/// Returns `Err` if the parameters in `cfg` are invalid or hashing fails.
+///
+pub async fn hash(cfg: Argon2Config, passphrase: String) -> anyhow::Result<String> {
+    task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+
+        Ok(argon2(&cfg)?
+            .hash_password(passphrase.as_bytes(), &salt)
+            .map_err(|e| anyhow!("failed to hash passphrase: {e}"))?
+            .to_string())
+    })
+    .await?
+}
+
+/// Verifies `passphrase` against the `stored` value of a `users.passphrase` column.
+///
+/// Plaintext values are compared in constant time and
+/// always reported as `Verification::Outdated` on a match.
+///
+/// # Errors
+///
+/// Returns `Err` if the parameters in `cfg` are invalid.
+///
+pub async fn verify(
+    cfg: Argon2Config,
+    stored: String,
+    passphrase: String,
+) -> anyhow::Result<Verification> {
+    task::spawn_blocking(move || {
+        let Ok(hash) = PasswordHash::new(&stored) else {
+            return Ok(
+                if constant_time_eq(stored.as_bytes(), passphrase.as_bytes()) {
+                    Verification::Outdated
+                } else {
+                    Verification::Invalid
+                },
+            );
+        };
+
+        let argon2 = argon2(&cfg)?;
+
+        if argon2
+            .verify_password(passphrase.as_bytes(), &hash)
+            .is_err()
+        {
+            return Ok(Verification::Invalid);
+        }
+
+        let outdated = !is_current(&hash, argon2.params());
+
+        Ok(if outdated {
+            Verification::Outdated
+        } else {
+            Verification::Valid
+        })
+    })
+    .await?
+}
+
+/// Returns whether `hash` is an Argon2id hash made with the cost parameters
+/// in `params` and the current version.
+///
+/// Parameters parsed from a PHC string never carry an output length,
+/// so only the costs are compared rather than the whole `Params`.
+fn is_current(hash: &PasswordHash, params: &Params) -> bool {
+    let Ok(parsed) = Params::try_from(hash) else {
+        return false;
+    };
+
+    hash.algorithm == Algorithm::Argon2id.ident()
+        && hash.version == Some(Version::V0x13.into())
+        && parsed.m_cost() == params.m_cost()
+        && parsed.t_cost() == params.t_cost()
+        && parsed.p_cost() == params.p_cost()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Checks `passphrase` against the user in the `users` table
+/// specified by the `id` or `name` fields of the provided `UserQuery`.
+///
+/// Plaintext passphrases left over from before hashing was introduced, and hashes
+/// made with outdated parameters, are replaced with a fresh hash on a match.
+///
+/// Returns `Ok(Some(id))` if the user exists and the passphrase matches.
+/// Returns `Ok(None)`     otherwise.
+///
+/// # Errors
+///
+/// Returns `Err` if the database could not be queried
+/// or the parameters in `cfg` are invalid.
+///
+pub async fn authenticate(
+    pool: &SqlitePool,
+    cfg: Argon2Config,
+    query: UserQuery,
+    passphrase: String,
+) -> anyhow::Result<Option<i64>> {
+    let row: Option<(i64, String)> = match query {
+        UserQuery::ById { id } => {
+            sqlx::query_as("SELECT id, passphrase FROM users WHERE id = ?")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?
+        }
+        UserQuery::ByName { name } => {
+            sqlx::query_as("SELECT id, passphrase FROM users WHERE name = ?")
+                .bind(name)
+                .fetch_optional(pool)
+                .await?
+        }
+    };
+
+    let Some((id, stored)) = row else {
+        return Ok(None);
+    };
+
+    let verification = verify(cfg.clone(), stored.clone(), passphrase.clone()).await?;
+
+    if verification == Verification::Outdated {
+        // Failing to upgrade the stored value should not fail the login itself.
+        let result = async {
+            sqlx::query("UPDATE users SET passphrase = ? WHERE id = ? AND passphrase = ?")
+                .bind(hash(cfg, passphrase).await?)
+                .bind(id)
+                .bind(stored)
+                .execute(pool)
+                .await?;
+
+            anyhow::Ok(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            warn!(err = ?e, id = id, "failed to upgrade passphrase hash");
+        }
+    }
+
+    Ok(verification.is_valid().then_some(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(memory_kib: u32) -> Argon2Config {
+        Argon2Config {
+            memory_kib,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    async fn verify_with(cfg: Argon2Config, stored: &str, passphrase: &str) -> Verification {
+        verify(cfg, stored.to_owned(), passphrase.to_owned())
+            .await
+            .expect("valid parameters")
+    }
+
+    #[tokio::test]
+    async fn fresh_hash_is_valid() {
+        let stored = hash(cfg(1024), "password1".to_owned()).await.unwrap();
+
+        assert_eq!(
+            verify_with(cfg(1024), &stored, "password1").await,
+            Verification::Valid
+        );
+        assert_eq!(
+            verify_with(cfg(1024), &stored, "password2").await,
+            Verification::Invalid
+        );
+    }
+
+    #[tokio::test]
+    async fn hash_with_other_params_is_outdated() {
+        let stored = hash(cfg(1024), "password1".to_owned()).await.unwrap();
+
+        assert_eq!(
+            verify_with(cfg(2048), &stored, "password1").await,
+            Verification::Outdated
+        );
+    }
+
+    #[tokio::test]
+    async fn plaintext_is_outdated() {
+        assert_eq!(
+            verify_with(cfg(1024), "password1", "password1").await,
+            Verification::Outdated
+        );
+        assert_eq!(
+            verify_with(cfg(1024), "password1", "password2").await,
+            Verification::Invalid
+        );
+    }
+}
@@ -0,0 +1,558 @@
+//! An optional POP3 listener (RFC 1939) for draining inboxes.
+//!
+//! Users log in with `USER` and `PASS`, after which the maildrop is
+//! their inbox (`new` and `read` mails) as it was at that moment. Messages
+//! are rendered by `message::render`, their unique ids are the `id`s of
+//! mails, and retrieving one marks it as `read`. Mails marked with `DELE`
+//! are moved to the trash once the client sends `QUIT`.
+
+use std::io;
+
+use sqlx::sqlite::SqlitePool;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    time::{self, Duration},
+};
+use tracing::{info, instrument, warn};
+
+use nasomail_shared::{
+    address,
+    payload::{event::EventPayload, mail::MailStatus},
+    query::user::UserQuery,
+};
+
+use crate::{
+    app::AppContextGuard,
+    config::Argon2Config,
+    delivery::{Mail, MailRow},
+    events::Events,
+    message, passphrase,
+};
+
+const CAPABILITY: &str = "USER\r\nUIDL\r\nTOP\r\nRESP-CODES\r\nPIPELINING\r\n";
+
+/// The longest command line accepted, including the `CRLF`.
+const MAX_LINE_LEN: u64 = 1024;
+
+/// How long a client may stay silent before the connection
+/// is closed, the least RFC 1939 allows.
+const TIMEOUT: Duration = Duration::from_secs(60 * 10);
+
+/// Accepts connections on `listener` until the process exits,
+/// handling each session in a task of its own.
+pub async fn serve(listener: TcpListener, app: AppContextGuard) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!(err = ?e, "failed to accept pop3 connection");
+                continue;
+            }
+        };
+
+        let app = app.clone();
+        tokio::spawn(async move {
+            if let Err(e) = session(stream, app).await {
+                info!(peer = %peer, err = %e, "pop3 session ended");
+            }
+        });
+    }
+}
+
+/// A message of the maildrop.
+struct Message {
+    id: i64,
+
+    /// The size of the rendered message, as estimated
+    /// on login, see `message::estimated_size`.
+    size: u64,
+    deleted: bool,
+}
+
+/// The maildrop of the user logged in.
+struct Maildrop {
+    user_id: i64,
+    messages: Vec<Message>,
+}
+
+struct Session {
+    pool: SqlitePool,
+    events: Events,
+    argon2: Argon2Config,
+    hostname: String,
+
+    /// The name given with `USER`, until `PASS` follows.
+    name: Option<String>,
+    maildrop: Option<Maildrop>,
+}
+
+#[instrument(skip_all, fields(peer = ?stream.peer_addr().ok()))]
+async fn session(stream: TcpStream, app: AppContextGuard) -> io::Result<()> {
+    // Clone what is needed so the context is not held for the whole session
+    let (pool, events, argon2, pub_addr) = {
+        let ctx = app.ctx().await;
+        let cfg = ctx.cfg().await;
+
+        (
+            ctx.pool().await.clone(),
+            ctx.events().clone(),
+            cfg.argon2().await.clone(),
+            cfg.pub_addr().await.clone(),
+        )
+    };
+
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    let mut session = Session {
+        pool,
+        events,
+        argon2,
+        hostname: address::host_name(&pub_addr).to_owned(),
+        name: None,
+        maildrop: None,
+    };
+
+    writer.write_all(b"+OK NasoMail POP3 ready\r\n").await?;
+
+    loop {
+        let Some(line) = read_line(&mut reader).await? else {
+            writer.write_all(b"-ERR line too long\r\n").await?;
+            continue;
+        };
+
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (verb, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let verb = verb.to_ascii_uppercase();
+
+        if verb == "QUIT" {
+            let reply = match session.update().await {
+                Ok(()) => "+OK bye\r\n",
+                Err(e) => {
+                    warn!(err = ?e, "failed to delete messages");
+                    "-ERR [SYS/TEMP] some deleted messages not removed\r\n"
+                }
+            };
+
+            writer.write_all(reply.as_bytes()).await?;
+            return Ok(());
+        }
+
+        let reply = match session.command(&verb, arg.trim()).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                warn!(err = ?e, command = %verb, "failed to run pop3 command");
+                b"-ERR [SYS/TEMP] local error in processing\r\n".to_vec()
+            }
+        };
+
+        writer.write_all(&reply).await?;
+    }
+}
+
+impl Session {
+    /// Runs the command `verb` with `arg`, other than `QUIT`,
+    /// and returns the response.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the database could not be queried.
+    ///
+    async fn command(&mut self, verb: &str, arg: &str) -> Result<Vec<u8>, sqlx::Error> {
+        let reply = match verb {
+            "CAPA" => format!("+OK capability list follows\r\n{}.\r\n", CAPABILITY),
+            "NOOP" if self.maildrop.is_some() => "+OK\r\n".to_owned(),
+            "USER" if self.maildrop.is_some() => "-ERR already logged in\r\n".to_owned(),
+            "USER" if arg.is_empty() => "-ERR expected a name\r\n".to_owned(),
+            "USER" => {
+                self.name = Some(arg.to_owned());
+                "+OK send PASS\r\n".to_owned()
+            }
+            "PASS" if self.maildrop.is_some() => "-ERR already logged in\r\n".to_owned(),
+            "PASS" => match self.name.take() {
+                None => "-ERR send USER first\r\n".to_owned(),
+                Some(name) => self.login(name, arg.to_owned()).await?,
+            },
+            _ if self.maildrop.is_none() => "-ERR log in first\r\n".to_owned(),
+            "STAT" => self.stat(),
+            "LIST" => self.list(arg),
+            "UIDL" => self.uidl(arg),
+            "RETR" => return self.retrieve(arg).await,
+            "TOP" => return self.top(arg).await,
+            "DELE" => match self.message(arg) {
+                Ok(index) => {
+                    self.messages_mut()[index].deleted = true;
+                    format!("+OK message {} deleted\r\n", index + 1)
+                }
+                Err(reply) => reply.to_owned(),
+            },
+            "RSET" => {
+                self.messages_mut()
+                    .iter_mut()
+                    .for_each(|message| message.deleted = false);
+                "+OK\r\n".to_owned()
+            }
+            _ => "-ERR command not recognized\r\n".to_owned(),
+        };
+
+        Ok(reply.into_bytes())
+    }
+
+    async fn login(&mut self, name: String, passphrase: String) -> Result<String, sqlx::Error> {
+        let query = UserQuery::ByName { name };
+
+        let user_id = match passphrase::authenticate(
+            &self.pool,
+            self.argon2.clone(),
+            query,
+            passphrase,
+        )
+        .await
+        {
+            Ok(Some(user_id)) => user_id,
+            Ok(None) => return Ok("-ERR [AUTH] invalid credentials\r\n".to_owned()),
+            Err(e) => {
+                warn!(err = ?e, "failed to authenticate");
+                return Ok("-ERR [SYS/TEMP] authentication is unavailable\r\n".to_owned());
+            }
+        };
+
+        // The sizes are estimated, since rendering every
+        // message would mean loading all of the attachments
+        let rows: Vec<(i64, i64, i64, i64)> = sqlx::query_as(
+            "SELECT mails.id,
+                    LENGTH(CAST(mails.sender || mails.recipient || mails.subject || mails.body
+                        || mails.message_id || COALESCE(mails.in_reply_to, '') || mails.refs
+                        AS BLOB)),
+                    COUNT(attachments.id),
+                    COALESCE(SUM(attachments.size), 0)
+                FROM mails
+                LEFT JOIN attachments ON attachments.mail_id = mails.id
+                WHERE mails.user_id = ? AND mails.status IN ('new', 'read')
+                    AND mails.deleted_at IS NULL
+                GROUP BY mails.id
+                ORDER BY mails.id",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let messages: Vec<Message> = rows
+            .into_iter()
+            .map(|(id, text_len, attachments, attachments_size)| {
+                let [text_len, attachments, attachments_size] =
+                    [text_len, attachments, attachments_size]
+                        .map(|value| u64::try_from(value).unwrap_or_default());
+
+                Message {
+                    id,
+                    size: message::estimated_size(text_len, attachments, attachments_size),
+                    deleted: false,
+                }
+            })
+            .collect();
+
+        let reply = format!("+OK maildrop has {} messages\r\n", messages.len());
+        self.maildrop = Some(Maildrop { user_id, messages });
+
+        Ok(reply)
+    }
+
+    fn messages(&self) -> &[Message] {
+        self.maildrop
+            .as_ref()
+            .map_or(&[], |maildrop| &maildrop.messages)
+    }
+
+    fn messages_mut(&mut self) -> &mut [Message] {
+        self.maildrop
+            .as_mut()
+            .map_or(&mut [], |maildrop| &mut maildrop.messages)
+    }
+
+    /// Resolves the message number `arg` to an index into the maildrop.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with the response if there is no such message
+    /// or it is marked as deleted.
+    ///
+    fn message(&self, arg: &str) -> Result<usize, &'static str> {
+        let index = arg
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| number.checked_sub(1))
+            .filter(|&index| index < self.messages().len())
+            .ok_or("-ERR no such message\r\n")?;
+
+        if self.messages()[index].deleted {
+            return Err("-ERR message is deleted\r\n");
+        }
+
+        Ok(index)
+    }
+
+    /// Renders the message at `index` of the maildrop.
+    ///
+    /// Returns `Ok(Err(reply))` with the reply to send instead if the mail was
+    /// deleted in the meantime or is too large to render, see `message::attachments`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the database could not be queried.
+    ///
+    async fn render(&self, index: usize) -> Result<Result<Vec<u8>, &'static str>, sqlx::Error> {
+        let (Some(maildrop), id) = (&self.maildrop, self.messages()[index].id) else {
+            return Ok(Err("-ERR message no longer exists\r\n"));
+        };
+
+        #[derive(sqlx::FromRow)]
+        struct RenderRow {
+            #[sqlx(flatten)]
+            mail: MailRow,
+            date: i64,
+        }
+
+        let row: Option<RenderRow> = sqlx::query_as(
+            "SELECT sender, recipient, subject, body, message_id, in_reply_to, refs, auto_submitted,
+                    UNIXEPOCH(created_at) AS date
+                FROM mails WHERE id = ? AND user_id = ?",
+        )
+        .bind(id)
+        .bind(maildrop.user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(RenderRow { mail, date }) = row else {
+            return Ok(Err("-ERR message no longer exists\r\n"));
+        };
+
+        let Some(attachments) = message::attachments(&self.pool, id).await? else {
+            return Ok(Err("-ERR [SYS/PERM] message is too large to retrieve\r\n"));
+        };
+
+        let mail = Mail::from(mail);
+
+        Ok(Ok(message::render(
+            &mail,
+            date,
+            &attachments,
+            &self.hostname,
+        )
+        .unwrap_or_default()))
+    }
+
+    fn stat(&self) -> String {
+        let (count, total) = self
+            .messages()
+            .iter()
+            .filter(|message| !message.deleted)
+            .fold((0, 0), |(count, total), message| {
+                (count + 1, total + message.size)
+            });
+
+        format!("+OK {} {}\r\n", count, total)
+    }
+
+    fn list(&self, arg: &str) -> String {
+        if !arg.is_empty() {
+            return match self.message(arg) {
+                Ok(index) => format!("+OK {} {}\r\n", index + 1, self.messages()[index].size),
+                Err(reply) => reply.to_owned(),
+            };
+        }
+
+        let mut reply = "+OK scan listing follows\r\n".to_owned();
+        for (index, message) in self.messages().iter().enumerate() {
+            if !message.deleted {
+                reply += &format!("{} {}\r\n", index + 1, message.size);
+            }
+        }
+        reply += ".\r\n";
+
+        reply
+    }
+
+    fn uidl(&self, arg: &str) -> String {
+        if !arg.is_empty() {
+            return match self.message(arg) {
+                Ok(index) => format!("+OK {} {}\r\n", index + 1, self.messages()[index].id),
+                Err(reply) => reply.to_owned(),
+            };
+        }
+
+        let mut reply = "+OK unique-id listing follows\r\n".to_owned();
+        for (index, message) in self.messages().iter().enumerate() {
+            if !message.deleted {
+                reply += &format!("{} {}\r\n", index + 1, message.id);
+            }
+        }
+        reply += ".\r\n";
+
+        reply
+    }
+
+    async fn retrieve(&mut self, arg: &str) -> Result<Vec<u8>, sqlx::Error> {
+        let index = match self.message(arg) {
+            Ok(index) => index,
+            Err(reply) => return Ok(reply.as_bytes().to_vec()),
+        };
+
+        let raw = match self.render(index).await? {
+            Ok(raw) => raw,
+            Err(reply) => return Ok(reply.as_bytes().to_vec()),
+        };
+
+        let id = self.messages()[index].id;
+        let updated =
+            sqlx::query("UPDATE mails SET status = 'read' WHERE id = ? AND status = 'new'")
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+
+        if let Some(maildrop) = &self.maildrop
+            && updated.rows_affected() > 0
+        {
+            self.events.publish(
+                maildrop.user_id,
+                EventPayload::StatusChanged {
+                    id,
+                    status: MailStatus::Read,
+                },
+            );
+        }
+
+        let mut reply = format!("+OK {} octets\r\n", raw.len()).into_bytes();
+        stuff(&raw, &mut reply);
+
+        Ok(reply)
+    }
+
+    /// Responds to `TOP`, i.e, with the header and the first
+    /// lines of the body, which does not mark the mail as `read`.
+    async fn top(&mut self, arg: &str) -> Result<Vec<u8>, sqlx::Error> {
+        let (number, lines) = arg.split_once(' ').unwrap_or((arg, ""));
+
+        let Ok(lines) = lines.trim().parse::<usize>() else {
+            return Ok(b"-ERR expected a message number and a line count\r\n".to_vec());
+        };
+
+        let index = match self.message(number) {
+            Ok(index) => index,
+            Err(reply) => return Ok(reply.as_bytes().to_vec()),
+        };
+
+        let raw = match self.render(index).await? {
+            Ok(raw) => raw,
+            Err(reply) => return Ok(reply.as_bytes().to_vec()),
+        };
+
+        let end = match raw.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(header_end) => raw[header_end + 4..]
+                .split_inclusive(|&b| b == b'\n')
+                .take(lines)
+                .fold(header_end + 4, |end, line| end + line.len()),
+            None => raw.len(),
+        };
+
+        let mut reply = b"+OK top of message follows\r\n".to_vec();
+        stuff(&raw[..end], &mut reply);
+
+        Ok(reply)
+    }
+
+    /// Moves the mails marked as deleted to the trash, on `QUIT`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the database could not be queried,
+    /// in which case none of them were moved.
+    ///
+    async fn update(&self) -> Result<(), sqlx::Error> {
+        let Some(maildrop) = &self.maildrop else {
+            return Ok(());
+        };
+
+        let mut tx = self.pool.begin().await?;
+        let mut trashed = Vec::new();
+
+        for message in maildrop.messages.iter().filter(|message| message.deleted) {
+            let result = sqlx::query(
+                "UPDATE mails SET deleted_at = CURRENT_TIMESTAMP
+                    WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+            )
+            .bind(message.id)
+            .bind(maildrop.user_id)
+            .execute(&mut *tx)
+            .await?;
+
+            if result.rows_affected() > 0 {
+                trashed.push(message.id);
+            }
+        }
+
+        tx.commit().await?;
+
+        for id in trashed {
+            self.events
+                .publish(maildrop.user_id, EventPayload::Trashed { id });
+        }
+
+        Ok(())
+    }
+}
+
+/// Appends `raw` to `out` as a multi-line response, i.e, with lines
+/// starting with a `.` stuffed with another one and a terminating `.` line.
+fn stuff(raw: &[u8], out: &mut Vec<u8>) {
+    for line in raw.split_inclusive(|&b| b == b'\n') {
+        if line.starts_with(b".") {
+            out.push(b'.');
+        }
+        out.extend_from_slice(line);
+    }
+
+    if !out.ends_with(b"\r\n") {
+        out.extend_from_slice(b"\r\n");
+    }
+    out.extend_from_slice(b".\r\n");
+}
+
+/// Reads a single command line.
+///
+/// Returns `Ok(None)` if the line is longer than `MAX_LINE_LEN`, in which
+/// case the rest of it is discarded.
+///
+/// # Errors
+///
+/// Returns `Err` if the connection was closed, timed out or failed.
+///
+async fn read_line<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    let mut too_long = false;
+
+    loop {
+        let read = time::timeout(
+            TIMEOUT,
+            (&mut *reader)
+                .take(MAX_LINE_LEN)
+                .read_until(b'\n', &mut line),
+        )
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "client timed out"))??;
+
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        if line.ends_with(b"\n") {
+            return Ok((!too_long).then_some(line));
+        }
+
+        // Discard the rest of an overly long line
+        too_long = true;
+        line.clear();
+    }
+}
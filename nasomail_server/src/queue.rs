@@ -0,0 +1,230 @@
+//! The persistent queue of mails waiting to be delivered to other servers.
+//!
+//! Rows in `delivery_queue` are picked up by a background worker started
+//! from `main`, see `worker`, which retries failed attempts with exponential
+//! backoff (see `DeliveryConfig`) and bounces mails that cannot be delivered back
+//! into the inbox of their sender. All of its state lives in the database,
+//! so the queue picks up where it left off after a restart.
+
+use password_hash::rand_core::{OsRng, RngCore};
+use reqwest::StatusCode;
+use sqlx::{SqliteConnection, sqlite::SqlitePool};
+use tracing::instrument;
+
+use nasomail_shared::{
+    address::{self, Address},
+    payload::event::EventPayload,
+};
+
+use crate::{
+    app::AppContextGuard,
+    config::{DeliveryConfig, RelayConfig},
+    delivery,
+    events::Events,
+    federation, relay,
+    worker::{self, Attempts, Settled, Worker},
+};
+
+static WORKER: Worker = Worker::new();
+
+/// The result of a single attempt at delivering a queued mail.
+#[derive(Debug)]
+pub struct Attempt {
+    /// The status the remote server responded with, if it could be reached,
+    /// i.e, an HTTP status for `federation` and an SMTP reply code for `relay`.
+    pub status_code: Option<u16>,
+
+    pub outcome: Outcome,
+}
+
+/// An enum of outcomes of delivery attempts.
+#[derive(Debug)]
+pub enum Outcome {
+    Delivered,
+
+    /// The attempt failed but may succeed later.
+    Retry(String),
+
+    /// The attempt failed and retrying would not help.
+    Fail(String),
+}
+
+impl Outcome {
+    /// Classifies an HTTP response with `status`, failing with `error`.
+    ///
+    /// Server errors, timeouts and rate limiting are worth
+    /// retrying, any other error response is not.
+    pub fn from_status(status: StatusCode, error: String) -> Self {
+        if status.is_success() {
+            Self::Delivered
+        } else if status.is_server_error()
+            || status == StatusCode::REQUEST_TIMEOUT
+            || status == StatusCode::TOO_MANY_REQUESTS
+        {
+            Self::Retry(error)
+        } else {
+            Self::Fail(error)
+        }
+    }
+
+    /// Returns why the attempt failed, if it did.
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            Self::Delivered => None,
+            Self::Retry(error) | Self::Fail(error) => Some(error),
+        }
+    }
+}
+
+/// Queues the `sent` copy `mail_id` for delivery to the remote `recipient`.
+///
+/// Returns the `id` of the new `delivery_queue` row. Call `wake` once
+/// the surrounding transaction has been committed, so the worker
+/// does not wait for its next poll to attempt it.
+pub async fn enqueue(
+    conn: &mut SqliteConnection,
+    mail_id: i64,
+    recipient: &Address,
+) -> Result<i64, sqlx::Error> {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    sqlx::query_scalar(
+        "INSERT INTO delivery_queue (mail_id, recipient, token)
+            VALUES (?, ?, ?)
+            RETURNING id",
+    )
+    .bind(mail_id)
+    .bind(recipient.to_string())
+    .bind(hex::encode(bytes))
+    .fetch_one(conn)
+    .await
+}
+
+/// Wakes the worker up to attempt every due mail right away.
+pub fn wake() {
+    WORKER.wake();
+}
+
+/// Runs the worker, which never returns.
+///
+/// Every due mail is attempted once per round, see `worker`.
+pub async fn run(app: AppContextGuard) {
+    WORKER.run::<QueueRound>(app).await;
+}
+
+struct QueueRound {
+    pool: SqlitePool,
+    events: Events,
+    origin: String,
+    cfg: DeliveryConfig,
+    relay: Option<RelayConfig>,
+}
+
+impl worker::Round for QueueRound {
+    const NAME: &'static str = "delivery queue";
+
+    const NEXT_DUE: &'static str =
+        "SELECT MAX(0, UNIXEPOCH(MIN(COALESCE(next_attempt_at, CURRENT_TIMESTAMP))) - UNIXEPOCH())
+            FROM delivery_queue WHERE status = 'pending'";
+
+    async fn load(app: &AppContextGuard) -> Self {
+        let ctx = app.ctx().await;
+        let cfg = ctx.cfg().await;
+
+        Self {
+            pool: ctx.pool().await.clone(),
+            events: ctx.events().clone(),
+            origin: cfg.pub_addr().await.clone(),
+            cfg: cfg.delivery().await.clone(),
+            relay: cfg.relay().await.clone(),
+        }
+    }
+
+    fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    /// Attempts every pending mail whose next attempt is due, relaying
+    /// it through `relay` if it is set and `relay::handles` the recipient.
+    async fn process(&self) -> Result<(), sqlx::Error> {
+        let due: Vec<(i64, String, String)> = sqlx::query_as(
+            "SELECT id, recipient, token FROM delivery_queue
+                WHERE status = 'pending'
+                  AND (next_attempt_at IS NULL OR next_attempt_at <= CURRENT_TIMESTAMP)
+                ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut attempts = Attempts::default();
+
+        for (id, recipient, token) in due {
+            let (pool, events, origin, cfg, relay_cfg) = (
+                self.pool.clone(),
+                self.events.clone(),
+                self.origin.clone(),
+                self.cfg.clone(),
+                self.relay.clone(),
+            );
+
+            attempts
+                .spawn(async move {
+                    let attempt = match recipient.parse::<Address>() {
+                        Ok(recipient) => match relay_cfg {
+                            Some(relay_cfg) if relay::handles(&recipient, &origin) => {
+                                let hostname = address::host_name(&origin);
+                                relay::push(&pool, &relay_cfg, hostname, id, &recipient).await
+                            }
+                            _ => federation::push(&origin, &recipient, &token).await,
+                        },
+                        Err(e) => Attempt {
+                            status_code: None,
+                            outcome: Outcome::Fail(e),
+                        },
+                    };
+
+                    record(&pool, &events, &cfg, &origin, id, attempt).await
+                })
+                .await;
+        }
+
+        attempts.join().await;
+
+        Ok(())
+    }
+}
+
+/// Records `attempt` at delivering the queued mail `id` in `delivery_attempts`
+/// and settles it, see `worker::settle`, bouncing it if it was given up on.
+#[instrument(skip(pool, events, cfg, origin))]
+async fn record(
+    pool: &SqlitePool,
+    events: &Events,
+    cfg: &DeliveryConfig,
+    origin: &str,
+    id: i64,
+    attempt: Attempt,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("INSERT INTO delivery_attempts (queue_id, status_code, error) VALUES (?, ?, ?)")
+        .bind(id)
+        .bind(attempt.status_code)
+        .bind(attempt.outcome.error())
+        .execute(&mut *tx)
+        .await?;
+
+    let bounce = match worker::settle(&mut tx, "delivery_queue", cfg, id, attempt.outcome).await? {
+        Settled::Failed(reason) => delivery::bounce(&mut tx, origin, id, &reason).await?,
+        Settled::Delivered | Settled::Pending => None,
+    };
+
+    tx.commit().await?;
+
+    if let Some((bounce_id, user_id)) = bounce {
+        events.publish(user_id, EventPayload::Received { id: bounce_id });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+//! Relaying mail for regular email addresses through an SMTP smarthost.
+//!
+//! Recipients whose host has no port (e.g, `user@example.com`) cannot be
+//! NasoMail servers, whose `pub_addr` always carries one, so while a
+//! `RelayConfig` is set they are handed to the smarthost instead of being
+//! delivered through `federation`. Both go through the `queue` alike.
+
+use std::time::Duration;
+
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+    address::Envelope,
+    transport::smtp::{
+        authentication::Credentials,
+        client::{Tls, TlsParameters},
+        extension::ClientId,
+    },
+};
+use sqlx::sqlite::SqlitePool;
+use tracing::instrument;
+
+use nasomail_shared::address::Address;
+
+use crate::{
+    config::RelayConfig,
+    delivery::{self, Mail},
+    message,
+    queue::{Attempt, Outcome},
+};
+
+/// How long to wait for the smarthost before retrying later.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Returns whether mail for `recipient` should be relayed rather than
+/// delivered to another NasoMail server, by the server at `origin`.
+///
+/// Mail for the host of `origin` is never relayed, see `Address::is_local`.
+pub fn handles(recipient: &Address, origin: &str) -> bool {
+    !recipient.is_local(origin)
+        && recipient
+            .host
+            .as_deref()
+            .is_some_and(|host| !host.contains(':'))
+}
+
+/// Renders the mail queued as `queue_id` and hands it to the smarthost
+/// of `cfg`, as the server whose `pub_addr` has the host `hostname`.
+///
+/// Connection failures and transient (`4xx`) replies are worth
+/// retrying, permanent (`5xx`) replies are not.
+#[instrument(skip(pool, cfg))]
+pub async fn push(
+    pool: &SqlitePool,
+    cfg: &RelayConfig,
+    hostname: &str,
+    queue_id: i64,
+    recipient: &Address,
+) -> Attempt {
+    let (envelope, raw) = match prepare(pool, hostname, queue_id, recipient).await {
+        Ok(prepared) => prepared,
+        Err(outcome) => {
+            return Attempt {
+                status_code: None,
+                outcome,
+            };
+        }
+    };
+
+    let mut transport = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&cfg.host)
+        .port(cfg.port)
+        .timeout(Some(TIMEOUT))
+        .hello_name(ClientId::Domain(hostname.to_owned()));
+
+    if cfg.starttls {
+        match TlsParameters::new(cfg.host.clone()) {
+            Ok(parameters) => transport = transport.tls(Tls::Required(parameters)),
+            Err(e) => {
+                return Attempt {
+                    status_code: None,
+                    outcome: Outcome::Retry(format!("invalid tls parameters: {e}")),
+                };
+            }
+        }
+    }
+
+    if let (Some(username), Some(password)) = (&cfg.username, &cfg.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+
+    match transport.build().send_raw(&envelope, &raw).await {
+        Ok(response) => Attempt {
+            status_code: Some(u16::from(response.code())),
+            outcome: Outcome::Delivered,
+        },
+        Err(e) => {
+            let error = format!("relaying through {} failed: {e}", cfg.host);
+
+            Attempt {
+                status_code: e.status().map(u16::from),
+                outcome: if e.is_permanent() {
+                    Outcome::Fail(error)
+                } else {
+                    Outcome::Retry(error)
+                },
+            }
+        }
+    }
+}
+
+/// Loads the mail queued as `queue_id` along with its attachments
+/// and renders it for `recipient`.
+///
+/// # Errors
+///
+/// Returns `Err(Retry)` if the database could not be queried.
+/// Returns `Err(Fail)`  if the mail cannot be relayed at all.
+///
+async fn prepare(
+    pool: &SqlitePool,
+    hostname: &str,
+    queue_id: i64,
+    recipient: &Address,
+) -> Result<(Envelope, Vec<u8>), Outcome> {
+    let retry = |e: sqlx::Error| Outcome::Retry(format!("database error: {e}"));
+
+    let (mail_id, sender, subject, body, message_id, in_reply_to, refs, auto_submitted, date): (
+        i64,
+        String,
+        String,
+        String,
+        String,
+        Option<String>,
+        String,
+        bool,
+        i64,
+    ) = sqlx::query_as(
+        "SELECT mails.id, mails.sender, mails.subject, mails.body, mails.message_id,
+                mails.in_reply_to, mails.refs, mails.auto_submitted, UNIXEPOCH(mails.created_at)
+            FROM delivery_queue
+            JOIN mails ON mails.id = delivery_queue.mail_id
+            WHERE delivery_queue.id = ?",
+    )
+    .bind(queue_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(retry)?
+    .ok_or_else(|| Outcome::Fail("the mail no longer exists".to_owned()))?;
+
+    let attachments = message::attachments(pool, mail_id)
+        .await
+        .map_err(retry)?
+        .ok_or_else(|| Outcome::Fail("the attachments are too large to relay".to_owned()))?;
+
+    let mail = Mail {
+        sender: message::qualify(&sender, hostname),
+        recipient: recipient.to_string(),
+        subject,
+        body,
+        message_id,
+        in_reply_to,
+        references: delivery::split_references(&refs),
+        auto_submitted,
+    };
+
+    let envelope = match (mail.sender.parse(), mail.recipient.parse()) {
+        (Ok(from), Ok(to)) => Envelope::new(Some(from), vec![to]),
+        (Err(e), _) | (_, Err(e)) => return Err(Outcome::Fail(format!("invalid address: {e}"))),
+    }
+    .map_err(|e| Outcome::Fail(format!("invalid envelope: {e}")))?;
+
+    let raw = message::render(&mail, date, &attachments, hostname)
+        .map_err(|e| Outcome::Fail(format!("failed to render mail: {e}")))?;
+
+    Ok((envelope, raw))
+}
@@ -0,0 +1,183 @@
+//! Sending drafts later, either at a time chosen by their sender or once
+//! the undo window of `Config::undo_send_secs` has passed.
+//!
+//! A mail that is sent later stays a `draft` with a row in `send_jobs` until
+//! a background worker started from `main`, see `worker`, sends it through
+//! `delivery`, so it can still be edited and cancelling its job turns it back
+//! into a regular draft. All of its state lives in the database, so jobs that
+//! became due while the server was down are sent once it is back up.
+
+use sqlx::{SqliteConnection, sqlite::SqlitePool};
+use tracing::{info, instrument, warn};
+
+use nasomail_shared::payload::event::EventPayload;
+
+use crate::{
+    app::AppContextGuard,
+    delivery::{self, DeliveryError},
+    events::Events,
+    worker::{self, Worker},
+};
+
+static WORKER: Worker = Worker::new();
+
+/// Schedules the draft `mail_id` of `user_id`, whose recipient is `recipient`,
+/// to be sent by the server at `origin` at `send_at`, an SQLite `DATETIME`,
+/// or in `undo_secs` seconds if it is `None`. A draft that was already
+/// scheduled is rescheduled.
+///
+/// Returns when the draft is going to be sent. Call `wake` once the
+/// surrounding transaction has been committed.
+///
+/// # Errors
+///
+/// Returns `Err(InvalidRecipient)` if `recipient` is not a valid address.
+/// Returns `Err(UnknownRecipient)` if `recipient` is local but not a registered user.
+/// Returns `Err(Database)`         if the database could not be queried.
+///
+pub async fn submit(
+    conn: &mut SqliteConnection,
+    origin: &str,
+    user_id: i64,
+    mail_id: i64,
+    recipient: &str,
+    send_at: Option<&str>,
+    undo_secs: u64,
+) -> Result<String, DeliveryError> {
+    // Check the recipient right away, rather than only once it is too late to tell
+    delivery::check_recipient(conn, origin, recipient).await?;
+
+    let run_at: String = sqlx::query_scalar(
+        "INSERT INTO send_jobs (mail_id, user_id, run_at)
+            VALUES (?, ?, COALESCE(
+                ?,
+                DATETIME('now', '+' || ? || ' seconds'),
+                -- `DATETIME` is NULL for windows too long to ever run out
+                '9999-12-31 23:59:59'
+            ))
+            ON CONFLICT (mail_id) DO UPDATE SET run_at = excluded.run_at
+            RETURNING run_at",
+    )
+    .bind(mail_id)
+    .bind(user_id)
+    .bind(send_at)
+    .bind(i64::try_from(undo_secs).unwrap_or(i64::MAX))
+    .fetch_one(conn)
+    .await?;
+
+    info!(mail_id = mail_id, run_at = run_at, "scheduled");
+
+    Ok(run_at)
+}
+
+/// Wakes the worker up to send every due draft right away.
+pub fn wake() {
+    WORKER.wake();
+}
+
+/// Runs the worker, which never returns.
+///
+/// Every due draft is sent once per round, see `worker`.
+pub async fn run(app: AppContextGuard) {
+    WORKER.run::<ScheduleRound>(app).await;
+}
+
+struct ScheduleRound {
+    pool: SqlitePool,
+    events: Events,
+    origin: String,
+}
+
+impl worker::Round for ScheduleRound {
+    const NAME: &'static str = "send jobs";
+
+    const NEXT_DUE: &'static str =
+        "SELECT MAX(0, UNIXEPOCH(MIN(run_at)) - UNIXEPOCH()) FROM send_jobs";
+
+    async fn load(app: &AppContextGuard) -> Self {
+        let ctx = app.ctx().await;
+
+        Self {
+            pool: ctx.pool().await.clone(),
+            events: ctx.events().clone(),
+            origin: ctx.cfg().await.pub_addr().await.clone(),
+        }
+    }
+
+    fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    /// Sends every draft whose job is due, oldest first.
+    async fn process(&self) -> Result<(), sqlx::Error> {
+        let due: Vec<(i64, i64)> = sqlx::query_as(
+            "SELECT mail_id, user_id FROM send_jobs
+                WHERE run_at <= CURRENT_TIMESTAMP
+                ORDER BY run_at, mail_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        for (mail_id, user_id) in due {
+            send(&self.pool, &self.events, &self.origin, mail_id, user_id).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends the due draft `mail_id` of `user_id`, bouncing it back
+/// into their inbox if it cannot be sent.
+///
+/// Jobs whose draft is gone, e.g, because it was sent, moved to the trash
+/// or its job was cancelled in the meantime, are dropped. Jobs that fail
+/// because of the database are left as they are, to be retried.
+#[instrument(skip(pool, events, origin))]
+async fn send(
+    pool: &SqlitePool,
+    events: &Events,
+    origin: &str,
+    mail_id: i64,
+    user_id: i64,
+) -> Result<(), sqlx::Error> {
+    let reason = match delivery::send_draft(pool, events, origin, user_id, mail_id, true).await {
+        Ok(delivered) => {
+            info!(sent_id = delivered.sent_id, "sent scheduled draft");
+            return Ok(());
+        }
+        Err(DeliveryError::Database(e)) => {
+            warn!(err = ?e, "failed to send scheduled draft, retrying later");
+            return Ok(());
+        }
+        Err(DeliveryError::NoSuchDraft(_)) => {
+            sqlx::query("DELETE FROM send_jobs WHERE mail_id = ? AND run_at <= CURRENT_TIMESTAMP")
+                .bind(mail_id)
+                .execute(pool)
+                .await?;
+
+            return Ok(());
+        }
+        Err(DeliveryError::Rejected(reason)) => reason,
+        Err(e) => e.to_string(),
+    };
+
+    warn!(reason = reason, "giving up on scheduled draft");
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM send_jobs WHERE mail_id = ?")
+        .bind(mail_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let bounce = delivery::bounce_mail(&mut tx, origin, mail_id, &reason).await?;
+
+    tx.commit().await?;
+
+    if let Some((bounce_id, user_id)) = bounce {
+        info!(id = bounce_id, "bounced");
+        events.publish(user_id, EventPayload::Received { id: bounce_id });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! Creation, lookup and revocation of the
+//! bearer tokens stored in the `sessions` table.
+//!
+//! Only a SHA-256 digest of each token is stored,
+//! so a leaked database does not leak usable tokens.
+
+use password_hash::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqlitePool;
+
+use nasomail_shared::payload::auth::SessionPayload;
+
+/// The user a bearer token belongs to.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: i64,
+    pub user_id: i64,
+    pub user_name: String,
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Creates a new session for `user_id` that expires
+/// after `lifetime_secs` seconds and returns its token.
+///
+/// Expired sessions of the same user are removed along the way.
+///
+/// # Errors
+///
+/// Returns `Err` if the database could not be queried.
+///
+pub async fn create(
+    pool: &SqlitePool,
+    user_id: i64,
+    lifetime_secs: u64,
+) -> Result<SessionPayload, sqlx::Error> {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token = hex::encode(bytes);
+
+    sqlx::query("DELETE FROM sessions WHERE user_id = ? AND expires_at <= CURRENT_TIMESTAMP")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    let expires_at: String = sqlx::query_scalar(
+        "INSERT INTO sessions (user_id, token_hash, expires_at)
+            VALUES (?, ?, DATETIME('now', '+' || ? || ' seconds'))
+            RETURNING expires_at",
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(lifetime_secs as i64)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(SessionPayload { token, expires_at })
+}
+
+/// Looks up the unexpired session that `token` belongs to.
+///
+/// # Errors
+///
+/// Returns `Err` if the database could not be queried.
+///
+pub async fn lookup(pool: &SqlitePool, token: &str) -> Result<Option<Session>, sqlx::Error> {
+    let row: Option<(i64, i64, String)> = sqlx::query_as(
+        "SELECT sessions.id, users.id, users.name FROM sessions
+            JOIN users ON users.id = sessions.user_id
+            WHERE sessions.token_hash = ? AND sessions.expires_at > CURRENT_TIMESTAMP",
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|(id, user_id, user_name)| Session {
+        id,
+        user_id,
+        user_name,
+    }))
+}
+
+/// Revokes the session with the given `id`.
+///
+/// # Errors
+///
+/// Returns `Err` if the database could not be queried.
+///
+pub async fn revoke(pool: &SqlitePool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM sessions WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,536 @@
+//! Running a `Script` against a mail.
+
+use crate::sieve::parser::{
+    AddressPart, Command, Comparator, Header, MatchKind, Matcher, Script, Test,
+};
+
+/// How many different addresses a script may redirect a single mail to.
+pub const MAX_REDIRECTS: usize = 4;
+
+/// The parts of a mail that a script can look at.
+#[derive(Debug, Clone, Copy)]
+pub struct Message<'a> {
+    pub sender: &'a str,
+    pub recipient: &'a str,
+    pub subject: &'a str,
+    pub body: &'a str,
+
+    /// The size of the body plus the sizes of all attachments, in bytes.
+    pub size: u64,
+}
+
+impl Message<'_> {
+    fn header(&self, header: Header) -> &str {
+        match header {
+            Header::From => self.sender,
+            Header::To => self.recipient,
+            Header::Subject => self.subject,
+        }
+    }
+}
+
+/// What a script decided to do with a mail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// Whether to keep the mail, i.e, either it was kept explicitly, or
+    /// filed into a label, or nothing cancelled the implicit keep.
+    /// Mails that are not kept go to the trash.
+    pub keep: bool,
+
+    /// Whether the mail is marked as read.
+    pub seen: bool,
+
+    /// The names of the labels to add to the mail.
+    pub labels: Vec<String>,
+
+    /// The addresses to forward the mail to.
+    pub redirects: Vec<String>,
+
+    /// The reason to reject the mail for, which
+    /// overrides every other action if it is set.
+    pub reject: Option<String>,
+}
+
+impl Default for Outcome {
+    /// What happens to a mail when there is no script, it is kept.
+    fn default() -> Self {
+        Self {
+            keep: true,
+            seen: false,
+            labels: Vec::new(),
+            redirects: Vec::new(),
+            reject: None,
+        }
+    }
+}
+
+/// Runs `script` against `message`.
+pub fn evaluate(script: &Script, message: &Message) -> Outcome {
+    let mut state = State {
+        implicit_keep: true,
+        explicit_keep: false,
+        outcome: Outcome::default(),
+    };
+
+    state.run(&script.commands, message);
+
+    let mut outcome = state.outcome;
+    outcome.keep = state.explicit_keep || state.implicit_keep || !outcome.labels.is_empty();
+    outcome
+}
+
+struct State {
+    implicit_keep: bool,
+    explicit_keep: bool,
+    outcome: Outcome,
+}
+
+impl State {
+    /// Runs `commands` and returns `false` once a `stop` was run.
+    fn run(&mut self, commands: &[Command], message: &Message) -> bool {
+        for command in commands {
+            match command {
+                Command::If {
+                    branches,
+                    otherwise,
+                } => {
+                    let block = branches
+                        .iter()
+                        .find(|(test, _)| test_matches(test, message))
+                        .map(|(_, block)| block)
+                        .or(otherwise.as_ref());
+
+                    if let Some(block) = block
+                        && !self.run(block, message)
+                    {
+                        return false;
+                    }
+                }
+                Command::Stop => return false,
+                Command::Keep => self.explicit_keep = true,
+                Command::Discard => self.implicit_keep = false,
+                Command::FileInto(label) => {
+                    self.implicit_keep = false;
+
+                    let labels = &mut self.outcome.labels;
+                    if !labels.iter().any(|other| other.eq_ignore_ascii_case(label)) {
+                        labels.push(label.clone());
+                    }
+                }
+                Command::Redirect(address) => {
+                    self.implicit_keep = false;
+
+                    let redirects = &mut self.outcome.redirects;
+                    if redirects.len() < MAX_REDIRECTS
+                        && !redirects
+                            .iter()
+                            .any(|other| other.eq_ignore_ascii_case(address))
+                    {
+                        redirects.push(address.clone());
+                    }
+                }
+                Command::Reject(reason) => {
+                    self.implicit_keep = false;
+                    self.outcome.reject.get_or_insert_with(|| reason.clone());
+                }
+                Command::Seen(seen) => self.outcome.seen = *seen,
+            }
+        }
+
+        true
+    }
+}
+
+fn test_matches(test: &Test, message: &Message) -> bool {
+    match test {
+        Test::True => true,
+        Test::False => false,
+        Test::Not(test) => !test_matches(test, message),
+        Test::AnyOf(tests) => tests.iter().any(|test| test_matches(test, message)),
+        Test::AllOf(tests) => tests.iter().all(|test| test_matches(test, message)),
+        Test::Address {
+            part,
+            matcher,
+            headers,
+            keys,
+        } => headers.iter().any(|header| {
+            let address = message.header(*header);
+            let (local, domain) = address.split_once('@').unwrap_or((address, ""));
+
+            let value = match part {
+                AddressPart::All => address,
+                AddressPart::LocalPart => local,
+                AddressPart::Domain => domain,
+            };
+
+            any_key_matches(matcher, value, keys)
+        }),
+        Test::Header {
+            matcher,
+            headers,
+            keys,
+        } => headers
+            .iter()
+            .any(|header| any_key_matches(matcher, message.header(*header), keys)),
+        Test::Body { matcher, keys } => any_key_matches(matcher, message.body, keys),
+        Test::Exists(headers) => headers
+            .iter()
+            .all(|header| !message.header(*header).is_empty()),
+        Test::Size { over, limit } => {
+            if *over {
+                message.size > *limit
+            } else {
+                message.size < *limit
+            }
+        }
+    }
+}
+
+fn any_key_matches(matcher: &Matcher, value: &str, keys: &[String]) -> bool {
+    let fold = |s: &str| match matcher.comparator {
+        Comparator::AsciiCasemap => s.to_ascii_lowercase(),
+        Comparator::Octet => s.to_owned(),
+    };
+
+    let value = fold(value);
+
+    keys.iter().any(|key| {
+        let key = fold(key);
+
+        match matcher.kind {
+            MatchKind::Is => value == key,
+            MatchKind::Contains => value.contains(&key),
+            MatchKind::Matches => wildcard(&key, &value),
+        }
+    })
+}
+
+/// Matches `value` against `pattern`, in which `*` matches any number of
+/// characters, `?` matches a single one and `\` escapes the next one.
+fn wildcard(pattern: &str, value: &str) -> bool {
+    enum Part {
+        Any,
+        One,
+        Char(char),
+    }
+
+    let mut parts = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        parts.push(match c {
+            '*' => Part::Any,
+            '?' => Part::One,
+            '\\' => Part::Char(chars.next().unwrap_or('\\')),
+            c => Part::Char(c),
+        });
+    }
+
+    let value: Vec<char> = value.chars().collect();
+
+    // Greedy matching that backtracks to the last `*`
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack = None;
+
+    while v < value.len() {
+        match parts.get(p) {
+            Some(Part::Any) => {
+                backtrack = Some((p, v));
+                p += 1;
+            }
+            Some(Part::One) => {
+                p += 1;
+                v += 1;
+            }
+            Some(Part::Char(c)) if *c == value[v] => {
+                p += 1;
+                v += 1;
+            }
+            _ => match backtrack {
+                Some((star, from)) => {
+                    backtrack = Some((star, from + 1));
+                    p = star + 1;
+                    v = from + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    parts[p..].iter().all(|part| matches!(part, Part::Any))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve;
+
+    fn message() -> Message<'static> {
+        Message {
+            sender: "alice@example.com",
+            recipient: "bob",
+            subject: "Quarterly Report Q3",
+            body: "Hi Bob,\nthe numbers are attached.\n",
+            size: 2048,
+        }
+    }
+
+    fn run(script: &str) -> Outcome {
+        let script = sieve::compile(script).unwrap_or_else(|e| panic!("{script}: {e:?}"));
+        evaluate(&script, &message())
+    }
+
+    /// Whether `test` matches, i.e, the mail is discarded by `if test { discard; }`.
+    fn matches(test: &str) -> bool {
+        let script = format!(r#"require ["body", "comparator-i;octet"]; if {test} {{ discard; }}"#);
+        !run(&script).keep
+    }
+
+    fn errors(script: &str) -> Vec<(u32, String)> {
+        sieve::compile(script)
+            .expect_err(script)
+            .into_iter()
+            .map(|e| (e.line, e.message))
+            .collect()
+    }
+
+    #[test]
+    fn empty_script_keeps() {
+        let outcome = run("");
+
+        assert!(outcome.keep);
+        assert!(!outcome.seen);
+        assert!(outcome.labels.is_empty());
+        assert!(outcome.redirects.is_empty());
+        assert_eq!(outcome.reject, None);
+    }
+
+    #[test]
+    fn discard_cancels_implicit_keep() {
+        assert!(!run("discard;").keep);
+        assert!(run("discard; keep;").keep);
+    }
+
+    #[test]
+    fn fileinto_labels_and_keeps() {
+        let outcome = run(r#"
+            require "fileinto";
+            fileinto "Reports";
+            fileinto "reports";
+            fileinto "Work";
+        "#);
+
+        assert!(outcome.keep);
+        assert_eq!(outcome.labels, vec!["Reports", "Work"]);
+    }
+
+    #[test]
+    fn redirect_without_keep_trashes() {
+        let outcome = run(r#"redirect "carol@127.0.0.1:8081";"#);
+
+        assert!(!outcome.keep);
+        assert_eq!(outcome.redirects, vec!["carol@127.0.0.1:8081"]);
+
+        assert!(run(r#"redirect "carol"; keep;"#).keep);
+    }
+
+    #[test]
+    fn redirects_are_limited() {
+        let outcome = run(r#"
+            redirect "a"; redirect "b"; redirect "A"; redirect "c"; redirect "d"; redirect "e";
+        "#);
+
+        assert_eq!(outcome.redirects, vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn reject_keeps_first_reason() {
+        let outcome = run(r#"
+            require "reject";
+            reject "first";
+            reject "second";
+        "#);
+
+        assert!(!outcome.keep);
+        assert_eq!(outcome.reject.as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn flags_mark_as_read() {
+        assert!(run(r#"require "imap4flags"; addflag "\\Seen";"#).seen);
+        assert!(run(r#"require "imap4flags"; setflag ["\\seen"];"#).seen);
+        assert!(!run(r#"require "imap4flags"; addflag "\\Seen"; removeflag "\\Seen";"#).seen);
+    }
+
+    #[test]
+    fn stop_ends_the_script() {
+        let outcome = run(r#"
+            require "fileinto";
+            if true { fileinto "A"; stop; }
+            fileinto "B";
+        "#);
+
+        assert_eq!(outcome.labels, vec!["A"]);
+    }
+
+    #[test]
+    fn if_elsif_else_picks_first_match() {
+        let script = r#"
+            require "fileinto";
+            if header :contains "subject" "invoice" { fileinto "Invoices"; }
+            elsif header :contains "subject" "report" { fileinto "Reports"; }
+            else { fileinto "Other"; }
+        "#;
+
+        assert_eq!(run(script).labels, vec!["Reports"]);
+    }
+
+    #[test]
+    fn match_types() {
+        assert!(matches(r#"header :is "subject" "quarterly report q3""#));
+        assert!(!matches(r#"header :is "subject" "Quarterly""#));
+        assert!(matches(r#"header :contains "subject" "REPORT""#));
+        assert!(matches(r#"header :matches "subject" "Quarterly*Q?""#));
+        assert!(!matches(r#"header :matches "subject" "*Q""#));
+        assert!(matches(r#"header :matches "subject" "*""#));
+        assert!(matches(r#"header :is ["to", "subject"] ["nobody", "BOB"]"#));
+    }
+
+    #[test]
+    fn wildcard_escapes() {
+        assert!(wildcard("a\\*b", "a*b"));
+        assert!(!wildcard("a\\*b", "axb"));
+        assert!(wildcard("*a*a*", "banana"));
+        assert!(!wildcard("a?", "a"));
+    }
+
+    #[test]
+    fn comparators() {
+        assert!(matches(
+            r#"header :comparator "i;octet" :contains "subject" "Report""#
+        ));
+        assert!(!matches(
+            r#"header :comparator "i;octet" :contains "subject" "report""#
+        ));
+        assert!(matches(
+            r#"header :comparator "i;ascii-casemap" :is "to" "BOB""#
+        ));
+    }
+
+    #[test]
+    fn address_parts() {
+        assert!(matches(r#"address :is "from" "alice@example.com""#));
+        assert!(matches(r#"address :localpart :is "from" "ALICE""#));
+        assert!(matches(r#"address :domain :is "from" "example.com""#));
+        assert!(matches(r#"address :domain :is "to" """#));
+        assert!(!matches(r#"address :localpart :is "from" "example.com""#));
+    }
+
+    #[test]
+    fn body_size_and_exists() {
+        assert!(matches(r#"body :contains "numbers""#));
+        assert!(matches(r#"body :text :contains ["nothing", "ATTACHED"]"#));
+        assert!(!matches(r#"body :is "numbers""#));
+        assert!(matches("size :over 1K"));
+        assert!(!matches("size :over 2k"));
+        assert!(!matches("size :under 2048"));
+        assert!(matches("size :under 1M"));
+        assert!(matches(r#"exists ["from", "subject"]"#));
+    }
+
+    #[test]
+    fn logical_tests() {
+        assert!(matches("anyof (false, true)"));
+        assert!(!matches("allof (false, true)"));
+        assert!(matches("allof (true, not false)"));
+        assert!(matches(r#"not header :is "to" "carol""#));
+    }
+
+    #[test]
+    fn multi_line_strings() {
+        let outcome =
+            run("require \"reject\";\nreject text:\r\nGone fishing.\n..and back soon\n.\n;");
+
+        assert_eq!(
+            outcome.reject.as_deref(),
+            Some("Gone fishing.\n.and back soon\n")
+        );
+    }
+
+    #[test]
+    fn comments_are_ignored() {
+        let outcome =
+            run("# hash\nrequire \"fileinto\"; /* a\nblock */ fileinto \"A\"; # trailing");
+
+        assert_eq!(outcome.labels, vec!["A"]);
+    }
+
+    #[test]
+    fn syntax_errors_have_lines() {
+        assert_eq!(
+            errors("keep;\nif true {\n  keep;\n"),
+            vec![(3, "expected '}'".to_owned())]
+        );
+        assert_eq!(errors("keep\n"), vec![(1, "expected ';'".to_owned())]);
+        assert_eq!(
+            errors("\n\n  \"unterminated"),
+            vec![(3, "unterminated string".to_owned())]
+        );
+        assert_eq!(
+            errors("keep;\n/* open"),
+            vec![(2, "unterminated comment".to_owned())]
+        );
+        assert_eq!(
+            errors("if header :is [\"subject\" \"x\"] { keep; }"),
+            vec![(1, "expected ']' or ','".to_owned())]
+        );
+    }
+
+    #[test]
+    fn semantic_errors_are_collected() {
+        let errors = errors(
+            "fileinto \"A\";\n\
+             frobnicate;\n\
+             require \"fileinto\";\n\
+             if header :is \"x-spam\" \"yes\" { keep; }\n\
+             require \"vacation\";",
+        );
+
+        assert_eq!(
+            errors,
+            vec![
+                (1, "missing 'require \"fileinto\"'".to_owned()),
+                (2, "unknown command 'frobnicate'".to_owned()),
+                (3, "'require' must come before any other command".to_owned()),
+                (4, "unsupported header \"x-spam\"".to_owned()),
+                (5, "'require' must come before any other command".to_owned()),
+                (5, "unsupported capability \"vacation\"".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_arguments_are_errors() {
+        assert_eq!(
+            errors("require \"imap4flags\";\naddflag \"\\\\Flagged\";")[0].0,
+            2
+        );
+        assert_eq!(errors("size 10;")[0].1, "unknown command 'size'");
+        assert_eq!(
+            errors("if size 10 { keep; }")[0].1,
+            "'size' expects ':over' or ':under' and a number"
+        );
+        assert_eq!(
+            errors("if header :is :contains \"to\" \"x\" { keep; }")[0].1,
+            "unexpected ':contains' for 'header'"
+        );
+        assert_eq!(
+            errors("redirect \"not an address\";")[0].1,
+            "invalid address \"not an address\""
+        );
+        assert_eq!(
+            errors("else { keep; }")[0].1,
+            "'else' must follow 'if' or 'elsif'"
+        );
+        assert_eq!(errors("if true;")[0].1, "expected '{'");
+    }
+}
@@ -0,0 +1,278 @@
+//! Splitting a script into tokens (RFC 5228, section 8.1).
+
+use crate::sieve::SieveError;
+
+/// An enum of the lexical elements of a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    /// An identifier, i.e, the name of a command or test.
+    Identifier(String),
+
+    /// A tagged argument without its leading `:`, e.g, `is` for `:is`.
+    Tag(String),
+
+    /// A number with its quantifier (`K`, `M` or `G`) already applied.
+    Number(u64),
+
+    /// A quoted or multi-line string, with escapes and dot-stuffing removed.
+    String(String),
+
+    LeftBracket,
+    RightBracket,
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Comma,
+    Semicolon,
+}
+
+/// A token along with where it starts, both counted from `1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: u32,
+    pub column: u32,
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: u32,
+    column: u32,
+}
+
+impl Lexer<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn error(&self, message: impl Into<String>) -> SieveError {
+        SieveError::new(self.line, self.column, message)
+    }
+
+    /// Skips whitespace and comments.
+    fn skip(&mut self) -> Result<(), SieveError> {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' | '\n' => {
+                    self.next();
+                }
+                '#' => while self.next().is_some_and(|c| c != '\n') {},
+                '/' => {
+                    let (line, column) = (self.line, self.column);
+                    self.next();
+
+                    if self.next() != Some('*') {
+                        return Err(SieveError::new(line, column, "unexpected '/'"));
+                    }
+
+                    let mut star = false;
+                    loop {
+                        match self.next() {
+                            Some('/') if star => break,
+                            Some(c) => star = c == '*',
+                            None => {
+                                return Err(SieveError::new(line, column, "unterminated comment"));
+                            }
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn quoted(&mut self) -> Result<String, SieveError> {
+        let (line, column) = (self.line, self.column);
+        self.next();
+
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(value),
+                // Only `\"` and `\\` mean anything, any other escaped character stands for itself
+                Some('\\') => match self.next() {
+                    Some(c) => value.push(c),
+                    None => break,
+                },
+                Some(c) => value.push(c),
+                None => break,
+            }
+        }
+
+        Err(SieveError::new(line, column, "unterminated string"))
+    }
+
+    /// Reads the rest of a `text:` string, right after the `:`.
+    fn multi_line(&mut self, line: u32, column: u32) -> Result<String, SieveError> {
+        // Only whitespace or a `#` comment may follow `text:` on its line
+        loop {
+            match self.next() {
+                Some(' ' | '\t' | '\r') => {}
+                Some('\n') => break,
+                Some('#') => {
+                    while self.next().is_some_and(|c| c != '\n') {}
+                    break;
+                }
+                _ => return Err(self.error("expected a line break after 'text:'")),
+            }
+        }
+
+        let mut value = String::new();
+        loop {
+            let mut current = String::new();
+            let mut terminated = false;
+
+            while let Some(c) = self.next() {
+                if c == '\n' {
+                    terminated = true;
+                    break;
+                }
+                current.push(c);
+            }
+
+            let current = current.strip_suffix('\r').unwrap_or(&current);
+
+            if current == "." {
+                return Ok(value);
+            }
+            if !terminated {
+                return Err(SieveError::new(
+                    line,
+                    column,
+                    "unterminated multi-line string",
+                ));
+            }
+
+            // Lines starting with a `.` are stuffed with another one
+            value.push_str(current.strip_prefix('.').unwrap_or(current));
+            value.push('\n');
+        }
+    }
+
+    fn number(&mut self) -> Result<u64, SieveError> {
+        let (line, column) = (self.line, self.column);
+
+        let mut value: u64 = 0;
+        while let Some(digit) = self.peek().and_then(|c| c.to_digit(10)) {
+            self.next();
+            value = value
+                .checked_mul(10)
+                .and_then(|value| value.checked_add(digit as u64))
+                .ok_or_else(|| SieveError::new(line, column, "number is too large"))?;
+        }
+
+        let shift = match self.peek() {
+            Some('K' | 'k') => 10,
+            Some('M' | 'm') => 20,
+            Some('G' | 'g') => 30,
+            _ => 0,
+        };
+        if shift > 0 {
+            self.next();
+        }
+
+        value
+            .checked_mul(1 << shift)
+            .ok_or_else(|| SieveError::new(line, column, "number is too large"))
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_alphanumeric() || c == '_') {
+                break;
+            }
+            word.push(c);
+            self.next();
+        }
+        word
+    }
+}
+
+/// Splits `script` into `Token`s.
+///
+/// # Errors
+///
+/// Returns `Err` at the first character that does not start a token
+/// and at strings or comments that are never terminated.
+///
+pub fn tokenize(script: &str) -> Result<Vec<Token>, SieveError> {
+    let mut lexer = Lexer {
+        chars: script.chars().peekable(),
+        line: 1,
+        column: 1,
+    };
+
+    let mut tokens = Vec::new();
+
+    loop {
+        lexer.skip()?;
+
+        let (line, column) = (lexer.line, lexer.column);
+        let Some(c) = lexer.peek() else {
+            return Ok(tokens);
+        };
+
+        let kind = match c {
+            '"' => TokenKind::String(lexer.quoted()?),
+            '0'..='9' => TokenKind::Number(lexer.number()?),
+            ':' => {
+                lexer.next();
+                let tag = lexer.word();
+
+                if tag.is_empty() || !tag.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    return Err(SieveError::new(line, column, "expected a tag after ':'"));
+                }
+                TokenKind::Tag(tag.to_ascii_lowercase())
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let word = lexer.word();
+
+                if word.eq_ignore_ascii_case("text") && lexer.peek() == Some(':') {
+                    lexer.next();
+                    TokenKind::String(lexer.multi_line(line, column)?)
+                } else {
+                    TokenKind::Identifier(word.to_ascii_lowercase())
+                }
+            }
+            _ => {
+                lexer.next();
+                match c {
+                    '[' => TokenKind::LeftBracket,
+                    ']' => TokenKind::RightBracket,
+                    '(' => TokenKind::LeftParen,
+                    ')' => TokenKind::RightParen,
+                    '{' => TokenKind::LeftBrace,
+                    '}' => TokenKind::RightBrace,
+                    ',' => TokenKind::Comma,
+                    ';' => TokenKind::Semicolon,
+                    c => {
+                        return Err(SieveError::new(
+                            line,
+                            column,
+                            format!("unexpected character '{c}'"),
+                        ));
+                    }
+                }
+            }
+        };
+
+        tokens.push(Token { kind, line, column });
+    }
+}
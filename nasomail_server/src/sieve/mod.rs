@@ -0,0 +1,70 @@
+//! Per-user filter rules in a subset of Sieve (RFC 5228), which
+//! are run whenever a mail is delivered into an inbox, see `delivery`.
+//!
+//! The supported subset is
+//! - the control commands `require`, `if`, `elsif`, `else` and `stop`
+//! - the actions `keep`, `discard`, which moves the mail to the trash instead
+//!   of dropping it, `redirect`, which forwards the mail on behalf of its
+//!   recipient, `fileinto` (RFC 5228), which adds a label and creates it if
+//!   needed, `reject` (RFC 5429), and `setflag`, `addflag` and `removeflag`
+//!   (RFC 5232) with only the `\Seen` flag, which marks the mail as read
+//! - the tests `address`, `header`, `exists`, `size`, `not`, `anyof`, `allof`,
+//!   `true`, `false` and `body` (RFC 5173) with only the `:text` transform
+//! - the match types `:is`, `:contains` and `:matches` and the comparators
+//!   `i;ascii-casemap`, the default, and `i;octet`
+//!
+//! A mail only has the headers `from`, its sender, `to`, its recipient,
+//! and `subject`, and its size is that of its body plus its attachments.
+//! Like with `fileinto`, a mail that is redirected without `keep` goes to
+//! the trash. Variables, `envelope` and every other extension are not supported.
+
+mod interpreter;
+mod lexer;
+mod parser;
+
+use std::fmt;
+
+pub use interpreter::{Message, Outcome, evaluate};
+pub use parser::Script;
+
+/// The color of the labels that `fileinto` creates.
+pub const LABEL_COLOR: &str = "#808080";
+
+/// A problem with a script, along with where it is, both counted from `1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SieveError {
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+}
+
+impl SieveError {
+    fn new(line: u32, column: u32, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SieveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+/// Parses `script` and checks that it only uses the supported subset of Sieve.
+///
+/// # Errors
+///
+/// Returns `Err` with every problem that was found, sorted by where they are.
+/// Only the first syntax error is reported, since nothing after it can be parsed.
+///
+pub fn compile(script: &str) -> Result<Script, Vec<SieveError>> {
+    parser::parse(lexer::tokenize(script).map_err(|e| vec![e])?)
+}
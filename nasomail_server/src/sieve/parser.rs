@@ -0,0 +1,776 @@
+//! Parsing tokens into a `Script` and checking it against the supported subset.
+
+use std::collections::HashSet;
+
+use nasomail_shared::{address::Address, payload::label::NAME_MAX_LEN};
+
+use crate::sieve::{
+    SieveError,
+    lexer::{Token, TokenKind},
+};
+
+/// How deeply blocks and tests may be nested.
+const MAX_DEPTH: usize = 32;
+
+/// The capabilities that can be required.
+pub const CAPABILITIES: &[&str] = &[
+    "fileinto",
+    "reject",
+    "imap4flags",
+    "body",
+    "comparator-i;octet",
+    "comparator-i;ascii-casemap",
+];
+
+/// A parsed script, i.e, its top-level commands.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Script {
+    pub commands: Vec<Command>,
+}
+
+/// An enum of the supported commands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// An `if` along with its `elsif`s, in order, and its `else`, if any.
+    If {
+        branches: Vec<(Test, Vec<Command>)>,
+        otherwise: Option<Vec<Command>>,
+    },
+    Stop,
+    Keep,
+    Discard,
+
+    /// `fileinto`, with the name of the label.
+    FileInto(String),
+
+    /// `redirect`, with the address to forward to.
+    Redirect(String),
+
+    /// `reject`, with the reason.
+    Reject(String),
+
+    /// `setflag`, `addflag` or `removeflag`, with
+    /// whether the `\Seen` flag is set afterwards.
+    Seen(bool),
+}
+
+/// An enum of the supported tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Test {
+    True,
+    False,
+    Not(Box<Test>),
+    AnyOf(Vec<Test>),
+    AllOf(Vec<Test>),
+    Address {
+        part: AddressPart,
+        matcher: Matcher,
+        headers: Vec<Header>,
+        keys: Vec<String>,
+    },
+    Header {
+        matcher: Matcher,
+        headers: Vec<Header>,
+        keys: Vec<String>,
+    },
+    Body {
+        matcher: Matcher,
+        keys: Vec<String>,
+    },
+    Exists(Vec<Header>),
+    Size {
+        over: bool,
+        limit: u64,
+    },
+}
+
+/// The headers a mail has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Header {
+    From,
+    To,
+    Subject,
+}
+
+impl Header {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "from" => Some(Self::From),
+            "to" => Some(Self::To),
+            "subject" => Some(Self::Subject),
+            _ => None,
+        }
+    }
+}
+
+/// The part of an address an `address` test looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddressPart {
+    #[default]
+    All,
+    LocalPart,
+    Domain,
+}
+
+/// How keys are compared with values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Matcher {
+    pub kind: MatchKind,
+    pub comparator: Comparator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchKind {
+    #[default]
+    Is,
+    Contains,
+    Matches,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Comparator {
+    /// `i;ascii-casemap`, which ignores the case of ASCII letters.
+    #[default]
+    AsciiCasemap,
+
+    /// `i;octet`, which compares exactly.
+    Octet,
+}
+
+/// A positional or tagged argument.
+#[derive(Debug)]
+enum Argument {
+    Tag(String),
+    Number(u64),
+    Strings(Vec<String>),
+}
+
+/// The arguments of a command or test, along with where they start.
+#[derive(Debug)]
+struct Arguments {
+    items: Vec<(Argument, u32, u32)>,
+    tests: Vec<Test>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+    required: HashSet<String>,
+    errors: Vec<SieveError>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Returns an error at the current token, or
+    /// right after the last one at the end of the script.
+    fn error_here(&self, message: impl Into<String>) -> SieveError {
+        match self.peek().or(self.tokens.last()) {
+            Some(token) => SieveError::new(token.line, token.column, message),
+            None => SieveError::new(1, 1, message),
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind, what: &str) -> Result<(), SieveError> {
+        if self.peek().map(|token| &token.kind) == Some(&kind) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error_here(format!("expected {what}")))
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), SieveError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error_here("nested too deeply"));
+        }
+        Ok(())
+    }
+
+    /// Parses commands up to the end of the script,
+    /// or up to the `}` of the current block.
+    fn commands(&mut self, top_level: bool) -> Result<Vec<Command>, SieveError> {
+        let mut commands = Vec::new();
+        let mut requires_allowed = top_level;
+
+        loop {
+            let Some(token) = self.peek() else {
+                if top_level {
+                    return Ok(commands);
+                }
+                return Err(self.error_here("expected '}'"));
+            };
+
+            let (line, column) = (token.line, token.column);
+            let name = match &token.kind {
+                TokenKind::RightBrace if !top_level => return Ok(commands),
+                TokenKind::Identifier(name) => name.clone(),
+                _ => return Err(self.error_here("expected a command")),
+            };
+            self.pos += 1;
+
+            if name == "require" {
+                let arguments = self.arguments()?;
+                self.expect(TokenKind::Semicolon, "';'")?;
+
+                if !requires_allowed {
+                    self.errors.push(SieveError::new(
+                        line,
+                        column,
+                        "'require' must come before any other command",
+                    ));
+                }
+                self.require(line, column, arguments);
+                continue;
+            }
+            requires_allowed = false;
+
+            match name.as_str() {
+                "if" => {
+                    let mut branches = vec![self.branch(line, column)?];
+                    let mut otherwise = None;
+
+                    while let Some(TokenKind::Identifier(name)) = self.peek().map(|t| &t.kind) {
+                        match name.as_str() {
+                            "elsif" => {
+                                let (line, column) =
+                                    self.next().map_or((line, column), |t| (t.line, t.column));
+                                branches.push(self.branch(line, column)?);
+                            }
+                            "else" => {
+                                self.pos += 1;
+                                otherwise = Some(self.block()?);
+                                break;
+                            }
+                            _ => break,
+                        }
+                    }
+
+                    commands.push(Command::If {
+                        branches,
+                        otherwise,
+                    });
+                }
+                "elsif" | "else" => {
+                    return Err(SieveError::new(
+                        line,
+                        column,
+                        format!("'{name}' must follow 'if' or 'elsif'"),
+                    ));
+                }
+                _ => {
+                    let arguments = self.arguments()?;
+                    self.expect(TokenKind::Semicolon, "';'")?;
+
+                    match self.action(&name, line, column, arguments) {
+                        Ok(command) => commands.push(command),
+                        Err(e) => self.errors.push(e),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parses the test and block of an `if` or `elsif`.
+    fn branch(&mut self, line: u32, column: u32) -> Result<(Test, Vec<Command>), SieveError> {
+        let arguments = self.arguments()?;
+
+        if !arguments.items.is_empty() || arguments.tests.len() != 1 {
+            self.errors
+                .push(SieveError::new(line, column, "expected exactly one test"));
+        }
+
+        let test = arguments.tests.into_iter().next().unwrap_or(Test::False);
+        Ok((test, self.block()?))
+    }
+
+    fn block(&mut self) -> Result<Vec<Command>, SieveError> {
+        self.expect(TokenKind::LeftBrace, "'{'")?;
+        self.enter()?;
+
+        let commands = self.commands(false)?;
+
+        self.depth -= 1;
+        self.expect(TokenKind::RightBrace, "'}'")?;
+
+        Ok(commands)
+    }
+
+    /// Parses arguments, i.e, any number of tags, numbers and strings
+    /// followed by either a single test or a test list.
+    fn arguments(&mut self) -> Result<Arguments, SieveError> {
+        let mut items = Vec::new();
+
+        while let Some(token) = self.peek().cloned() {
+            let argument = match token.kind {
+                TokenKind::Tag(tag) => {
+                    self.pos += 1;
+                    Argument::Tag(tag)
+                }
+                TokenKind::Number(number) => {
+                    self.pos += 1;
+                    Argument::Number(number)
+                }
+                TokenKind::String(value) => {
+                    self.pos += 1;
+                    Argument::Strings(vec![value])
+                }
+                TokenKind::LeftBracket => {
+                    self.pos += 1;
+                    Argument::Strings(self.string_list()?)
+                }
+                _ => break,
+            };
+
+            items.push((argument, token.line, token.column));
+        }
+
+        let tests = match self.peek().map(|token| &token.kind) {
+            Some(TokenKind::Identifier(_)) => vec![self.test()?],
+            Some(TokenKind::LeftParen) => {
+                self.pos += 1;
+
+                let mut tests = vec![self.test()?];
+                while self.peek().map(|token| &token.kind) == Some(&TokenKind::Comma) {
+                    self.pos += 1;
+                    tests.push(self.test()?);
+                }
+
+                self.expect(TokenKind::RightParen, "')' or ','")?;
+                tests
+            }
+            _ => Vec::new(),
+        };
+
+        Ok(Arguments { items, tests })
+    }
+
+    /// Parses the rest of a string list, right after the `[`.
+    fn string_list(&mut self) -> Result<Vec<String>, SieveError> {
+        let mut strings = Vec::new();
+
+        loop {
+            match self.next().map(|token| token.kind) {
+                Some(TokenKind::String(value)) => strings.push(value),
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error_here("expected a string"));
+                }
+            }
+
+            match self.next().map(|token| token.kind) {
+                Some(TokenKind::Comma) => {}
+                Some(TokenKind::RightBracket) => return Ok(strings),
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error_here("expected ']' or ','"));
+                }
+            }
+        }
+    }
+
+    fn test(&mut self) -> Result<Test, SieveError> {
+        let Some(Token {
+            kind: TokenKind::Identifier(name),
+            line,
+            column,
+        }) = self.next()
+        else {
+            self.pos -= 1;
+            return Err(self.error_here("expected a test"));
+        };
+
+        self.enter()?;
+        let arguments = self.arguments()?;
+        self.depth -= 1;
+
+        Ok(match self.check_test(&name, line, column, arguments) {
+            Ok(test) => test,
+            Err(e) => {
+                self.errors.push(e);
+                Test::False
+            }
+        })
+    }
+
+    fn require(&mut self, line: u32, column: u32, arguments: Arguments) {
+        let capabilities = match arguments.items.as_slice() {
+            [(Argument::Strings(capabilities), _, _)] if arguments.tests.is_empty() => capabilities,
+            _ => {
+                self.errors.push(SieveError::new(
+                    line,
+                    column,
+                    "'require' expects a string list",
+                ));
+                return;
+            }
+        };
+
+        for capability in capabilities {
+            if CAPABILITIES.contains(&capability.as_str()) {
+                self.required.insert(capability.clone());
+            } else {
+                self.errors.push(SieveError::new(
+                    line,
+                    column,
+                    format!("unsupported capability \"{capability}\""),
+                ));
+            }
+        }
+    }
+
+    /// Fails unless `capability` was required.
+    fn needs(&self, capability: &str, line: u32, column: u32) -> Result<(), SieveError> {
+        if self.required.contains(capability) {
+            Ok(())
+        } else {
+            Err(SieveError::new(
+                line,
+                column,
+                format!("missing 'require \"{capability}\"'"),
+            ))
+        }
+    }
+
+    fn action(
+        &self,
+        name: &str,
+        line: u32,
+        column: u32,
+        arguments: Arguments,
+    ) -> Result<Command, SieveError> {
+        let error = |message: String| SieveError::new(line, column, message);
+
+        if !arguments.tests.is_empty() {
+            return Err(error(format!("'{name}' does not take a test")));
+        }
+
+        let mut items = arguments.items.into_iter().map(|(argument, _, _)| argument);
+        let argument = items.next();
+
+        if items.next().is_some() {
+            return Err(error(format!("too many arguments for '{name}'")));
+        }
+
+        let single = |argument: Option<Argument>| match argument {
+            Some(Argument::Strings(strings)) if strings.len() == 1 => {
+                Ok(strings.into_iter().next().unwrap_or_default())
+            }
+            _ => Err(error(format!("'{name}' expects a single string"))),
+        };
+
+        match name {
+            "stop" | "keep" | "discard" => {
+                if argument.is_some() {
+                    return Err(error(format!("'{name}' does not take any arguments")));
+                }
+
+                Ok(match name {
+                    "stop" => Command::Stop,
+                    "keep" => Command::Keep,
+                    _ => Command::Discard,
+                })
+            }
+            "fileinto" => {
+                self.needs("fileinto", line, column)?;
+                let label = single(argument)?;
+
+                let len = label.chars().count();
+                if label.trim() != label || len == 0 || len > NAME_MAX_LEN {
+                    return Err(error(format!(
+                        "the label must be 1 to {NAME_MAX_LEN} characters long \
+                         without leading or trailing whitespace"
+                    )));
+                }
+
+                Ok(Command::FileInto(label))
+            }
+            "redirect" => {
+                let address = single(argument)?;
+
+                if address.parse::<Address>().is_err() {
+                    return Err(error(format!("invalid address \"{address}\"")));
+                }
+
+                Ok(Command::Redirect(address))
+            }
+            "reject" => {
+                self.needs("reject", line, column)?;
+                Ok(Command::Reject(single(argument)?))
+            }
+            "setflag" | "addflag" | "removeflag" => {
+                self.needs("imap4flags", line, column)?;
+
+                let Some(Argument::Strings(flags)) = argument else {
+                    return Err(error(format!("'{name}' expects a string list")));
+                };
+
+                let mut seen = false;
+                for flag in flags.iter().flat_map(|flags| flags.split_whitespace()) {
+                    if !flag.eq_ignore_ascii_case("\\seen") {
+                        return Err(error(format!(
+                            "unsupported flag \"{flag}\", only \"\\\\Seen\" is supported"
+                        )));
+                    }
+                    seen = true;
+                }
+
+                Ok(Command::Seen(match name {
+                    "setflag" => seen,
+                    "addflag" => true,
+                    _ => !seen,
+                }))
+            }
+            _ => Err(error(format!("unknown command '{name}'"))),
+        }
+    }
+
+    fn check_test(
+        &self,
+        name: &str,
+        line: u32,
+        column: u32,
+        arguments: Arguments,
+    ) -> Result<Test, SieveError> {
+        let error =
+            |line: u32, column: u32, message: String| SieveError::new(line, column, message);
+
+        let takes_tests = matches!(name, "not" | "anyof" | "allof");
+        if !takes_tests && !arguments.tests.is_empty() {
+            return Err(error(
+                line,
+                column,
+                format!("'{name}' does not take a test"),
+            ));
+        }
+
+        let mut matcher = Matcher::default();
+        let mut part = None;
+        let mut size = None;
+        let mut seen_kind = false;
+        let mut seen_comparator = false;
+        let mut positional = Vec::new();
+
+        let mut items = arguments.items.into_iter();
+        while let Some((argument, line, column)) = items.next() {
+            let tag = match argument {
+                Argument::Tag(tag) => tag,
+                argument => {
+                    positional.push((argument, line, column));
+                    continue;
+                }
+            };
+
+            match (name, tag.as_str()) {
+                ("address" | "header" | "body", "is" | "contains" | "matches") if !seen_kind => {
+                    seen_kind = true;
+                    matcher.kind = match tag.as_str() {
+                        "is" => MatchKind::Is,
+                        "contains" => MatchKind::Contains,
+                        _ => MatchKind::Matches,
+                    };
+                }
+                ("address" | "header" | "body", "comparator") if !seen_comparator => {
+                    seen_comparator = true;
+                    matcher.comparator = match items.next() {
+                        Some((Argument::Strings(strings), line, column)) if strings.len() == 1 => {
+                            match strings[0].as_str() {
+                                "i;ascii-casemap" => Comparator::AsciiCasemap,
+                                "i;octet" => Comparator::Octet,
+                                comparator => {
+                                    return Err(error(
+                                        line,
+                                        column,
+                                        format!("unsupported comparator \"{comparator}\""),
+                                    ));
+                                }
+                            }
+                        }
+                        _ => {
+                            return Err(error(
+                                line,
+                                column,
+                                "':comparator' expects a string".to_owned(),
+                            ));
+                        }
+                    };
+                }
+                ("address", "all" | "localpart" | "domain") if part.is_none() => {
+                    part = Some(match tag.as_str() {
+                        "all" => AddressPart::All,
+                        "localpart" => AddressPart::LocalPart,
+                        _ => AddressPart::Domain,
+                    });
+                }
+                ("body", "text") => {}
+                ("size", "over" | "under") if size.is_none() => {
+                    size = Some(tag == "over");
+                }
+                _ => {
+                    return Err(error(
+                        line,
+                        column,
+                        format!("unexpected ':{tag}' for '{name}'"),
+                    ));
+                }
+            }
+        }
+
+        let strings = |positional: Vec<(Argument, u32, u32)>, expected: usize| {
+            let strings: Vec<Vec<String>> = positional
+                .into_iter()
+                .filter_map(|(argument, _, _)| match argument {
+                    Argument::Strings(strings) => Some(strings),
+                    _ => None,
+                })
+                .collect();
+
+            if strings.len() == expected {
+                Ok(strings)
+            } else {
+                Err(error(
+                    line,
+                    column,
+                    format!("'{name}' expects {expected} string list(s)"),
+                ))
+            }
+        };
+
+        let headers = |names: Vec<String>, allowed: &[Header]| {
+            names
+                .iter()
+                .map(|name| {
+                    Header::from_name(name)
+                        .filter(|header| allowed.contains(header))
+                        .ok_or_else(|| {
+                            error(line, column, format!("unsupported header \"{name}\""))
+                        })
+                })
+                .collect::<Result<Vec<Header>, SieveError>>()
+        };
+
+        let all_headers = [Header::From, Header::To, Header::Subject];
+
+        match name {
+            "true" | "false" | "not" | "anyof" | "allof" if !positional.is_empty() => Err(error(
+                line,
+                column,
+                format!("'{name}' does not take arguments"),
+            )),
+            "true" => Ok(Test::True),
+            "false" => Ok(Test::False),
+            "not" => match <[Test; 1]>::try_from(arguments.tests) {
+                Ok([test]) => Ok(Test::Not(Box::new(test))),
+                Err(_) => Err(error(
+                    line,
+                    column,
+                    "'not' expects a single test".to_owned(),
+                )),
+            },
+            "anyof" | "allof" => {
+                if arguments.tests.is_empty() {
+                    return Err(error(line, column, format!("'{name}' expects a test list")));
+                }
+
+                Ok(if name == "anyof" {
+                    Test::AnyOf(arguments.tests)
+                } else {
+                    Test::AllOf(arguments.tests)
+                })
+            }
+            "address" | "header" => {
+                if matcher.comparator == Comparator::Octet {
+                    self.needs("comparator-i;octet", line, column)?;
+                }
+
+                let [names, keys] =
+                    <[Vec<String>; 2]>::try_from(strings(positional, 2)?).unwrap_or_default();
+
+                if name == "address" {
+                    Ok(Test::Address {
+                        part: part.unwrap_or_default(),
+                        matcher,
+                        headers: headers(names, &[Header::From, Header::To])?,
+                        keys,
+                    })
+                } else {
+                    Ok(Test::Header {
+                        matcher,
+                        headers: headers(names, &all_headers)?,
+                        keys,
+                    })
+                }
+            }
+            "body" => {
+                self.needs("body", line, column)?;
+                if matcher.comparator == Comparator::Octet {
+                    self.needs("comparator-i;octet", line, column)?;
+                }
+
+                let [keys] =
+                    <[Vec<String>; 1]>::try_from(strings(positional, 1)?).unwrap_or_default();
+
+                Ok(Test::Body { matcher, keys })
+            }
+            "exists" => {
+                let [names] =
+                    <[Vec<String>; 1]>::try_from(strings(positional, 1)?).unwrap_or_default();
+
+                Ok(Test::Exists(headers(names, &all_headers)?))
+            }
+            "size" => match (size, positional.as_slice()) {
+                (Some(over), [(Argument::Number(limit), _, _)]) => Ok(Test::Size {
+                    over,
+                    limit: *limit,
+                }),
+                _ => Err(error(
+                    line,
+                    column,
+                    "'size' expects ':over' or ':under' and a number".to_owned(),
+                )),
+            },
+            _ => Err(error(line, column, format!("unknown test '{name}'"))),
+        }
+    }
+}
+
+/// Parses `tokens` into a `Script`, checking that it only uses the supported
+/// commands, tests and arguments and that every extension it uses is required.
+///
+/// # Errors
+///
+/// Returns `Err` with every problem that was found, in the order they
+/// appear in the script. Parsing stops at the first syntax error.
+///
+pub fn parse(tokens: Vec<Token>) -> Result<Script, Vec<SieveError>> {
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+        required: HashSet::new(),
+        errors: Vec::new(),
+    };
+
+    let result = parser.commands(true);
+
+    let mut errors = parser.errors;
+    match result {
+        Ok(commands) if errors.is_empty() => Ok(Script { commands }),
+        Ok(_) => Err(errors),
+        Err(e) => {
+            errors.push(e);
+            errors.sort_by_key(|e| (e.line, e.column));
+            Err(errors)
+        }
+    }
+}
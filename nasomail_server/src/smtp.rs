@@ -0,0 +1,466 @@
+//! An optional SMTP listener (RFC 5321) accepting mail for local users.
+//!
+//! Only mail for users of this server is accepted, i.e, the listener is
+//! not a relay. Recipients must be at the host of `pub_addr`, with or
+//! without its port. Each accepted message is parsed by `message::parse`
+//! and delivered into the inbox of every recipient whose filter does not
+//! reject it.
+
+use std::io;
+
+use sqlx::sqlite::SqlitePool;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    time::{self, Duration},
+};
+use tracing::{info, instrument, warn};
+
+use nasomail_shared::{
+    address::{self, Address},
+    payload::mail::{ADDRESS_MAX_LEN, ATTACHMENT_MAX_SIZE},
+};
+
+use crate::{
+    app::AppContextGuard,
+    delivery::{self, DeliveryError, Mail},
+    events::Events,
+    message,
+};
+
+/// The largest message accepted, in bytes, as announced with `SIZE`.
+pub const MAX_MESSAGE_SIZE: u64 = 1024 * 1024 * 50;
+
+/// The most recipients accepted for a single message.
+const MAX_RECIPIENTS: usize = 100;
+
+/// The longest command line accepted, including the `CRLF`.
+const MAX_LINE_LEN: u64 = 4096;
+
+/// How long a client may stay silent before the connection is closed.
+const TIMEOUT: Duration = Duration::from_secs(60 * 5);
+
+/// Accepts connections on `listener` until the process exits,
+/// handling each session in a task of its own.
+pub async fn serve(listener: TcpListener, app: AppContextGuard) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!(err = ?e, "failed to accept smtp connection");
+                continue;
+            }
+        };
+
+        let app = app.clone();
+        tokio::spawn(async move {
+            if let Err(e) = session(stream, app).await {
+                info!(peer = %peer, err = %e, "smtp session ended");
+            }
+        });
+    }
+}
+
+/// The envelope of the message being received in a session.
+#[derive(Default)]
+struct Envelope {
+    /// The reverse-path of `MAIL FROM`, `Some("")` for the null path.
+    from: Option<String>,
+
+    /// The local users accepted with `RCPT TO`, as registered.
+    recipients: Vec<String>,
+}
+
+#[instrument(skip_all, fields(peer = ?stream.peer_addr().ok()))]
+async fn session(stream: TcpStream, app: AppContextGuard) -> io::Result<()> {
+    // Clone what is needed so the context is not held for the whole session
+    let (pool, events, pub_addr, max_attachment_size) = {
+        let ctx = app.ctx().await;
+        let cfg = ctx.cfg().await;
+        let max = *cfg.max_attachment_size().await;
+
+        (
+            ctx.pool().await.clone(),
+            ctx.events().clone(),
+            cfg.pub_addr().await.clone(),
+            max.min(ATTACHMENT_MAX_SIZE),
+        )
+    };
+    let hostname = address::host_name(&pub_addr).to_owned();
+
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    let mut envelope = Envelope::default();
+    let mut greeted = false;
+
+    writer
+        .write_all(format!("220 {} ESMTP NasoMail\r\n", hostname).as_bytes())
+        .await?;
+
+    loop {
+        let Some(line) = read_line(&mut reader).await? else {
+            writer.write_all(b"500 5.5.2 Line too long\r\n").await?;
+            continue;
+        };
+
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (verb, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+
+        let reply = match verb.to_ascii_uppercase().as_str() {
+            "EHLO" => {
+                greeted = true;
+                envelope = Envelope::default();
+                format!(
+                    "250-{}\r\n250-SIZE {}\r\n250-8BITMIME\r\n250 PIPELINING\r\n",
+                    hostname, MAX_MESSAGE_SIZE
+                )
+            }
+            "HELO" => {
+                greeted = true;
+                envelope = Envelope::default();
+                format!("250 {}\r\n", hostname)
+            }
+            "MAIL" if !greeted => "503 5.5.1 Send EHLO first\r\n".to_owned(),
+            "MAIL" if envelope.from.is_some() => "503 5.5.1 Nested MAIL\r\n".to_owned(),
+            "MAIL" => match parse_path(arg, "FROM:") {
+                None => "501 5.5.4 Syntax: MAIL FROM:<address>\r\n".to_owned(),
+                Some((_, params)) if exceeds_size(params) => {
+                    "552 5.3.4 Message too large\r\n".to_owned()
+                }
+                Some((path, _)) => {
+                    envelope.from = Some(path.to_owned());
+                    "250 2.1.0 OK\r\n".to_owned()
+                }
+            },
+            "RCPT" if envelope.from.is_none() => "503 5.5.1 Send MAIL first\r\n".to_owned(),
+            "RCPT" if envelope.recipients.len() >= MAX_RECIPIENTS => {
+                "452 4.5.3 Too many recipients\r\n".to_owned()
+            }
+            "RCPT" => match parse_path(arg, "TO:") {
+                None => "501 5.5.4 Syntax: RCPT TO:<address>\r\n".to_owned(),
+                Some((path, _)) => match recipient(&pool, path, &pub_addr).await {
+                    Ok(Some(name)) => {
+                        envelope.recipients.push(name);
+                        "250 2.1.5 OK\r\n".to_owned()
+                    }
+                    Ok(None) => "550 5.1.1 No such user\r\n".to_owned(),
+                    Err(Rejected::Relay) => "550 5.7.1 Relaying denied\r\n".to_owned(),
+                    Err(Rejected::Internal) => "451 4.3.0 Local error in processing\r\n".to_owned(),
+                },
+            },
+            "DATA" if envelope.recipients.is_empty() => {
+                "503 5.5.1 No valid recipients\r\n".to_owned()
+            }
+            "DATA" => {
+                writer
+                    .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                    .await?;
+
+                let reply = match read_data(&mut reader).await? {
+                    None => "552 5.3.4 Message too large\r\n".to_owned(),
+                    Some(raw) => {
+                        deliver(
+                            &pool,
+                            &events,
+                            &pub_addr,
+                            &envelope,
+                            &raw,
+                            max_attachment_size,
+                        )
+                        .await
+                    }
+                };
+
+                envelope = Envelope::default();
+                reply
+            }
+            "RSET" => {
+                envelope = Envelope::default();
+                "250 2.0.0 OK\r\n".to_owned()
+            }
+            "NOOP" => "250 2.0.0 OK\r\n".to_owned(),
+            "VRFY" => "252 2.5.0 Cannot VRFY user\r\n".to_owned(),
+            "QUIT" => {
+                writer.write_all(b"221 2.0.0 Bye\r\n").await?;
+                return Ok(());
+            }
+            _ => "500 5.5.2 Command not recognized\r\n".to_owned(),
+        };
+
+        writer.write_all(reply.as_bytes()).await?;
+    }
+}
+
+/// Reads a single command line.
+///
+/// Returns `Ok(None)` if the line is longer than `MAX_LINE_LEN`, in which
+/// case the rest of it is discarded.
+///
+/// # Errors
+///
+/// Returns `Err` if the connection was closed, timed out or failed.
+///
+async fn read_line<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    read_until_newline(reader, &mut line, MAX_LINE_LEN).await?;
+
+    if line.ends_with(b"\n") {
+        return Ok(Some(line));
+    }
+
+    // Discard the rest of an overly long line
+    loop {
+        line.clear();
+        read_until_newline(reader, &mut line, MAX_LINE_LEN).await?;
+
+        if line.ends_with(b"\n") {
+            return Ok(None);
+        }
+    }
+}
+
+/// Reads the message following `DATA` up to the terminating `.` line,
+/// undoing the dot-stuffing of lines starting with a `.`.
+///
+/// Returns `Ok(None)` if the message is larger than `MAX_MESSAGE_SIZE`,
+/// in which case the rest of it is discarded.
+///
+/// # Errors
+///
+/// Returns `Err` if the connection was closed, timed out or failed.
+///
+async fn read_data<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> io::Result<Option<Vec<u8>>> {
+    let mut data = Vec::new();
+    let mut line = Vec::new();
+    let mut too_large = false;
+    let mut line_start = true;
+
+    loop {
+        line.clear();
+        read_until_newline(reader, &mut line, MAX_LINE_LEN).await?;
+
+        let complete = line.ends_with(b"\n");
+
+        if line_start && (line == b".\r\n" || line == b".\n") {
+            return Ok((!too_large).then_some(data));
+        }
+
+        let content = if line_start && line.starts_with(b".") {
+            &line[1..]
+        } else {
+            &line[..]
+        };
+
+        if !too_large {
+            if data.len() as u64 + content.len() as u64 > MAX_MESSAGE_SIZE {
+                too_large = true;
+                data = Vec::new();
+            } else {
+                data.extend_from_slice(content);
+            }
+        }
+
+        line_start = complete;
+    }
+}
+
+async fn read_until_newline<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+    buf: &mut Vec<u8>,
+    max: u64,
+) -> io::Result<()> {
+    let read = time::timeout(TIMEOUT, (&mut *reader).take(max).read_until(b'\n', buf))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "client timed out"))??;
+
+    if read == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(())
+}
+
+/// Splits the argument of `MAIL` or `RCPT` into its path
+/// (without the angle brackets) and its parameters.
+///
+/// Returns `None` if it does not start with `keyword`
+/// or the path is not in angle brackets.
+///
+fn parse_path<'a>(arg: &'a str, keyword: &str) -> Option<(&'a str, &'a str)> {
+    let rest = arg
+        .get(..keyword.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(keyword))
+        .map(|_| arg[keyword.len()..].trim_start())?;
+
+    let rest = rest.strip_prefix('<')?;
+    let (path, params) = rest.split_once('>')?;
+
+    // Drop a source route, e.g, `<@relay:user@host>`
+    let path = path.rsplit_once(':').map_or(path, |(_, path)| path);
+
+    Some((path.trim(), params.trim()))
+}
+
+/// Returns whether the `SIZE=` parameter of `MAIL`
+/// announces a message larger than `MAX_MESSAGE_SIZE`.
+fn exceeds_size(params: &str) -> bool {
+    params
+        .split_whitespace()
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case("SIZE"))
+        .and_then(|(_, size)| size.parse::<u64>().ok())
+        .is_some_and(|size| size > MAX_MESSAGE_SIZE)
+}
+
+/// An enum of reasons to reject a recipient other than it not existing.
+enum Rejected {
+    Relay,
+    Internal,
+}
+
+/// Resolves the recipient `path` of `RCPT TO` to a local user.
+///
+/// Returns `Ok(Some(name))` with the name as it was registered.
+/// Returns `Ok(None)` if there is no such user.
+///
+/// # Errors
+///
+/// Returns `Err(Relay)`    if `path` is not at `pub_addr`, see `address::is_local_host`.
+/// Returns `Err(Internal)` if the database could not be queried.
+///
+async fn recipient(
+    pool: &SqlitePool,
+    path: &str,
+    pub_addr: &str,
+) -> Result<Option<String>, Rejected> {
+    let Some((user, domain)) = path.rsplit_once('@') else {
+        return Err(Rejected::Relay);
+    };
+
+    let domain = domain
+        .strip_prefix('[')
+        .and_then(|domain| domain.strip_suffix(']'))
+        .unwrap_or(domain);
+
+    if user.is_empty() || !address::is_local_host(domain, pub_addr) {
+        return Err(Rejected::Relay);
+    }
+
+    let mut conn = pool.acquire().await.map_err(|e| {
+        warn!(err = ?e, "failed to acquire connection");
+        Rejected::Internal
+    })?;
+
+    match delivery::resolve_local(&mut conn, user).await {
+        Ok(found) => Ok(found.map(|(_, name)| name)),
+        Err(e) => {
+            warn!(err = ?e, "failed to resolve recipient");
+            Err(Rejected::Internal)
+        }
+    }
+}
+
+/// Returns `from` cut down to fit the `mails` table if it is
+/// a qualified address at a host other than `pub_addr`.
+///
+/// Returns `None` for bare usernames and addresses at `pub_addr`,
+/// see `Address::is_local`, and for anything that is no address.
+fn external_sender(from: &str, pub_addr: &str) -> Option<String> {
+    let from = message::fit(from, ADDRESS_MAX_LEN);
+
+    from.parse::<Address>()
+        .is_ok_and(|address| !address.is_local(pub_addr))
+        .then_some(from)
+}
+
+/// Parses `raw` and delivers it to every recipient of `envelope`, as the
+/// server at `pub_addr`, then returns the reply to the `DATA` command.
+///
+/// The sender is the address of `From:`, or else the reverse-path, as long
+/// as it is at another host, see `external_sender`. Messages without a
+/// valid `Message-ID` get one at `pub_addr`. The message
+/// is only refused if the filter of every recipient rejects it, since there
+/// is a single reply for all of them.
+async fn deliver(
+    pool: &SqlitePool,
+    events: &Events,
+    pub_addr: &str,
+    envelope: &Envelope,
+    raw: &[u8],
+    max_attachment_size: u64,
+) -> String {
+    let Some(parsed) = message::parse(raw) else {
+        return "554 5.6.0 Malformed message\r\n".to_owned();
+    };
+
+    if parsed
+        .attachments
+        .iter()
+        .any(|attachment| attachment.data.len() as u64 > max_attachment_size)
+    {
+        return "552 5.3.4 Attachment too large\r\n".to_owned();
+    }
+
+    // Neither is authenticated, so a sender at this server would let anyone
+    // impersonate its users and is never taken as is
+    let Some(sender) = [parsed.from.as_deref(), envelope.from.as_deref()]
+        .into_iter()
+        .flatten()
+        .find_map(|from| external_sender(from, pub_addr))
+    else {
+        return "550 5.7.1 Sender address rejected\r\n".to_owned();
+    };
+
+    let message_id = parsed
+        .message_id
+        .clone()
+        .unwrap_or_else(|| delivery::message_id(pub_addr));
+
+    let mut delivered = false;
+    let mut rejected = None;
+
+    for recipient in &envelope.recipients {
+        let mail = Mail {
+            sender: sender.clone(),
+            recipient: recipient.clone(),
+            subject: parsed.subject.clone(),
+            body: parsed.body.clone(),
+            message_id: message_id.clone(),
+            in_reply_to: parsed.in_reply_to.clone(),
+            references: parsed.references.clone(),
+            // Mail from the null reverse-path, e.g, a bounce, is never replied to either
+            auto_submitted: parsed.auto_submitted || envelope.from.as_deref() == Some(""),
+        };
+
+        match delivery::receive(pool, events, pub_addr, &mail, &parsed.attachments).await {
+            Ok(_) => delivered = true,
+            Err(DeliveryError::Rejected(reason)) => {
+                info!(recipient = %recipient, "rejected by filter");
+                rejected = Some(reason);
+            }
+            Err(DeliveryError::UnknownRecipient(_)) => {
+                // The user was removed since `RCPT`, nothing to retry
+                warn!(recipient = %recipient, "recipient vanished before delivery");
+            }
+            Err(e) => {
+                warn!(err = ?e, recipient = %recipient, "failed to deliver smtp message");
+                return "451 4.3.0 Local error in processing\r\n".to_owned();
+            }
+        }
+    }
+
+    match rejected {
+        Some(reason) if !delivered => {
+            // A reply is a single line, so only the first line of the reason fits
+            let reason = reason.lines().map(str::trim).find(|line| !line.is_empty());
+            format!(
+                "550 5.7.1 {}\r\n",
+                reason.unwrap_or("Rejected by recipient")
+            )
+        }
+        _ => "250 2.0.0 OK: queued\r\n".to_owned(),
+    }
+}
@@ -0,0 +1,69 @@
+//! Deleting mails that have been in the trash for too long.
+
+use sqlx::sqlite::SqlitePool;
+use tokio::time::{self, Duration};
+use tracing::{info, warn};
+
+use nasomail_shared::payload::event::EventPayload;
+
+use crate::{app::AppContextGuard, events::Events};
+
+/// How often the trash is purged.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Runs the purge task, which never returns.
+///
+/// Every `PURGE_INTERVAL`, mails that were moved to the trash more than
+/// `Config::trash_retention_days` ago are deleted for good, along with
+/// their attachments.
+pub async fn run(app: AppContextGuard) {
+    loop {
+        let (pool, events, retention_days) = {
+            let ctx = app.ctx().await;
+
+            (
+                ctx.pool().await.clone(),
+                ctx.events().clone(),
+                *ctx.cfg().await.trash_retention_days().await,
+            )
+        };
+
+        match purge(&pool, &events, retention_days).await {
+            Ok(0) => {}
+            Ok(purged) => info!(purged = purged, "purged trash"),
+            Err(e) => warn!(err = ?e, "failed to purge trash"),
+        }
+
+        time::sleep(PURGE_INTERVAL).await;
+    }
+}
+
+/// Deletes every mail that was moved to the trash at least
+/// `retention_days` ago and publishes an `EventPayload::Deleted` for it.
+///
+/// Returns how many mails were deleted.
+///
+/// # Errors
+///
+/// Returns `Err` if the database could not be queried.
+///
+async fn purge(
+    pool: &SqlitePool,
+    events: &Events,
+    retention_days: u64,
+) -> Result<usize, sqlx::Error> {
+    let purged: Vec<(i64, i64)> = sqlx::query_as(
+        "DELETE FROM mails
+            WHERE deleted_at IS NOT NULL AND deleted_at <= DATETIME('now', '-' || ? || ' days')
+            RETURNING id, user_id",
+    )
+    .bind(i64::try_from(retention_days).unwrap_or(i64::MAX))
+    .fetch_all(pool)
+    .await?;
+
+    for (id, user_id) in &purged {
+        events.publish(*user_id, EventPayload::Deleted { id: *id });
+    }
+
+    Ok(purged.len())
+}
@@ -0,0 +1,154 @@
+//! Out-of-office replies.
+//!
+//! While a user is on vacation, see `/api/vacation`, the sender of every
+//! mail delivered into their inbox gets an automatic reply, but only once
+//! every `days` days, as tracked in the `vacation_replies` table.
+//!
+//! Following RFC 3834, mails that were generated automatically themselves,
+//! see `Mail::auto_submitted`, and mails from addresses that no human reads,
+//! e.g, `mailer-daemon` or mailing lists, are never replied to, and the
+//! replies are marked as generated automatically, so two servers can never
+//! keep replying to each other.
+
+use sqlx::{SqliteConnection, sqlite::SqlitePool};
+use tracing::{info, warn};
+
+use crate::{
+    delivery::{self, Delivered, DeliveryError, MAILER_DAEMON, Mail, MailRow},
+    events::Events,
+};
+
+/// Sends the out-of-office reply of `user_id`, if they are on vacation,
+/// to the sender of their mail `id`, as the server at `origin`.
+///
+/// Called by `delivery::announce` for every mail delivered into an inbox.
+pub async fn respond(pool: SqlitePool, events: Events, origin: String, id: i64, user_id: i64) {
+    match reply(&pool, &events, &origin, id, user_id).await {
+        Ok(Some(delivered)) => info!(
+            id = id,
+            sent_id = delivered.sent_id,
+            "sent out-of-office reply"
+        ),
+        Ok(None) => {}
+        Err(e) => warn!(err = ?e, id = id, "failed to send out-of-office reply"),
+    }
+}
+
+/// Sends the out-of-office reply of `user_id` to the sender of their mail `id`
+/// and records that they got it in a single transaction, so a reply that could
+/// not be sent is not recorded and is sent for the next mail instead.
+///
+/// Returns `Ok(None)` if no reply is due, see `prepare`.
+///
+async fn reply(
+    pool: &SqlitePool,
+    events: &Events,
+    origin: &str,
+    id: i64,
+    user_id: i64,
+) -> Result<Option<Delivered>, DeliveryError> {
+    let mut tx = pool.begin().await?;
+
+    let Some(reply) = prepare(&mut tx, origin, id, user_id).await? else {
+        return Ok(None);
+    };
+
+    let staged = delivery::send_in(&mut tx, origin, user_id, &reply, None).await?;
+
+    tx.commit().await?;
+
+    Ok(Some(staged.finish(pool, events, origin)))
+}
+
+/// Builds the out-of-office reply of `user_id` to their mail `id` and records
+/// that its sender got it, so they do not get it again for `days` days.
+///
+/// Returns `Ok(None)` if there is no such mail, the user is not on vacation,
+/// the mail must not be replied to or its sender already got the reply.
+///
+async fn prepare(
+    conn: &mut SqliteConnection,
+    origin: &str,
+    id: i64,
+    user_id: i64,
+) -> Result<Option<Mail>, sqlx::Error> {
+    let Some(mail): Option<MailRow> = sqlx::query_as(
+        "SELECT sender, recipient, subject, body, message_id, in_reply_to, refs, auto_submitted
+            FROM mails WHERE id = ? AND user_id = ?",
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    if mail.auto_submitted
+        || !is_personal(&mail.sender)
+        || mail.sender.eq_ignore_ascii_case(&mail.recipient)
+    {
+        return Ok(None);
+    }
+
+    let Some((subject, body, days)): Option<(String, String, i64)> = sqlx::query_as(
+        "SELECT subject, body, days FROM vacations
+            WHERE user_id = ? AND DATE('now') BETWEEN starts_on AND ends_on",
+    )
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    // Nothing is recorded if the sender got the reply within the last `days` days
+    let recorded = sqlx::query(
+        "INSERT INTO vacation_replies (user_id, sender) VALUES (?, ?)
+            ON CONFLICT (user_id, sender) DO UPDATE SET replied_at = CURRENT_TIMESTAMP
+                WHERE replied_at <= DATETIME('now', '-' || ? || ' days')",
+    )
+    .bind(user_id)
+    .bind(&mail.sender)
+    .bind(days)
+    .execute(&mut *conn)
+    .await?;
+
+    if recorded.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    let references =
+        delivery::references_of(&mail.message_id, &delivery::split_references(&mail.refs));
+
+    Ok(Some(Mail {
+        sender: mail.recipient,
+        recipient: mail.sender,
+        subject,
+        body,
+        message_id: delivery::message_id(origin),
+        in_reply_to: Some(mail.message_id),
+        references,
+        auto_submitted: true,
+    }))
+}
+
+/// Returns whether mail from `sender` is likely to have been sent by a human,
+/// rather than by `mailer-daemon`, a mailing list or the like, see section 2
+/// of RFC 3834.
+fn is_personal(sender: &str) -> bool {
+    let user = sender
+        .rsplit_once('@')
+        .map_or(sender, |(user, _)| user)
+        .to_ascii_lowercase();
+
+    !(user.is_empty()
+        || user == MAILER_DAEMON
+        || user == "postmaster"
+        || user.starts_with("owner-")
+        || user.ends_with("-request")
+        || user.starts_with("noreply")
+        || user.starts_with("no-reply")
+        || user.starts_with("listserv")
+        || user.starts_with("majordomo"))
+}
@@ -0,0 +1,454 @@
+//! Outgoing webhooks, which post the events of `events` to URLs.
+//!
+//! A listener started from `main` turns every published event into a row
+//! in `webhook_deliveries` for every webhook subscribed to it, i.e, those
+//! of the user and the server-wide ones of `Config::webhooks`. A worker,
+//! see `worker`, then posts them, signed with the secret of their
+//! webhook, retrying failed attempts with the backoff of `DeliveryConfig`
+//! and recording every attempt in `webhook_attempts`.
+//!
+//! Events are only picked up while the server is running, so deliveries
+//! survive a restart but events published during one are not posted.
+//!
+//! Like remote servers, webhooks are only posted to on public hosts,
+//! see `net`, which also goes for the server-wide ones.
+
+use std::{sync::LazyLock, time::Duration as StdDuration};
+
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Url, header, redirect};
+use sha2::Sha256;
+use sqlx::sqlite::SqlitePool;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{instrument, warn};
+
+use nasomail_shared::payload::{
+    event::EventPayload,
+    mail::{MailStatus, MailSummary},
+    webhook::{DELIVERY_HEADER, SIGNATURE_HEADER, WebhookEvent, WebhookMessage},
+};
+
+use crate::{
+    app::AppContextGuard,
+    config::{DeliveryConfig, WebhookConfig},
+    events::Event,
+    net::{self, NetError},
+    queue::{Attempt, Outcome},
+    worker::{self, Attempts, Worker},
+};
+
+/// How long a webhook may take to respond.
+const TIMEOUT: StdDuration = StdDuration::from_secs(10);
+
+static WORKER: Worker = Worker::new();
+
+static CLIENT: LazyLock<Client> = LazyLock::new(|| {
+    Client::builder()
+        .timeout(TIMEOUT)
+        .dns_resolver(net::PublicResolver)
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap_or_default()
+});
+
+/// Formats `events` for the `events` column of the `webhooks` table.
+pub fn join_events(events: &[WebhookEvent]) -> String {
+    let mut kinds: Vec<&str> = events.iter().map(WebhookEvent::as_str).collect();
+    kinds.sort_unstable();
+    kinds.dedup();
+
+    kinds.join(",")
+}
+
+/// Parses the `events` column of the `webhooks` table, skipping unknown events.
+pub fn split_events(value: &str) -> Vec<WebhookEvent> {
+    value
+        .split(',')
+        .filter_map(|kind| kind.parse().ok())
+        .collect()
+}
+
+/// Returns the `host:port` that a request to `url` goes to,
+/// or `None` if it has no host or its scheme has no known port.
+pub fn host(url: &Url) -> Option<String> {
+    Some(format!(
+        "{}:{}",
+        url.host_str()?,
+        url.port_or_known_default()?
+    ))
+}
+
+/// Returns the value of the `SIGNATURE_HEADER` of a request
+/// with `body` to a webhook with `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Makes the server-wide rows of the `webhooks` table match `webhooks`.
+///
+/// Rows that are still configured are kept along with their delivery log,
+/// everything else is removed and new webhooks are added.
+///
+pub async fn sync(pool: &SqlitePool, webhooks: &[WebhookConfig]) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let rows: Vec<(i64, String, String, String)> =
+        sqlx::query_as("SELECT id, url, events, secret FROM webhooks WHERE user_id IS NULL")
+            .fetch_all(&mut *tx)
+            .await?;
+
+    let mut configured: Vec<(String, String, String)> = webhooks
+        .iter()
+        .map(|webhook| {
+            (
+                webhook.url.trim().to_owned(),
+                join_events(&webhook.events),
+                webhook.secret.clone(),
+            )
+        })
+        .collect();
+
+    for (id, url, events, secret) in rows {
+        let row = (url, events, secret);
+
+        match configured.iter().position(|webhook| *webhook == row) {
+            Some(index) => {
+                configured.swap_remove(index);
+            }
+            None => {
+                sqlx::query("DELETE FROM webhooks WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+    }
+
+    for (url, events, secret) in configured {
+        sqlx::query("INSERT INTO webhooks (user_id, url, events, secret) VALUES (NULL, ?, ?, ?)")
+            .bind(url)
+            .bind(events)
+            .bind(secret)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await
+}
+
+/// Wakes the worker up to attempt every due delivery right away.
+pub fn wake() {
+    WORKER.wake();
+}
+
+/// Queues a delivery for every event published from now on, which never returns.
+pub async fn listen(app: AppContextGuard) {
+    let (pool, mut receiver) = {
+        let ctx = app.ctx().await;
+        (
+            ctx.pool().await.clone(),
+            ctx.events().subscribe(None).receiver,
+        )
+    };
+
+    loop {
+        match receiver.recv().await {
+            Ok(event) => match enqueue(&pool, &event).await {
+                Ok(0) => {}
+                Ok(_) => wake(),
+                Err(e) => warn!(err = ?e, event_id = event.id, "failed to queue webhooks"),
+            },
+            Err(RecvError::Lagged(missed)) => {
+                warn!(
+                    missed = missed,
+                    "fell behind on events, some were not posted"
+                );
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Queues a delivery of `event` for every webhook subscribed to it.
+///
+/// Returns how many deliveries were queued.
+///
+async fn enqueue(pool: &SqlitePool, event: &Event) -> Result<usize, sqlx::Error> {
+    let (kind, mail_id) = match event.payload {
+        EventPayload::Received { id } => (WebhookEvent::Received, Some(id)),
+        EventPayload::StatusChanged { id, .. } => (WebhookEvent::StatusChanged, Some(id)),
+        EventPayload::Trashed { id } => (WebhookEvent::Trashed, Some(id)),
+        EventPayload::Restored { id } => (WebhookEvent::Restored, Some(id)),
+        EventPayload::Deleted { .. } => (WebhookEvent::Deleted, None),
+        EventPayload::Resync => return Ok(0),
+    };
+
+    let webhooks: Vec<(i64, String)> =
+        sqlx::query_as("SELECT id, events FROM webhooks WHERE user_id = ? OR user_id IS NULL")
+            .bind(event.user_id)
+            .fetch_all(pool)
+            .await?;
+
+    let webhooks: Vec<i64> = webhooks
+        .into_iter()
+        .filter(|(_, events)| split_events(events).contains(&kind))
+        .map(|(id, _)| id)
+        .collect();
+
+    if webhooks.is_empty() {
+        return Ok(0);
+    }
+
+    let Some((user, created_at)): Option<(String, String)> =
+        sqlx::query_as("SELECT name, CURRENT_TIMESTAMP FROM users WHERE id = ?")
+            .bind(event.user_id)
+            .fetch_optional(pool)
+            .await?
+    else {
+        return Ok(0);
+    };
+
+    let mail = match mail_id {
+        Some(id) => mail_summary(pool, event.user_id, id).await?,
+        None => None,
+    };
+
+    let message = WebhookMessage {
+        user,
+        event: event.payload.clone(),
+        mail,
+        created_at,
+    };
+
+    let event = serde_json::to_string(&message.event).unwrap_or_default();
+    let body = serde_json::to_string(&message).unwrap_or_default();
+
+    let mut tx = pool.begin().await?;
+
+    for webhook_id in &webhooks {
+        sqlx::query("INSERT INTO webhook_deliveries (webhook_id, event, body) VALUES (?, ?, ?)")
+            .bind(webhook_id)
+            .bind(&event)
+            .bind(&body)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(webhooks.len())
+}
+
+async fn mail_summary(
+    pool: &SqlitePool,
+    user_id: i64,
+    id: i64,
+) -> Result<Option<MailSummary>, sqlx::Error> {
+    let row: Option<(String, String, String, String, String)> = sqlx::query_as(
+        "SELECT subject, sender, recipient, status, created_at
+            FROM mails WHERE id = ? AND user_id = ?",
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(
+        |(subject, sender, recipient, status, created_at)| MailSummary {
+            id,
+            subject,
+            sender,
+            recipient,
+            // The `CHECK` constraint on `status` guarantees this parses
+            status: status.parse().unwrap_or(MailStatus::Read),
+            created_at,
+        },
+    ))
+}
+
+/// Runs the worker, which never returns.
+///
+/// Every due delivery is attempted once per round, see `worker`.
+pub async fn run(app: AppContextGuard) {
+    WORKER.run::<WebhookRound>(app).await;
+}
+
+struct WebhookRound {
+    pool: SqlitePool,
+    cfg: DeliveryConfig,
+}
+
+impl worker::Round for WebhookRound {
+    const NAME: &'static str = "webhook deliveries";
+
+    const NEXT_DUE: &'static str =
+        "SELECT MAX(0, UNIXEPOCH(MIN(COALESCE(next_attempt_at, CURRENT_TIMESTAMP))) - UNIXEPOCH())
+            FROM webhook_deliveries WHERE status = 'pending'";
+
+    async fn load(app: &AppContextGuard) -> Self {
+        let ctx = app.ctx().await;
+        let cfg = ctx.cfg().await;
+
+        Self {
+            pool: ctx.pool().await.clone(),
+            cfg: cfg.delivery().await.clone(),
+        }
+    }
+
+    fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    /// Attempts every pending delivery whose next attempt is due.
+    async fn process(&self) -> Result<(), sqlx::Error> {
+        let due: Vec<(i64, String, String, String)> = sqlx::query_as(
+            "SELECT webhook_deliveries.id, webhooks.url, webhooks.secret, webhook_deliveries.body
+                FROM webhook_deliveries
+                JOIN webhooks ON webhooks.id = webhook_deliveries.webhook_id
+                WHERE webhook_deliveries.status = 'pending'
+                  AND (webhook_deliveries.next_attempt_at IS NULL
+                    OR webhook_deliveries.next_attempt_at <= CURRENT_TIMESTAMP)
+                ORDER BY webhook_deliveries.id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut attempts = Attempts::default();
+
+        for (id, url, secret, body) in due {
+            let (pool, cfg) = (self.pool.clone(), self.cfg.clone());
+
+            attempts
+                .spawn(async move {
+                    let attempt = post(&url, &secret, id, body).await;
+                    record(&pool, &cfg, id, attempt).await
+                })
+                .await;
+        }
+
+        attempts.join().await;
+
+        Ok(())
+    }
+}
+
+/// Posts `body` to the webhook at `url`, signed with `secret`.
+///
+/// The error of an attempt ends up in the delivery log of the webhook, so it
+/// never says more than whether and how `url` responded, e.g, not why it
+/// could not be reached, which would tell its owner about the network.
+async fn post(url: &str, secret: &str, id: i64, body: String) -> Attempt {
+    let host = Url::parse(url).ok().as_ref().and_then(host);
+
+    if let Err(e) = match &host {
+        Some(host) => net::check(host).await,
+        None => Err(NetError::NotPublic(url.to_owned())),
+    } {
+        return Attempt {
+            status_code: None,
+            outcome: match e {
+                NetError::Resolve(..) => Outcome::Retry(format!("could not resolve {url}")),
+                NetError::NotPublic(_) => Outcome::Fail(format!("{url} is not a public host")),
+            },
+        };
+    }
+
+    let result = CLIENT
+        .post(url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, sign(secret, body.as_bytes()))
+        .header(DELIVERY_HEADER, id.to_string())
+        .body(body)
+        .send()
+        .await;
+
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => {
+            warn!(err = ?e, url = url, "failed to post webhook");
+
+            return Attempt {
+                status_code: None,
+                outcome: Outcome::Retry(format!("could not reach {url}")),
+            };
+        }
+    };
+
+    let status = response.status();
+
+    Attempt {
+        status_code: Some(status.as_u16()),
+        outcome: Outcome::from_status(status, format!("{url} responded with {status}")),
+    }
+}
+
+/// Records `attempt` at posting the delivery `id` in `webhook_attempts`
+/// and settles it, see `worker::settle`.
+#[instrument(skip(pool, cfg))]
+async fn record(
+    pool: &SqlitePool,
+    cfg: &DeliveryConfig,
+    id: i64,
+    attempt: Attempt,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("INSERT INTO webhook_attempts (delivery_id, status_code, error) VALUES (?, ?, ?)")
+        .bind(id)
+        .bind(attempt.status_code)
+        .bind(attempt.outcome.error())
+        .execute(&mut *tx)
+        .await?;
+
+    worker::settle(&mut tx, "webhook_deliveries", cfg, id, attempt.outcome).await?;
+
+    tx.commit().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_matches_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn events_round_trip() {
+        let events = [
+            WebhookEvent::Trashed,
+            WebhookEvent::Received,
+            WebhookEvent::Trashed,
+            WebhookEvent::StatusChanged,
+        ];
+
+        let joined = join_events(&events);
+        assert_eq!(joined, "received,status_changed,trashed");
+        assert_eq!(
+            split_events(&joined),
+            [
+                WebhookEvent::Received,
+                WebhookEvent::StatusChanged,
+                WebhookEvent::Trashed,
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_events_are_skipped() {
+        assert_eq!(
+            split_events("deleted,unknown,,restored"),
+            [WebhookEvent::Deleted, WebhookEvent::Restored]
+        );
+        assert_eq!(split_events(""), []);
+    }
+}
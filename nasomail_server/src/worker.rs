@@ -0,0 +1,227 @@
+//! The background workers of `queue`, `webhook` and `schedule`, which
+//! work through whatever is due in their table in rounds.
+//!
+//! A new round starts whenever a worker is woken up, e.g, because something
+//! was queued, or once the next item is due, and at least every
+//! `POLL_INTERVAL`, in case something was queued without waking it up.
+//! Failed deliveries are retried with the backoff of `DeliveryConfig`,
+//! see `settle`.
+
+use std::future::Future;
+
+use sqlx::{SqliteConnection, sqlite::SqlitePool};
+use tokio::{
+    sync::Notify,
+    task::{JoinError, JoinSet},
+    time::{self, Duration},
+};
+use tracing::{info, warn};
+
+use crate::{app::AppContextGuard, config::DeliveryConfig, queue::Outcome};
+
+/// The longest a worker sleeps between rounds, in case
+/// something was queued without calling `Worker::wake`.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How many attempts run at once, so a long queue does not
+/// open a connection to every remote host at the same time.
+const MAX_CONCURRENT: usize = 16;
+
+/// A single round of a worker.
+pub trait Round: Sized {
+    /// What the worker is called in logs.
+    const NAME: &'static str;
+
+    /// A query for in how many seconds the next item is due,
+    /// which is `NULL` if there is none, at least `0`.
+    const NEXT_DUE: &'static str;
+
+    /// Clones everything the round needs out of `app`,
+    /// so the context is not held while processing.
+    fn load(app: &AppContextGuard) -> impl Future<Output = Self> + Send;
+
+    fn pool(&self) -> &SqlitePool;
+
+    /// Attempts every item that is due.
+    fn process(&self) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+}
+
+/// A background worker, which is woken up with `wake`.
+pub struct Worker {
+    wake: Notify,
+}
+
+impl Worker {
+    pub const fn new() -> Self {
+        Self {
+            wake: Notify::const_new(),
+        }
+    }
+
+    /// Wakes the worker up to attempt every due item right away.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Runs the worker with rounds of `R`, which never returns.
+    pub async fn run<R: Round>(&self, app: AppContextGuard) {
+        loop {
+            let round = R::load(&app).await;
+
+            if let Err(e) = round.process().await {
+                warn!(err = ?e, worker = R::NAME, "failed to process");
+            }
+
+            let sleep = match next_due(round.pool(), R::NEXT_DUE).await {
+                // Sleep for at least a second so an item that keeps failing
+                // to be recorded does not turn this into a busy loop
+                Ok(Some(secs)) => Duration::from_secs(secs.max(1)).min(POLL_INTERVAL),
+                Ok(None) => POLL_INTERVAL,
+                Err(e) => {
+                    warn!(err = ?e, worker = R::NAME, "failed to query next due item");
+                    POLL_INTERVAL
+                }
+            };
+
+            tokio::select! {
+                _ = self.wake.notified() => {}
+                _ = time::sleep(sleep) => {}
+            }
+        }
+    }
+}
+
+/// Returns in how many seconds the next item is due according
+/// to the `query` of `Round::NEXT_DUE`, or `None` if none is.
+async fn next_due(pool: &SqlitePool, query: &str) -> Result<Option<u64>, sqlx::Error> {
+    let secs: Option<i64> = sqlx::query_scalar(query).fetch_one(pool).await?;
+
+    Ok(secs.map(|secs| u64::try_from(secs).unwrap_or_default()))
+}
+
+/// Attempts that run concurrently, up to `MAX_CONCURRENT` at a time.
+#[derive(Default)]
+pub struct Attempts(JoinSet<Result<(), sqlx::Error>>);
+
+impl Attempts {
+    /// Spawns `attempt`, first waiting for one to
+    /// finish if `MAX_CONCURRENT` are running.
+    pub async fn spawn<F>(&mut self, attempt: F)
+    where
+        F: Future<Output = Result<(), sqlx::Error>> + Send + 'static,
+    {
+        if self.0.len() >= MAX_CONCURRENT {
+            joined(self.0.join_next().await);
+        }
+
+        self.0.spawn(attempt);
+    }
+
+    /// Waits for every attempt to finish.
+    pub async fn join(mut self) {
+        while let Some(result) = self.0.join_next().await {
+            joined(Some(result));
+        }
+    }
+}
+
+/// Logs the `result` of an attempt if it failed.
+fn joined(result: Option<Result<Result<(), sqlx::Error>, JoinError>>) {
+    match result {
+        Some(Ok(Err(e))) => warn!(err = ?e, "failed to record attempt"),
+        Some(Err(e)) => warn!(err = ?e, "attempt panicked"),
+        Some(Ok(Ok(()))) | None => {}
+    }
+}
+
+/// What became of an item after an attempt at it, see `settle`.
+#[derive(Debug)]
+pub enum Settled {
+    Delivered,
+
+    /// The next attempt is scheduled.
+    Pending,
+
+    /// The item was given up on, for the given reason.
+    Failed(String),
+}
+
+/// Counts an attempt with `outcome` at the row `id` of `table`, which has
+/// `status`, `attempts`, `created_at` and `next_attempt_at` columns like
+/// `delivery_queue`, and updates its `status` accordingly.
+///
+/// Attempts worth retrying are retried after `DeliveryConfig::backoff_secs`,
+/// unless the row is older than `lifetime_secs`, in which case it is given
+/// up on like after an attempt that is not worth retrying.
+///
+pub async fn settle(
+    conn: &mut SqliteConnection,
+    table: &str,
+    cfg: &DeliveryConfig,
+    id: i64,
+    outcome: Outcome,
+) -> Result<Settled, sqlx::Error> {
+    let (attempts, expired): (u32, bool) = sqlx::query_as(&format!(
+        "UPDATE {table} SET attempts = attempts + 1
+            WHERE id = ?
+            RETURNING attempts,
+                -- `DATETIME` is NULL for lifetimes too long to ever run out
+                COALESCE(created_at <= DATETIME('now', '-' || ? || ' seconds'), FALSE)"
+    ))
+    .bind(id)
+    .bind(i64::try_from(cfg.lifetime_secs).unwrap_or(i64::MAX))
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let settled = match outcome {
+        Outcome::Delivered => {
+            info!(attempts = attempts, "delivered");
+            Settled::Delivered
+        }
+        Outcome::Retry(error) if !expired => {
+            let backoff = cfg.backoff_secs(attempts);
+            warn!(
+                err = error,
+                attempts = attempts,
+                backoff_secs = backoff,
+                "retrying later"
+            );
+
+            sqlx::query(&format!(
+                "UPDATE {table}
+                    SET next_attempt_at = COALESCE(
+                        DATETIME('now', '+' || ? || ' seconds'),
+                        '9999-12-31 23:59:59'
+                    )
+                    WHERE id = ?"
+            ))
+            .bind(i64::try_from(backoff).unwrap_or(i64::MAX))
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+
+            Settled::Pending
+        }
+        Outcome::Retry(error) => Settled::Failed(format!(
+            "gave up after {attempts} attempts, the last one failed: {error}"
+        )),
+        Outcome::Fail(error) => Settled::Failed(error),
+    };
+
+    let status = match &settled {
+        Settled::Delivered => "delivered",
+        Settled::Pending => "pending",
+        Settled::Failed(reason) => {
+            warn!(reason = reason, "giving up");
+            "failed"
+        }
+    };
+
+    sqlx::query(&format!("UPDATE {table} SET status = ? WHERE id = ?"))
+        .bind(status)
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(settled)
+}
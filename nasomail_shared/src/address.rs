@@ -0,0 +1,87 @@
+//! Mail addresses, which are either a bare username
+//! or a username at a NasoMail server, i.e, `user@host:port`.
+
+use std::{fmt, str::FromStr};
+
+/// A parsed mail address.
+///
+/// `host` is the `pub_addr` of the server the user is registered on,
+/// or `None` for a bare username, which always refers to a user
+/// of the server the address is handed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub user: String,
+    pub host: Option<String>,
+}
+
+impl Address {
+    /// Returns whether the address refers to a user of the
+    /// server whose `pub_addr` is `local_host`, see `is_local_host`.
+    pub fn is_local(&self, local_host: &str) -> bool {
+        self.host
+            .as_deref()
+            .is_none_or(|host| is_local_host(host, local_host))
+    }
+}
+
+/// Returns whether `host` is the `pub_addr` `local_host`, with or without
+/// its port, so regular email addresses of a server are local as well.
+pub fn is_local_host(host: &str, local_host: &str) -> bool {
+    host.eq_ignore_ascii_case(local_host) || host.eq_ignore_ascii_case(host_name(local_host))
+}
+
+/// Checks that `host` looks like a `host` or `host:port` that
+/// can be put into a URL as is, without any path or credentials.
+pub fn is_valid_host(host: &str) -> bool {
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) => (name, Some(port)),
+        None => (host, None),
+    };
+
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        && port.is_none_or(|port| port.parse::<u16>().is_ok())
+}
+
+/// Strips the port off a `host:port`, e.g, to use the
+/// `pub_addr` of a server as a domain of email addresses.
+pub fn host_name(host: &str) -> &str {
+    host.rsplit_once(':').map_or(host, |(name, _)| name)
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.host {
+            Some(host) => write!(f, "{}@{}", self.user, host),
+            None => f.write_str(&self.user),
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (user, host) = match s.split_once('@') {
+            Some((user, host)) => (user, Some(host)),
+            None => (s, None),
+        };
+
+        if user.is_empty() || user.chars().any(char::is_whitespace) {
+            return Err(format!("invalid user in address: {s}"));
+        }
+
+        if let Some(host) = host
+            && !is_valid_host(host)
+        {
+            return Err(format!("invalid host in address: {s}"));
+        }
+
+        Ok(Self {
+            user: user.to_owned(),
+            host: host.map(|host| host.to_lowercase()),
+        })
+    }
+}
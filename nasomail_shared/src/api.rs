@@ -9,8 +9,61 @@ pub const API_USERS_HAS: &str = "/has";
 pub const API_USERS_AUTH: &str = "/auth";
 pub const API_USERS_REGISTER: &str = "/register";
 
+pub const API_SESSIONS: &str = "/sessions";
+pub const API_SESSIONS_LOGIN: &str = "/login";
+pub const API_SESSIONS_LOGOUT: &str = "/logout";
+pub const API_SESSIONS_CURRENT: &str = "/current";
+
+pub const API_MAILS: &str = "/mails";
+pub const API_MAILS_ROOT: &str = "/";
+pub const API_MAILS_ID: &str = "/{id}";
+pub const API_MAILS_ID_STATUS: &str = "/{id}/status";
+pub const API_MAILS_ID_REPLY: &str = "/{id}/reply";
+pub const API_MAILS_ID_FORWARD: &str = "/{id}/forward";
+pub const API_MAILS_ID_RESTORE: &str = "/{id}/restore";
+pub const API_MAILS_SEARCH: &str = "/search";
+
+pub const API_THREADS: &str = "/threads";
+pub const API_THREADS_ROOT: &str = "/";
+pub const API_THREADS_ID: &str = "/{id}";
+
+pub const API_LABELS: &str = "/labels";
+pub const API_LABELS_ROOT: &str = "/";
+pub const API_LABELS_ID: &str = "/{id}";
+pub const API_LABELS_ID_MAILS: &str = "/{id}/mails";
+
+pub const API_FILTER: &str = "/filter";
+pub const API_FILTER_ROOT: &str = "/";
+pub const API_FILTER_VALIDATE: &str = "/validate";
+
+pub const API_VACATION: &str = "/vacation";
+pub const API_VACATION_ROOT: &str = "/";
+
+pub const API_DRAFTS: &str = "/drafts";
+pub const API_DRAFTS_ROOT: &str = "/";
+pub const API_DRAFTS_ID: &str = "/{id}";
+pub const API_DRAFTS_ID_SEND: &str = "/{id}/send";
+pub const API_DRAFTS_ID_CANCEL: &str = "/{id}/cancel";
+pub const API_DRAFTS_ID_ATTACHMENTS: &str = "/{id}/attachments";
+
+pub const API_ATTACHMENTS: &str = "/attachments";
+pub const API_ATTACHMENTS_ID: &str = "/{id}";
+
+pub const API_EVENTS: &str = "/events";
+
+pub const API_WEBHOOKS: &str = "/webhooks";
+pub const API_WEBHOOKS_ROOT: &str = "/";
+pub const API_WEBHOOKS_ID: &str = "/{id}";
+pub const API_WEBHOOKS_ID_DELIVERIES: &str = "/{id}/deliveries";
+
+pub const API_FEDERATION: &str = "/federation";
+pub const API_FEDERATION_DELIVER: &str = "/deliver";
+pub const API_FEDERATION_OUTBOUND_TOKEN: &str = "/outbound/{token}";
+pub const API_FEDERATION_OUTBOUND_TOKEN_ATTACHMENTS_INDEX: &str =
+    "/outbound/{token}/attachments/{index}";
+
 pub fn api_absolute() -> String {
-    format!("{}", API)
+    API.to_string()
 }
 
 pub fn api_ctest_absolute() -> String {
@@ -32,3 +85,212 @@ pub fn api_users_auth_absolute() -> String {
 pub fn api_users_register_absolute() -> String {
     format!("{}{}", api_users_absolute(), API_USERS_REGISTER)
 }
+
+pub fn api_sessions_absolute() -> String {
+    format!("{}{}", api_absolute(), API_SESSIONS)
+}
+
+pub fn api_sessions_login_absolute() -> String {
+    format!("{}{}", api_sessions_absolute(), API_SESSIONS_LOGIN)
+}
+
+pub fn api_sessions_logout_absolute() -> String {
+    format!("{}{}", api_sessions_absolute(), API_SESSIONS_LOGOUT)
+}
+
+pub fn api_sessions_current_absolute() -> String {
+    format!("{}{}", api_sessions_absolute(), API_SESSIONS_CURRENT)
+}
+
+pub fn api_mails_absolute() -> String {
+    format!("{}{}", api_absolute(), API_MAILS)
+}
+
+pub fn api_mails_id_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_mails_absolute(),
+        API_MAILS_ID.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_mails_id_status_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_mails_absolute(),
+        API_MAILS_ID_STATUS.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_mails_id_reply_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_mails_absolute(),
+        API_MAILS_ID_REPLY.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_mails_id_forward_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_mails_absolute(),
+        API_MAILS_ID_FORWARD.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_mails_id_restore_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_mails_absolute(),
+        API_MAILS_ID_RESTORE.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_mails_search_absolute() -> String {
+    format!("{}{}", api_mails_absolute(), API_MAILS_SEARCH)
+}
+
+pub fn api_threads_absolute() -> String {
+    format!("{}{}", api_absolute(), API_THREADS)
+}
+
+pub fn api_threads_id_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_threads_absolute(),
+        API_THREADS_ID.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_labels_absolute() -> String {
+    format!("{}{}", api_absolute(), API_LABELS)
+}
+
+pub fn api_labels_id_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_labels_absolute(),
+        API_LABELS_ID.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_labels_id_mails_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_labels_absolute(),
+        API_LABELS_ID_MAILS.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_filter_absolute() -> String {
+    format!("{}{}", api_absolute(), API_FILTER)
+}
+
+pub fn api_filter_validate_absolute() -> String {
+    format!("{}{}", api_filter_absolute(), API_FILTER_VALIDATE)
+}
+
+pub fn api_vacation_absolute() -> String {
+    format!("{}{}", api_absolute(), API_VACATION)
+}
+
+pub fn api_drafts_absolute() -> String {
+    format!("{}{}", api_absolute(), API_DRAFTS)
+}
+
+pub fn api_drafts_id_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_drafts_absolute(),
+        API_DRAFTS_ID.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_drafts_id_send_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_drafts_absolute(),
+        API_DRAFTS_ID_SEND.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_drafts_id_cancel_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_drafts_absolute(),
+        API_DRAFTS_ID_CANCEL.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_drafts_id_attachments_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_drafts_absolute(),
+        API_DRAFTS_ID_ATTACHMENTS.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_attachments_absolute() -> String {
+    format!("{}{}", api_absolute(), API_ATTACHMENTS)
+}
+
+pub fn api_attachments_id_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_attachments_absolute(),
+        API_ATTACHMENTS_ID.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_events_absolute() -> String {
+    format!("{}{}", api_absolute(), API_EVENTS)
+}
+
+pub fn api_webhooks_absolute() -> String {
+    format!("{}{}", api_absolute(), API_WEBHOOKS)
+}
+
+pub fn api_webhooks_id_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_webhooks_absolute(),
+        API_WEBHOOKS_ID.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_webhooks_id_deliveries_absolute(id: i64) -> String {
+    format!(
+        "{}{}",
+        api_webhooks_absolute(),
+        API_WEBHOOKS_ID_DELIVERIES.replace("{id}", &id.to_string())
+    )
+}
+
+pub fn api_federation_absolute() -> String {
+    format!("{}{}", api_absolute(), API_FEDERATION)
+}
+
+pub fn api_federation_deliver_absolute() -> String {
+    format!("{}{}", api_federation_absolute(), API_FEDERATION_DELIVER)
+}
+
+pub fn api_federation_outbound_token_absolute(token: &str) -> String {
+    format!(
+        "{}{}",
+        api_federation_absolute(),
+        API_FEDERATION_OUTBOUND_TOKEN.replace("{token}", token)
+    )
+}
+
+pub fn api_federation_outbound_token_attachments_index_absolute(
+    token: &str,
+    index: usize,
+) -> String {
+    format!(
+        "{}{}",
+        api_federation_absolute(),
+        API_FEDERATION_OUTBOUND_TOKEN_ATTACHMENTS_INDEX
+            .replace("{token}", token)
+            .replace("{index}", &index.to_string())
+    )
+}
@@ -1,3 +1,4 @@
+pub mod address;
 pub mod api;
 pub mod payload;
 pub mod query;
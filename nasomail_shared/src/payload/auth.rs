@@ -18,3 +18,13 @@ pub struct AuthPayload {
 pub struct PassOnlyAuthPayload {
     pub passphrase: String,
 }
+
+/// A bearer token issued by the server when a user logs in.
+///
+/// The token is opaque to the client and must be sent in the
+/// `Authorization: Bearer` header of authenticated requests.
+#[derive(Serialize, Deserialize)]
+pub struct SessionPayload {
+    pub token: String,
+    pub expires_at: String, // An SQLite `DATETIME`, in UTC
+}
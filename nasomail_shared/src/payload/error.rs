@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// The body returned by the `nasomail_server` REST API
+/// whenever a request is rejected with a structured error,
+/// such as when one or more fields fail validation.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ErrorPayload {
+    pub error: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+/// Describes why a single field of a request was rejected.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+
+    #[serde(flatten)]
+    pub kind: FieldErrorKind,
+}
+
+/// The reason a field of a request was rejected.
+///
+/// Lengths are counted in characters, the same way
+/// SQLite's `LENGTH` counts them for `TEXT` columns.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FieldErrorKind {
+    /// The value has leading or trailing whitespace.
+    Untrimmed,
+    TooShort {
+        min: usize,
+    },
+    TooLong {
+        max: usize,
+    },
+
+    /// The value clashes with an existing unique value,
+    /// e.g, a username that is already registered.
+    Taken,
+
+    /// The value is well-formed but not allowed here,
+    /// e.g, marking a sent mail as unread.
+    Invalid,
+
+    /// The value could not be parsed,
+    /// e.g, a pagination cursor that was tampered with.
+    Malformed,
+
+    /// The value refers to something that does not exist,
+    /// e.g, a recipient that is not a registered user.
+    NotFound,
+}
+
+impl FieldError {
+    pub fn new(field: &str, kind: FieldErrorKind) -> Self {
+        Self {
+            field: field.to_owned(),
+            kind,
+        }
+    }
+}
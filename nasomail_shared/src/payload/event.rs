@@ -0,0 +1,46 @@
+//! Payloads of the `/api/events` stream.
+
+use serde::{Deserialize, Serialize};
+
+use crate::payload::mail::MailStatus;
+
+/// Something that happened to the mails of the authenticated user.
+///
+/// Sent as the data of a Server-Sent Event whose
+/// `event` field is the `type` of the payload.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventPayload {
+    /// A mail arrived in the inbox.
+    Received { id: i64 },
+
+    /// The `status` of a mail changed, e.g, it was read or a draft was sent.
+    StatusChanged { id: i64, status: MailStatus },
+
+    /// A mail was moved to the trash.
+    Trashed { id: i64 },
+
+    /// A mail was restored from the trash.
+    Restored { id: i64 },
+
+    /// A mail was deleted for good.
+    Deleted { id: i64 },
+
+    /// Events may have been missed, e.g, because `Last-Event-ID` is too old
+    /// to be replayed, so anything cached should be fetched again.
+    Resync,
+}
+
+impl EventPayload {
+    /// Returns the `type` of the payload.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Received { .. } => "received",
+            Self::StatusChanged { .. } => "status_changed",
+            Self::Trashed { .. } => "trashed",
+            Self::Restored { .. } => "restored",
+            Self::Deleted { .. } => "deleted",
+            Self::Resync => "resync",
+        }
+    }
+}
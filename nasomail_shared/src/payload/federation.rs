@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+/// Tells a server that a mail for one of its users is waiting
+/// on the server at `origin` and can be fetched with `token`.
+#[derive(Serialize, Deserialize)]
+pub struct DeliverPayload {
+    pub origin: String, // The `pub_addr` of the sending server
+    pub token: String,
+}
+
+/// A mail waiting on its origin server to be fetched
+/// by the server of its recipient.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FederatedMail {
+    pub sender: String,    // The name of the sender on the origin server
+    pub recipient: String, // The address of the recipient, i.e, `user@host:port`
+    pub subject: String,
+    pub body: String,
+    pub attachments: Vec<FederatedAttachment>,
+
+    // Servers from before threading do not send these
+    #[serde(default)]
+    pub message_id: Option<String>,
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+    #[serde(default)]
+    pub references: Vec<String>,
+
+    /// Whether the mail was generated automatically,
+    /// e.g, an out-of-office reply, see RFC 3834.
+    #[serde(default)]
+    pub auto_submitted: bool,
+}
+
+/// The metadata of an attachment of a `FederatedMail`,
+/// whose data is fetched separately by its index.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FederatedAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+    pub sha256: Option<String>,
+}
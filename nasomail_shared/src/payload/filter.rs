@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+// This mirrors the `CHECK` constraint of the `filters` table,
+// the length is counted in characters.
+pub const SCRIPT_MAX_LEN: usize = 64 * 1024;
+
+/// The Sieve script that filters the mails delivered to the authenticated user.
+///
+/// See `sieve` in `nasomail_server` for the supported subset of Sieve.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilterPayload {
+    pub script: String,
+}
+
+/// A problem with a Sieve script, `line` and `column` are counted from `1`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilterError {
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+}
+
+/// Every problem with a Sieve script, sorted by where they are.
+///
+/// `errors` is empty if the script is valid.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilterValidationPayload {
+    pub errors: Vec<FilterError>,
+}
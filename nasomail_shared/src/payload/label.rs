@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+// These mirror the `CHECK` constraints of the `labels` table,
+// lengths are counted in characters.
+pub const NAME_MAX_LEN: usize = 64;
+
+/// How many mails can be labeled or unlabeled at once.
+pub const MAILS_MAX_LEN: usize = 1000;
+
+/// A new label of the authenticated user, or new values for an existing one.
+///
+/// `name` must be unique among the labels of the user, ignoring case,
+/// and `color` must be a `#rrggbb` hex color.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LabelPayload {
+    pub name: String,
+    pub color: String,
+}
+
+/// A label along with how many mails have it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LabelSummary {
+    pub id: i64,
+    pub name: String,
+    pub color: String, // A lowercase `#rrggbb` hex color
+
+    pub count: u32,
+    pub unread: u32,
+
+    pub created_at: String, // An SQLite `DATETIME`, in UTC
+}
+
+/// The mails to add a label to or remove it from.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LabelMailsPayload {
+    pub ids: Vec<i64>,
+}
@@ -0,0 +1,191 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+// These mirror the `CHECK` constraints of the `mails` table,
+// lengths are counted in characters.
+pub const SUBJECT_MAX_LEN: usize = 255;
+pub const BODY_MAX_LEN: usize = 1024 * 1024;
+pub const ADDRESS_MAX_LEN: usize = 255;
+pub const ATTACHMENT_MAX_SIZE: u64 = 1024 * 1024 * 1000; // In bytes
+pub const MESSAGE_ID_MAX_LEN: usize = 255;
+pub const REFERENCES_MAX_LEN: usize = 8192;
+
+/// Everything necessary to send a mail
+/// as the currently authenticated user.
+#[derive(Serialize, Deserialize)]
+pub struct SendMailPayload {
+    pub recipient: String, // A local username or a `user@host:port` address
+    pub subject: String,
+    pub body: String,
+
+    /// When to send the mail, an SQLite `DATETIME` in UTC, see `query::mail::DraftSendQuery`.
+    #[serde(default)]
+    pub send_at: Option<String>,
+}
+
+/// A reply to a mail of the currently authenticated user.
+///
+/// The recipient, subject and threading headers are taken from the
+/// mail that is replied to, which is quoted below `body`.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ReplyPayload {
+    pub body: String,
+
+    /// When to send the reply, an SQLite `DATETIME` in UTC, see `query::mail::DraftSendQuery`.
+    pub send_at: Option<String>,
+}
+
+/// A forward of a mail of the currently authenticated user to `recipient`.
+///
+/// The subject and threading headers are taken from the forwarded mail,
+/// which is included below `body` along with all of its attachments.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ForwardPayload {
+    pub recipient: String,
+    pub body: String,
+
+    /// When to send the forward, an SQLite `DATETIME` in UTC, see `query::mail::DraftSendQuery`.
+    pub send_at: Option<String>,
+}
+
+/// The contents of a draft.
+///
+/// Every field may be left empty while the draft
+/// is being edited, the recipient is only checked
+/// once the draft is sent.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DraftPayload {
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// The `status` column of the `mails` table.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MailStatus {
+    New,
+    Read,
+    Draft,
+    Sent,
+}
+
+impl MailStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::New => "new",
+            Self::Read => "read",
+            Self::Draft => "draft",
+            Self::Sent => "sent",
+        }
+    }
+}
+
+impl fmt::Display for MailStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MailStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "new" => Ok(Self::New),
+            "read" => Ok(Self::Read),
+            "draft" => Ok(Self::Draft),
+            "sent" => Ok(Self::Sent),
+            _ => Err(format!("unknown mail status: {s}")),
+        }
+    }
+}
+
+/// A mail without its body, for use in listings.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MailSummary {
+    pub id: i64,
+    pub subject: String,
+    pub sender: String,
+    pub recipient: String,
+    pub status: MailStatus,
+    pub created_at: String, // An SQLite `DATETIME`, in UTC
+}
+
+/// A complete mail, including its body and
+/// the metadata of its attachments.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MailDetail {
+    pub id: i64,
+    pub subject: String,
+    pub body: String,
+    pub sender: String,
+    pub recipient: String,
+    pub status: MailStatus,
+    pub created_at: String, // An SQLite `DATETIME`, in UTC
+
+    /// The `Message-ID` of the mail, without angle brackets.
+    pub message_id: String,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+
+    /// The `id` of the thread the mail belongs to, see `ThreadSummary`.
+    pub thread_id: i64,
+
+    /// The `id`s of the labels of the mail, see `LabelSummary`.
+    pub labels: Vec<i64>,
+
+    /// When the mail was moved to the trash, `None` if it is not in the trash.
+    pub deleted_at: Option<String>,
+
+    /// When the draft is going to be sent, `None` if it is not scheduled.
+    pub send_at: Option<String>,
+
+    pub attachments: Vec<AttachmentSummary>,
+}
+
+/// Surround the matched terms in `MailSearchHit::subject` and
+/// `MailSearchHit::snippet`. The text in between is not escaped.
+pub const HIGHLIGHT_START: &str = "<mark>";
+pub const HIGHLIGHT_END: &str = "</mark>";
+
+/// A mail matching a search along with where it matched.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MailSearchHit {
+    pub mail: MailSummary,
+
+    /// The subject of the mail with the matched terms highlighted.
+    pub subject: String,
+
+    /// An excerpt of the body around the matched terms, with them highlighted,
+    /// or the beginning of the body if only other fields matched.
+    pub snippet: String,
+}
+
+/// The metadata of an attachment, without its data.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AttachmentSummary {
+    pub id: i64,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64, // In bytes
+
+    /// A hex-encoded SHA-256 digest of the data, `None` while the upload
+    /// is in progress or for attachments uploaded before digests were stored.
+    pub sha256: Option<String>,
+
+    pub created_at: String,
+}
+
+/// A new `status` for a mail.
+///
+/// Only received mails can be updated and only
+/// between `MailStatus::New` and `MailStatus::Read`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct MailStatusPayload {
+    pub status: MailStatus,
+}
@@ -7,8 +7,35 @@
 use serde::{Deserialize, Serialize};
 
 pub mod auth;
+pub mod error;
+pub mod event;
+pub mod federation;
+pub mod filter;
+pub mod label;
+pub mod mail;
+pub mod thread;
+pub mod user;
+pub mod vacation;
+pub mod webhook;
 
 #[derive(Serialize, Deserialize)]
 pub struct BoolPayload {
     pub result: bool,
 }
+
+/// Contains the `id` of a row that
+/// was created by the server.
+#[derive(Serialize, Deserialize)]
+pub struct IdPayload {
+    pub id: i64,
+}
+
+/// A single page of a listing.
+///
+/// Pass `next_cursor` back as the `cursor` query parameter
+/// to fetch the following page, it is `None` on the last page.
+#[derive(Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+use crate::payload::mail::MailSummary;
+
+/// A conversation, i.e, a mail along with every reply to it and
+/// every reply to those, in the mailbox of a single user.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThreadSummary {
+    /// The `id` of the first mail of the thread, also
+    /// after that mail was deleted.
+    pub id: i64,
+
+    /// The subject of the oldest mail that is left in the thread.
+    pub subject: String,
+
+    pub count: u32,
+    pub unread: u32,
+
+    /// The newest mail of the thread.
+    pub latest: MailSummary,
+}
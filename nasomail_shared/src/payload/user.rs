@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+/// Public information about a user account.
+#[derive(Serialize, Deserialize)]
+pub struct UserPayload {
+    pub id: i64,
+    pub name: String,
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+// These mirror the `CHECK` constraints of the `vacations` table,
+// lengths are counted in characters.
+pub const SUBJECT_MAX_LEN: usize = 255;
+pub const BODY_MAX_LEN: usize = 64 * 1024;
+pub const DAYS_MAX: u32 = 365;
+
+/// How many days to wait before replying to the same sender again, by default.
+pub const DAYS_DEFAULT: u32 = 7;
+
+/// The out-of-office reply of the authenticated user.
+///
+/// `start` and `end` are `YYYY-MM-DD` dates in UTC and are both included,
+/// every sender gets the reply at most once every `days` days.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VacationPayload {
+    pub start: String,
+    pub end: String,
+
+    pub subject: String,
+    pub body: String,
+
+    #[serde(default = "days_default")]
+    pub days: u32,
+}
+
+fn days_default() -> u32 {
+    DAYS_DEFAULT
+}
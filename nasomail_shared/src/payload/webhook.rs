@@ -0,0 +1,114 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::payload::{event::EventPayload, mail::MailSummary};
+
+// These mirror the `CHECK` constraints of the `webhooks` table,
+// lengths are counted in characters.
+pub const URL_MAX_LEN: usize = 2048;
+pub const SECRET_MAX_LEN: usize = 255;
+
+/// The header carrying the signature of a webhook request, i.e,
+/// `sha256=` followed by the hex-encoded HMAC-SHA256 of the body
+/// keyed with the secret of the webhook.
+pub const SIGNATURE_HEADER: &str = "X-NasoMail-Signature";
+
+/// The header carrying the `id` of the delivery a webhook request belongs
+/// to, which stays the same when it is retried.
+pub const DELIVERY_HEADER: &str = "X-NasoMail-Delivery";
+
+/// The types of events a webhook can be subscribed to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    Received,
+    StatusChanged,
+    Trashed,
+    Restored,
+    Deleted,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Received => "received",
+            Self::StatusChanged => "status_changed",
+            Self::Trashed => "trashed",
+            Self::Restored => "restored",
+            Self::Deleted => "deleted",
+        }
+    }
+}
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "received" => Ok(Self::Received),
+            "status_changed" => Ok(Self::StatusChanged),
+            "trashed" => Ok(Self::Trashed),
+            "restored" => Ok(Self::Restored),
+            "deleted" => Ok(Self::Deleted),
+            _ => Err(format!("unknown webhook event: {s}")),
+        }
+    }
+}
+
+/// A new webhook of the authenticated user.
+///
+/// `url` must be an `http` or `https` URL and
+/// `events` must contain at least one event.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookPayload {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub secret: String,
+}
+
+/// A webhook, without its secret.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookSummary {
+    pub id: i64,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: String, // An SQLite `DATETIME`, in UTC
+}
+
+/// An entry of the delivery log of a webhook.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookDeliverySummary {
+    pub id: i64,
+    pub event: EventPayload,
+
+    /// `pending`, `delivered` or `failed`, once every retry failed.
+    pub status: String,
+    pub attempts: u32,
+
+    /// The HTTP status of the last attempt, `None` if the URL could not be reached.
+    pub last_status_code: Option<u16>,
+    pub last_error: Option<String>,
+
+    pub created_at: String,
+}
+
+/// The body of a webhook request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookMessage {
+    /// The name of the user the event is about.
+    pub user: String,
+    pub event: EventPayload,
+
+    /// The mail the event is about, `None` if it no longer exists.
+    pub mail: Option<MailSummary>,
+
+    /// When the event happened, as an SQLite `DATETIME` in UTC.
+    pub created_at: String,
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use crate::payload::mail::MailStatus;
+
+/// Filters and pagination for listing the mails
+/// of the currently authenticated user.
+///
+/// Every filter is optional, `since` and `until` are SQLite
+/// `DATETIME`s in UTC, `label` is the `id` of a label the mails
+/// must have and `cursor` is the `next_cursor` of the previous page.
+///
+/// Only mails in the trash are listed if `trashed` is set,
+/// otherwise they are left out.
+#[derive(Serialize, Deserialize, Default)]
+pub struct MailListQuery {
+    pub status: Option<MailStatus>,
+    pub sender: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub label: Option<i64>,
+    #[serde(default)]
+    pub trashed: bool,
+
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+pub const SEARCH_QUERY_MAX_LEN: usize = 255; // In characters
+
+/// A full-text search over the mails of the
+/// currently authenticated user, with pagination.
+///
+/// `q` consists of terms which all have to match, i.e,
+/// - words, e.g, `invoice`, which also match with different diacritics
+/// - `"quoted phrases"`, whose words have to appear in order
+/// - prefixes, e.g, `inv*` or `"quoted phr"*`
+///
+/// Each of them may be scoped to a single field by prefixing it with
+/// `from:`, `to:`, `subject:` or `body:`, e.g, `from:alice subject:"q3 report"`.
+#[derive(Serialize, Deserialize, Default)]
+pub struct MailSearchQuery {
+    pub q: String,
+
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// Options for deleting a single mail.
+#[derive(Serialize, Deserialize, Default)]
+pub struct MailDeleteQuery {
+    /// Delete the mail for good instead of moving it to the trash.
+    #[serde(default)]
+    pub permanent: bool,
+}
+
+/// Options for fetching a single mail.
+#[derive(Serialize, Deserialize, Default)]
+pub struct MailGetQuery {
+    /// Fetch the mail without marking it as read.
+    #[serde(default)]
+    pub peek: bool,
+}
+
+/// Options for uploading an attachment.
+///
+/// The MIME type is taken from the `Content-Type` header
+/// of the upload and guessed by the server if it is missing.
+#[derive(Serialize, Deserialize, Default)]
+pub struct AttachmentUploadQuery {
+    pub filename: Option<String>,
+}
+
+/// Options for sending a draft.
+#[derive(Serialize, Deserialize, Default)]
+pub struct DraftSendQuery {
+    /// When to send the draft, an SQLite `DATETIME` in UTC, e.g,
+    /// `2026-01-31 08:00:00`. The draft is sent right away (or once the
+    /// undo window has passed) if it is `None`. A `send_at` that is not in
+    /// the future is rejected with a 422 (`FieldErrorKind::Invalid`).
+    pub send_at: Option<String>,
+}
@@ -1 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+pub mod mail;
 pub mod user;
+
+/// Pagination for listings without any filters.
+///
+/// `cursor` is the `next_cursor` of the previous page.
+#[derive(Serialize, Deserialize, Default)]
+pub struct PageQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}